@@ -114,7 +114,30 @@ impl Scope{
             self.tags(&mut ptr.tags);
         }
 
-
+        if let ast::Type::Instance(name, args) = &mut t.t {
+            for arg in args.iter_mut() {
+                self.abs(arg, inbody);
+            }
+            let mut generic = ast::Typed{
+                t:      ast::Type::Other(name.clone()),
+                loc:    t.loc.clone(),
+                ptr:    Vec::new(),
+                tail:   ast::Tail::None,
+            };
+            self.abs(&mut generic, inbody);
+            match generic.t {
+                ast::Type::Other(n) => {
+                    *name = n;
+                }
+                _ => {
+                    emit_error(format!("'{}' is not generic", name), &[
+                        (t.loc.clone(), "builtin types take no type arguments"),
+                    ]);
+                    ABORT.store(true, Ordering::Relaxed);
+                }
+            }
+            return;
+        }
 
         let name = match &mut t.t {
             ast::Type::Other(name) => name,
@@ -482,6 +505,29 @@ fn abs_expr(
             abs_expr(lhs, scope, inbody, all_modules, self_md_name);
             abs_expr(rhs, scope, inbody, all_modules, self_md_name);
         }
+        ast::Expression::Ternary {cond, then, otherwise,.. } => {
+            abs_expr(cond, scope, inbody, all_modules, self_md_name);
+            abs_expr(then, scope, inbody, all_modules, self_md_name);
+            abs_expr(otherwise, scope, inbody, all_modules, self_md_name);
+        }
+        ast::Expression::Closure {args, ret, body, ..} => {
+            for typed in args.iter_mut().map(|arg|&mut arg.typed).chain(ret.iter_mut().map(|ret|&mut ret.typed)) {
+                scope.abs(typed, false);
+                if let ast::Type::Other(ref mut name) = &mut typed.t {
+                    check_abs_available(name, &ast::Visibility::Object, all_modules, &typed.loc, self_md_name);
+                }
+            }
+            for arg in args.iter_mut() {
+                scope.tags(&mut arg.tags);
+            }
+            abs_block(body, scope, all_modules, self_md_name);
+        }
+        ast::Expression::Quantifier {var, range, body, ..} => {
+            scope.abs(&mut var.typed, false);
+            scope.tags(&mut var.tags);
+            abs_expr(range, scope, inbody, all_modules, self_md_name);
+            abs_expr(body, scope, inbody, all_modules, self_md_name);
+        }
     }
 }
 
@@ -508,7 +554,7 @@ fn abs_statement(
         ast::Statement::Unsafe(b2) => {
             abs_block(b2, &scope, all_modules, self_md_name);
         }
-        ast::Statement::For{e1,e2,e3, body} => {
+        ast::Statement::For{e1,e2,e3, invariants, decreases, body} => {
             abs_block(body, &scope, all_modules, self_md_name);
             for s in e1 {
                 abs_statement(s, scope, inbody, all_modules, self_md_name);
@@ -519,9 +565,26 @@ fn abs_statement(
             for s in e3 {
                 abs_statement(s, scope, inbody, all_modules, self_md_name);
             }
+            for s in invariants.iter_mut().chain(decreases.iter_mut()) {
+                abs_expr(s, scope, inbody, all_modules, self_md_name);
+            }
+        },
+        ast::Statement::While{expr, invariants, decreases, body} => {
+            abs_expr(expr, &scope, inbody, all_modules, self_md_name);
+            for s in invariants.iter_mut().chain(decreases.iter_mut()) {
+                abs_expr(s, &scope, inbody, all_modules, self_md_name);
+            }
+            abs_block(body, &scope, all_modules, self_md_name);
         },
-        ast::Statement::While{expr, body} => {
+        ast::Statement::ForIn{typed, expr, invariants, body, ..} => {
             abs_expr(expr, &scope, inbody, all_modules, self_md_name);
+            for s in invariants {
+                abs_expr(s, &scope, inbody, all_modules, self_md_name);
+            }
+            scope.abs(typed, false);
+            if let ast::Type::Other(ref mut name) = &mut typed.t {
+                check_abs_available(name, &ast::Visibility::Object, all_modules, &typed.loc, self_md_name);
+            }
             abs_block(body, &scope, all_modules, self_md_name);
         },
         ast::Statement::If{branches} => {
@@ -558,6 +621,9 @@ fn abs_statement(
                 abs_expr(expr, &scope, inbody, all_modules, self_md_name);
             }
         }
+        ast::Statement::Defer {stm, ..} | ast::Statement::Ghost {stm, ..} => {
+            abs_statement(stm, scope, inbody, all_modules, self_md_name);
+        }
         ast::Statement::Switch {expr, cases, default, ..} => {
             abs_expr(expr, &scope, inbody, all_modules, self_md_name);
             for (conds, block) in cases {
@@ -697,6 +763,16 @@ pub fn abs(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>, ex
                 }
                 scope.insert(ast.name.clone(), ns, &ast.loc, false, true);
             }
+            ast::Def::Struct{variants, ..} if !variants.is_empty() => {
+                // the tags of a tagged union are named like enum members
+                for variant in variants.iter() {
+                    let subname = format!("{}::{}", ast.name, variant.name);
+                    let mut ns = md.name.clone();
+                    ns.push(subname.clone());
+                    scope.insert(subname, ns, &variant.loc, false, false);
+                }
+                scope.insert(ast.name.clone(), ns, &ast.loc, false, true);
+            }
             _ => {
                 scope.insert(ast.name.clone(), ns, &ast.loc, false, false);
             }
@@ -722,8 +798,11 @@ pub fn abs(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>, ex
                     check_abs_available(name, &ast.vis, all_modules, &typed.loc, &md.name);
                 }
             }
-            ast::Def::Function{ret, args, ref mut body, callassert, calleffect, ..} => {
+            ast::Def::Function{ret, args, ref mut body, callassert, calleffect, decreases, modifies, generics, ..} => {
                 scope.push();
+                for generic in generics.iter() {
+                    scope.insert(generic.clone(), Name::from(generic), &ast.loc, false, false);
+                }
                 if let Some(ret) = ret {
                     scope.abs(&mut ret.typed, false);
                     if let ast::Type::Other(ref mut name) = &mut ret.typed.t{
@@ -737,9 +816,12 @@ pub fn abs(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>, ex
                 for calleffect in calleffect {
                     abs_expr(calleffect, &scope, true, all_modules, &md.name);
                 }
-                for callassert in callassert {
+                for callassert in callassert.iter_mut().chain(modifies.iter_mut()) {
                     abs_expr(callassert, &scope, true, all_modules, &md.name);
                 }
+                if let Some(decreases) = decreases {
+                    abs_expr(decreases, &scope, true, all_modules, &md.name);
+                }
                 abs_block(body, &scope,all_modules, &md.name);
                 scope.pop();
             }
@@ -769,7 +851,19 @@ pub fn abs(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>, ex
                     }
                 }
             }
-            ast::Def::Struct{fields,..} => {
+            ast::Def::Struct{fields, generics, invariants, ..} => {
+                scope.push();
+                for generic in generics.iter() {
+                    scope.insert(generic.clone(), Name::from(generic), &ast.loc, false, false);
+                }
+                if !invariants.is_empty() {
+                    scope.push();
+                    scope.insert("self".to_string(), Name::from("self"), &ast.loc, false, false);
+                    for invariant in invariants {
+                        abs_expr(invariant, &scope, true, all_modules, &md.name);
+                    }
+                    scope.pop();
+                }
 
                 let fieldslen = fields.len();
                 for (i, field) in fields.iter_mut().enumerate() {
@@ -795,6 +889,7 @@ pub fn abs(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>, ex
                         }
                     }
                 }
+                scope.pop();
             }
             ast::Def::Enum{..} => {
             }
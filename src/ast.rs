@@ -90,9 +90,14 @@ pub enum Def {
         vararg:     bool,
         callassert: Vec<Expression>,
         calleffect: Vec<Expression>,
+        decreases:  Option<Expression>,
+        // what the function may write through its arguments. empty means anything they reach
+        modifies:   Vec<Expression>,
 
         // never checked, only asserted into smt
         callattests: Vec<Expression>,
+        // type parameters. flatten emits one monomorphized copy per instance
+        generics:   Vec<String>,
     },
     Theory {
         ret:        Option<AnonArg>,
@@ -112,6 +117,13 @@ pub enum Def {
         tail:       Tail,
         union:      bool,
         impls:      HashMap<String, (Name, Location)>,
+        // tagged union. the first field is the tag, the others belong to one variant each
+        variants:   Vec<Variant>,
+        generics:   Vec<String>,
+        // declared with closure. the fields are the call fntype and the environment
+        closure:    bool,
+        // hold for every instance behind a pointer. self is the pointer
+        invariants: Vec<Expression>,
     },
     Enum {
         names:      Vec<(String, Option<u64>)>,
@@ -133,6 +145,23 @@ pub enum Def {
 }
 
 
+#[derive(Clone, Debug)]
+pub struct Variant {
+    pub name:       String,
+    pub fields:     Vec<String>,
+    pub loc:        Location,
+}
+
+impl Variant {
+    /// the tagged union a struct def is, if it is one
+    pub fn of(def: &Def) -> Option<&Vec<Variant>> {
+        match def {
+            Def::Struct{variants, ..} if !variants.is_empty() => Some(variants),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Local {
     pub name:       String,
@@ -187,8 +216,13 @@ pub enum Type {
     // untyped literal int,
     ULiteral,
     ILiteral,
+    // untyped literal float
+    FLiteral,
 
     Other(Name),
+
+    // a generic with type arguments. flatten replaces it with the name of the monomorphized copy
+    Instance(Name, Vec<Typed>),
 }
 
 impl Type {
@@ -206,7 +240,9 @@ impl Type {
             | Type::F32
             | Type::F64
             | Type::ULiteral
+            | Type::FLiteral
             | Type::Other(_)
+            | Type::Instance(_, _)
                 => false,
 
             Type::I8
@@ -260,8 +296,19 @@ impl std::fmt::Display for Typed{
             Type::F32           => write!(f, "f32"),
             Type::F64           => write!(f, "f64"),
             Type::ILiteral      => write!(f, "iliteral"),
+            Type::FLiteral      => write!(f, "fliteral"),
             Type::ULiteral      => write!(f, "uliteral"),
             Type::Other(name)   => write!(f, "{}", name),
+            Type::Instance(name, args) => {
+                write!(f, "{}<", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ">")
+            }
         }?;
 
         for _ in &self.ptr {
@@ -408,6 +455,31 @@ impl InfixOperator {
             => true,
         }
     }
+    pub fn takes_float(&self) -> bool {
+        match self {
+            InfixOperator::Equals
+            | InfixOperator::Nequals
+            | InfixOperator::Add
+            | InfixOperator::Subtract
+            | InfixOperator::Multiply
+            | InfixOperator::Divide
+            | InfixOperator::Moreeq
+            | InfixOperator::Lesseq
+            | InfixOperator::Lessthan
+            | InfixOperator::Morethan
+            => true,
+
+            InfixOperator::Booland
+            | InfixOperator::Boolor
+            | InfixOperator::Bitxor
+            | InfixOperator::Shiftleft
+            | InfixOperator::Shiftright
+            | InfixOperator::Modulo
+            | InfixOperator::Bitand
+            | InfixOperator::Bitor
+            => false,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -416,6 +488,7 @@ pub enum PrefixOperator {
     Bitnot,
     Increment,
     Decrement,
+    Negate,
     AddressOf,
     Deref,
 }
@@ -430,8 +503,14 @@ pub enum PostfixOperator {
 pub enum AssignOperator {
     Bitor,
     Bitand,
+    Bitxor,
     Add,
     Sub,
+    Mul,
+    Div,
+    Mod,
+    Shiftleft,
+    Shiftright,
     Eq,
 }
 
@@ -508,6 +587,28 @@ pub enum Expression {
         loc:        Location,
         fields:     Vec<Box<Expression>>,
     },
+    // cond ? then : otherwise
+    Ternary {
+        loc:        Location,
+        cond:       Box<Expression>,
+        then:       Box<Expression>,
+        otherwise:  Box<Expression>,
+    },
+    // closure (args) -> ret { body }, lowered to a function and an environment by expand
+    Closure {
+        loc:        Location,
+        args:       Vec<NamedArg>,
+        ret:        Option<AnonArg>,
+        body:       Block,
+    },
+    // forall (usize i; range) body, only for the prover
+    Quantifier {
+        loc:        Location,
+        forall:     bool,
+        var:        NamedArg,
+        range:      Box<Expression>,
+        body:       Box<Expression>,
+    },
 }
 
 impl Expression {
@@ -526,6 +627,9 @@ impl Expression {
             Expression::UnaryPre {loc,..}       => loc,
             Expression::StructInit {loc,..}     => loc,
             Expression::ArrayInit {loc,..}      => loc,
+            Expression::Ternary {loc,..}        => loc,
+            Expression::Closure {loc,..}        => loc,
+            Expression::Quantifier {loc,..}     => loc,
         }
     }
 }
@@ -569,6 +673,16 @@ pub enum Statement {
         loc:        Location,
         expr:       Option<Expression>,
     },
+    // a statement that only changes ghost state, marked by ghost::erase so it isn't emitted
+    Ghost {
+        loc:        Location,
+        stm:        Box<Statement>,
+    },
+    // removed by expand, which copies stm to every exit of the enclosing scope
+    Defer {
+        loc:        Location,
+        stm:        Box<Statement>,
+    },
     Var {
         loc:        Location,
         typed:      Typed,
@@ -579,17 +693,31 @@ pub enum Statement {
     },
     While {
         expr:       Expression,
+        invariants: Vec<Expression>,
+        decreases:  Option<Expression>,
         body:       Block,
     },
     For {
         e1:         Vec<Box<Statement>>,
         e2:         Option<Expression>,
         e3:         Vec<Box<Statement>>,
+        invariants: Vec<Expression>,
+        decreases:  Option<Expression>,
         body:       Block,
     },
     If {
         branches:   Vec<(Location, Option<Expression>, Block)>,
     },
+    // for (typed name in expr), lowered to an index loop by symbolic
+    ForIn {
+        loc:        Location,
+        typed:      Typed,
+        tags:       Tags,
+        name:       String,
+        expr:       Expression,
+        invariants: Vec<Expression>,
+        body:       Block,
+    },
     Block(Box<Block>),
     Unsafe(Box<Block>),
     CBlock{
@@ -0,0 +1,291 @@
+use super::ast;
+use super::loader;
+use super::name::Name;
+use std::collections::{HashMap, HashSet};
+use std::process::Command;
+
+/// a place where the proof trusts the programmer
+pub struct Assumption {
+    pub kind:       &'static str,
+    pub module:     Name,
+    pub function:   Option<String>,
+    pub loc:        ast::Location,
+}
+
+pub fn collect(modules: &HashMap<Name, loader::Module>) -> Vec<Assumption> {
+    let mut r = Vec::new();
+    for (name, module) in modules {
+        let module = match module {
+            loader::Module::ZZ(ast) => ast,
+            loader::Module::C(_) => continue,
+        };
+        for local in &module.locals {
+            let mut audit = Audit {
+                module:     name.clone(),
+                function:   Some(local.name.clone()),
+                r:          &mut r,
+            };
+            match &local.def {
+                ast::Def::Theory{..} => {
+                    audit.function = None;
+                    audit.push("theory", &local.loc);
+                }
+                ast::Def::Function{args, body, ..} => {
+                    for arg in args {
+                        if arg.tags.contains("unsafe") || arg.typed.ptr.iter().any(|p|p.tags.contains("unsafe")) {
+                            audit.push("unsafe argument", &arg.loc);
+                        }
+                    }
+                    audit.block(body);
+                }
+                ast::Def::Macro{body, ..} => {
+                    audit.block(body);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    r.sort_by(|a,b|(&a.module.0, &a.function, &a.loc.file, a.loc.span.start())
+        .cmp(&(&b.module.0, &b.function, &b.loc.file, b.loc.span.start())));
+    r
+}
+
+struct Audit<'a> {
+    module:     Name,
+    function:   Option<String>,
+    r:          &'a mut Vec<Assumption>,
+}
+
+impl<'a> Audit<'a> {
+    fn push(&mut self, kind: &'static str, loc: &ast::Location) {
+        self.r.push(Assumption{
+            kind,
+            module:     self.module.clone(),
+            function:   self.function.clone(),
+            loc:        loc.clone(),
+        });
+    }
+
+    fn block(&mut self, block: &ast::Block) {
+        for stm in &block.statements {
+            self.statement(stm);
+        }
+    }
+
+    fn statement(&mut self, stm: &ast::Statement) {
+        match stm {
+            ast::Statement::Unsafe(block) => {
+                let loc = block.statements.iter().find_map(|stm|statement_loc(stm)).unwrap_or(&block.end);
+                self.push("unsafe block", loc);
+                self.block(block);
+            }
+            ast::Statement::CBlock{loc, ..} => {
+                self.push("inline C", loc);
+            }
+            ast::Statement::Block(block) => self.block(block),
+            ast::Statement::Mark{lhs, ..} => self.expr(lhs),
+            ast::Statement::Assign{lhs, rhs, ..} => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ast::Statement::Expr{expr, ..} => self.expr(expr),
+            ast::Statement::Return{expr, ..} => {
+                if let Some(expr) = expr {
+                    self.expr(expr);
+                }
+            }
+            ast::Statement::Defer{stm, ..} | ast::Statement::Ghost{stm, ..} => self.statement(stm),
+            ast::Statement::Var{assign, ..} => {
+                if let Some(expr) = assign {
+                    self.expr(expr);
+                }
+            }
+            ast::Statement::Switch{expr, cases, default, ..} => {
+                self.expr(expr);
+                for (_, block) in cases {
+                    self.block(block);
+                }
+                if let Some(block) = default {
+                    self.block(block);
+                }
+            }
+            ast::Statement::While{expr, body, ..} | ast::Statement::ForIn{expr, body, ..} => {
+                self.expr(expr);
+                self.block(body);
+            }
+            ast::Statement::For{e1, e2, e3, body, ..} => {
+                for stm in e1.iter().chain(e3.iter()) {
+                    self.statement(stm);
+                }
+                if let Some(expr) = e2 {
+                    self.expr(expr);
+                }
+                self.block(body);
+            }
+            ast::Statement::If{branches} => {
+                for (_, expr, block) in branches {
+                    if let Some(expr) = expr {
+                        self.expr(expr);
+                    }
+                    self.block(block);
+                }
+            }
+            ast::Statement::Label{..} | ast::Statement::Continue{..} | ast::Statement::Break{..} => (),
+        }
+    }
+
+    fn expr(&mut self, expr: &ast::Expression) {
+        match expr {
+            ast::Expression::Call{loc, name, args, ..} => {
+                if let ast::Expression::Name(typed) = name.as_ref() {
+                    if let ast::Type::Other(n) = &typed.t {
+                        if n.0.last().map(|s|s.as_str()) == Some("static_attest") {
+                            self.push("static_attest", loc);
+                        }
+                    }
+                }
+                self.expr(name);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ast::Expression::MemberAccess{lhs, ..} => self.expr(lhs),
+            ast::Expression::ArrayAccess{lhs, rhs, ..} | ast::Expression::Infix{lhs, rhs, ..} => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ast::Expression::Cast{expr, ..}
+                | ast::Expression::UnaryPost{expr, ..}
+                | ast::Expression::UnaryPre{expr, ..} => self.expr(expr),
+            ast::Expression::StructInit{fields, ..} => {
+                for (_, expr) in fields {
+                    self.expr(expr);
+                }
+            }
+            ast::Expression::ArrayInit{fields, ..} => {
+                for expr in fields {
+                    self.expr(expr);
+                }
+            }
+            ast::Expression::Ternary{cond, then, otherwise, ..} => {
+                self.expr(cond);
+                self.expr(then);
+                self.expr(otherwise);
+            }
+            ast::Expression::Closure{args, body, ..} => {
+                for arg in args {
+                    if arg.tags.contains("unsafe") || arg.typed.ptr.iter().any(|p|p.tags.contains("unsafe")) {
+                        self.push("unsafe argument", &arg.loc);
+                    }
+                }
+                self.block(body);
+            }
+            ast::Expression::Quantifier{range, body, ..} => {
+                self.expr(range);
+                self.expr(body);
+            }
+            ast::Expression::Name(_)
+                | ast::Expression::Literal{..}
+                | ast::Expression::LiteralString{..}
+                | ast::Expression::LiteralChar{..} => (),
+        }
+    }
+}
+
+// blocks do not know where they start, so an unsafe block is reported at its first statement
+fn statement_loc(stm: &ast::Statement) -> Option<&ast::Location> {
+    match stm {
+        ast::Statement::Mark{loc, ..}
+            | ast::Statement::Label{loc, ..}
+            | ast::Statement::Assign{loc, ..}
+            | ast::Statement::Expr{loc, ..}
+            | ast::Statement::Switch{loc, ..}
+            | ast::Statement::Continue{loc}
+            | ast::Statement::Break{loc}
+            | ast::Statement::Return{loc, ..}
+            | ast::Statement::Defer{loc, ..}
+            | ast::Statement::Ghost{loc, ..}
+            | ast::Statement::Var{loc, ..}
+            | ast::Statement::CBlock{loc, ..} => Some(loc),
+        ast::Statement::While{expr, ..} => Some(expr.loc()),
+        ast::Statement::If{branches} => branches.first().map(|(loc,_,_)|loc),
+        _ => None,
+    }
+}
+
+/// lines of a file that were added or changed since a git revision.
+/// none if git knows nothing about the file, for example when it is in a dependency outside the repository
+pub fn added_lines(since: &str, file: &str) -> Option<HashSet<usize>> {
+    let known = Command::new("git").args(&["ls-files", "--error-unmatch", file]).output().ok()?;
+    if !known.status.success() {
+        return None;
+    }
+
+    let out = Command::new("git").args(&["diff", "-U0", since, "--", file]).output().ok()?;
+    if !out.status.success() {
+        error!("git diff {} failed: {}", since, String::from_utf8_lossy(&out.stderr));
+        std::process::exit(9);
+    }
+
+    // hunk headers look like @@ -12,3 +14,5 @@
+    let mut lines = HashSet::new();
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        if !line.starts_with("@@") {
+            continue;
+        }
+        let added = match line.split_whitespace().find(|w|w.starts_with('+')) {
+            Some(v) => &v[1..],
+            None => continue,
+        };
+        let mut added = added.split(',');
+        let start : usize = added.next().and_then(|v|v.parse().ok()).unwrap_or(0);
+        let len   : usize = added.next().and_then(|v|v.parse().ok()).unwrap_or(1);
+        lines.extend(start..start + len);
+    }
+    Some(lines)
+}
+
+pub fn print(assumptions: &[Assumption], since: Option<&str>) {
+    let mut added_cache : HashMap<String, Option<HashSet<usize>>> = HashMap::new();
+    let mut added = 0;
+
+    let mut module   = None;
+    let mut function = None;
+    for a in assumptions {
+        if module != Some(&a.module) {
+            module   = Some(&a.module);
+            function = None;
+            println!("\n{}", a.module);
+        }
+        if function != Some(&a.function) {
+            function = Some(&a.function);
+            if let Some(f) = &a.function {
+                println!("  fn {}", f);
+            }
+        }
+
+        let new = match since {
+            None => false,
+            Some(since) => {
+                let lines = added_cache.entry(a.loc.file.clone()).or_insert_with(||added_lines(since, &a.loc.file));
+                match lines {
+                    Some(lines) => lines.contains(&a.loc.line()),
+                    None => false,
+                }
+            }
+        };
+        if new {
+            added += 1;
+        }
+
+        let code = a.loc.span.start_pos().line_of().trim();
+        println!("  {} {:<16} {}  {}", if new { "+" } else { " " }, a.kind, a.loc, code);
+    }
+
+    println!();
+    match since {
+        Some(since) => println!("{} assumptions, {} added since {}", assumptions.len(), added, since),
+        None        => println!("{} assumptions", assumptions.len()),
+    }
+}
@@ -0,0 +1,832 @@
+use super::Error;
+use super::flatten;
+use crate::name::Name;
+use std::collections::{HashMap, HashSet};
+use crate::ast;
+use ast::Tags;
+
+// a closure literal becomes a function taking a pointer to its environment as first argument.
+// the environment is a struct of pointers to the captured locals, which lives in the scope of the call.
+// since the literal can only be passed directly into a call, and closure types cannot be stored
+// anywhere but in locals and arguments, the environment never outlives the locals it points to.
+
+#[derive(Clone)]
+struct Local {
+    typed:      ast::Typed,
+    tags:       Tags,
+    loc:        ast::Location,
+    array:      bool,
+}
+
+struct Lower<'a> {
+    defs:       &'a HashMap<Name, ast::Def>,
+    module:     Name,
+    function:   String,
+    count:      usize,
+    scopes:     Vec<HashMap<String, Local>>,
+    generated:  Vec<ast::Local>,
+}
+
+pub fn lower(module: &mut flatten::Module) -> Result<(), Error> {
+    let mut defs = HashMap::new();
+    for (d,_,_) in &module.d {
+        defs.insert(Name::from(&d.name), d.def.clone());
+    }
+
+    let mut i = 0;
+    while i < module.d.len() {
+        let (d, _, defined_here) = &mut module.d[i];
+        if !*defined_here {
+            i += 1;
+            continue;
+        }
+        check_escape(&defs, d)?;
+
+        let mut generated = Vec::new();
+        if let ast::Def::Function{args, body, ..} = &mut d.def {
+            let mut name = Name::from(&d.name);
+            let function = name.pop().unwrap();
+            let mut lower = Lower{
+                defs:       &defs,
+                module:     name,
+                function,
+                count:      0,
+                scopes:     vec![HashMap::new()],
+                generated:  Vec::new(),
+            };
+            for arg in args.iter() {
+                lower.declare(&arg.name, &arg.typed, &arg.tags, &arg.loc, false);
+            }
+            lower.block(&mut body.statements)?;
+            generated = lower.generated;
+        }
+
+        // declared before the function that creates them
+        let n = generated.len();
+        for (j, local) in generated.into_iter().enumerate() {
+            module.d.insert(i + j, (local, true, true));
+        }
+        i += n + 1;
+    }
+    Ok(())
+}
+
+fn is_closure(defs: &HashMap<Name, ast::Def>, typed: &ast::Typed) -> bool {
+    if let ast::Type::Other(name) = &typed.t {
+        if let Some(ast::Def::Struct{closure: true, ..}) = defs.get(name) {
+            return true;
+        }
+    }
+    false
+}
+
+// closures point into the stack of their creator, so they must not be stored anywhere that outlives a call
+fn check_escape(defs: &HashMap<Name, ast::Def>, d: &ast::Local) -> Result<(), Error> {
+    let escape = |typed: &ast::Typed, what: &str| {
+        if is_closure(defs, typed) {
+            Err(Error::new(format!("closure {} cannot escape", typed), vec![
+                (typed.loc.clone(), format!("closures cannot be {}", what)),
+            ]))
+        } else {
+            Ok(())
+        }
+    };
+    match &d.def {
+        ast::Def::Struct{fields, ..} => {
+            for field in fields {
+                escape(&field.typed, "stored in a struct")?;
+            }
+        }
+        ast::Def::Static{typed, ..} | ast::Def::Const{typed, ..} => {
+            escape(typed, "stored in a static")?;
+        }
+        ast::Def::Function{ret, args, ..} | ast::Def::Fntype{ret, args, ..} => {
+            if let Some(ret) = ret {
+                escape(&ret.typed, "returned")?;
+            }
+            for arg in args {
+                if !arg.typed.ptr.is_empty() {
+                    escape(&arg.typed, "used through a pointer")?;
+                }
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+// arguments that are written at the call site, as opposed to generated by the callee's tags
+fn explicit_args(args: &[ast::NamedArg]) -> Vec<ast::NamedArg> {
+    args.iter().filter(|arg|!arg.tags.contains("callsite_source") && !arg.tags.contains("tail")).cloned().collect()
+}
+
+fn name_expr(name: &Name, loc: &ast::Location) -> ast::Expression {
+    ast::Expression::Name(ast::Typed{
+        t:      ast::Type::Other(name.clone()),
+        ptr:    Vec::new(),
+        loc:    loc.clone(),
+        tail:   ast::Tail::None,
+    })
+}
+
+fn member_expr(lhs: ast::Expression, op: &str, rhs: &str, loc: &ast::Location) -> ast::Expression {
+    ast::Expression::MemberAccess{
+        loc:    loc.clone(),
+        lhs:    Box::new(lhs),
+        op:     op.to_string(),
+        rhs:    rhs.to_string(),
+    }
+}
+
+fn attest_safe(expr: ast::Expression, loc: &ast::Location) -> ast::Statement {
+    let safe = ast::Expression::Call{
+        loc:        loc.clone(),
+        name:       Box::new(name_expr(&Name::from("safe"), loc)),
+        args:       vec![Box::new(expr)],
+        expanded:   false,
+        emit:       ast::EmitBehaviour::Default,
+    };
+    ast::Statement::Expr{
+        loc:    loc.clone(),
+        expr:   ast::Expression::Call{
+            loc:        loc.clone(),
+            name:       Box::new(name_expr(&Name::from("static_attest"), loc)),
+            args:       vec![Box::new(safe)],
+            expanded:   false,
+            emit:       ast::EmitBehaviour::Default,
+        },
+    }
+}
+
+fn pointer_to(typed: &ast::Typed, tags: &Tags, loc: &ast::Location) -> ast::Typed {
+    let mut typed = typed.clone();
+    let mut ptrtags = Tags::new();
+    if tags.contains("mut") {
+        ptrtags.insert("mut".to_string(), String::new(), loc.clone());
+    }
+    typed.ptr.push(ast::Pointer{
+        tags:   ptrtags,
+        loc:    loc.clone(),
+    });
+    typed
+}
+
+impl<'a> Lower<'a> {
+    fn declare(&mut self, name: &str, typed: &ast::Typed, tags: &Tags, loc: &ast::Location, array: bool) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), Local{
+            typed:  typed.clone(),
+            tags:   tags.clone(),
+            loc:    loc.clone(),
+            array,
+        });
+    }
+
+    fn lookup(&self, name: &Name) -> Option<&Local> {
+        if name.len() != 1 {
+            return None;
+        }
+        self.scopes.iter().rev().find_map(|scope|scope.get(&name.0[0]))
+    }
+
+    fn block(&mut self, body: &mut Vec<Box<ast::Statement>>) -> Result<(), Error> {
+        self.scopes.push(HashMap::new());
+        let mut i = 0;
+        while i < body.len() {
+            let mut hoisted = Vec::new();
+            self.statement(&mut body[i], &mut hoisted)?;
+            let n = hoisted.len();
+            for (j, stm) in hoisted.into_iter().enumerate() {
+                body.insert(i + j, Box::new(stm));
+            }
+            i += n + 1;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn statement(&mut self, stm: &mut ast::Statement, hoisted: &mut Vec<ast::Statement>) -> Result<(), Error> {
+        match stm {
+            ast::Statement::Var{loc, typed, tags, name, array, assign} => {
+                if !typed.ptr.is_empty() && is_closure(self.defs, typed) {
+                    return Err(Error::new(format!("closure {} cannot escape", typed), vec![
+                        (loc.clone(), "closures cannot be used through a pointer".to_string()),
+                    ]));
+                }
+                // the environment is only ever created by a closure literal
+                if is_closure(self.defs, typed) && matches!(assign, None | Some(ast::Expression::ArrayInit{..})) {
+                    return Err(Error::new(format!("closure {} must be initialized from a closure", typed), vec![
+                        (loc.clone(), "closures can only be copied from another closure".to_string()),
+                    ]));
+                }
+                if let Some(Some(expr)) = array {
+                    self.expr(expr, hoisted)?;
+                }
+                if let Some(expr) = assign {
+                    self.expr(expr, hoisted)?;
+                }
+                self.declare(name, typed, tags, loc, array.is_some());
+            }
+            ast::Statement::Expr{expr, ..} | ast::Statement::Mark{lhs: expr, ..} => {
+                self.expr(expr, hoisted)?;
+            }
+            ast::Statement::Return{expr, ..} => {
+                if let Some(expr) = expr {
+                    self.expr(expr, hoisted)?;
+                }
+            }
+            ast::Statement::Assign{lhs, rhs, ..} => {
+                self.expr(lhs, hoisted)?;
+                self.expr(rhs, hoisted)?;
+            }
+            ast::Statement::If{branches} => {
+                for (_, expr, block) in branches {
+                    if let Some(expr) = expr {
+                        self.expr(expr, hoisted)?;
+                    }
+                    self.block(&mut block.statements)?;
+                }
+            }
+            ast::Statement::While{expr, body, ..} => {
+                self.expr(expr, hoisted)?;
+                self.block(&mut body.statements)?;
+            }
+            ast::Statement::For{e1, e2, e3, body, ..} => {
+                self.scopes.push(HashMap::new());
+                for stm in e1.iter_mut().chain(e3.iter_mut()) {
+                    self.statement(stm, hoisted)?;
+                }
+                if let Some(expr) = e2 {
+                    self.expr(expr, hoisted)?;
+                }
+                self.block(&mut body.statements)?;
+                self.scopes.pop();
+            }
+            ast::Statement::ForIn{loc, typed, tags, name, expr, body, ..} => {
+                self.expr(expr, hoisted)?;
+                self.scopes.push(HashMap::new());
+                self.declare(name, typed, tags, loc, false);
+                self.block(&mut body.statements)?;
+                self.scopes.pop();
+            }
+            ast::Statement::Switch{expr, cases, default, ..} => {
+                self.expr(expr, hoisted)?;
+                for block in cases.iter_mut().map(|(_, block)|block).chain(default.iter_mut()) {
+                    self.block(&mut block.statements)?;
+                }
+            }
+            ast::Statement::Block(block) | ast::Statement::Unsafe(block) => {
+                self.block(&mut block.statements)?;
+            }
+            ast::Statement::Defer{stm, ..} | ast::Statement::Ghost{stm, ..} => {
+                self.statement(stm, hoisted)?;
+            }
+            ast::Statement::Label{..}
+                | ast::Statement::Continue{..}
+                | ast::Statement::Break{..}
+                | ast::Statement::CBlock{..} => (),
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &mut ast::Expression, hoisted: &mut Vec<ast::Statement>) -> Result<(), Error> {
+        match expr {
+            ast::Expression::Call{name, args, ..} => {
+                self.expr(name, hoisted)?;
+                let params = self.callee_args(name);
+                for (i, arg) in args.iter_mut().enumerate() {
+                    if let ast::Expression::Closure{..} = arg.as_ref() {
+                        let param = params.as_ref().and_then(|params|params.get(i));
+                        **arg = self.closure(arg, param, hoisted)?;
+                    } else {
+                        self.expr(arg, hoisted)?;
+                    }
+                }
+            }
+            ast::Expression::Closure{loc, ..} => {
+                return Err(Error::new("closure cannot escape".to_string(), vec![
+                    (loc.clone(), "closures can only be passed directly as call arguments".to_string()),
+                ]));
+            }
+            ast::Expression::MemberAccess{lhs, loc, ..} => {
+                if let ast::Expression::Name(typed) = lhs.as_ref() {
+                    if let ast::Type::Other(n) = &typed.t {
+                        if let Some(local) = self.lookup(n) {
+                            if local.typed.ptr.is_empty() && is_closure(self.defs, &local.typed) {
+                                return Err(Error::new(format!("members of closure {} are not accessible", local.typed), vec![
+                                    (loc.clone(), "a closure can only be called".to_string()),
+                                ]));
+                            }
+                        }
+                    }
+                }
+                self.expr(lhs, hoisted)?;
+            }
+            ast::Expression::ArrayAccess{lhs, rhs, ..} | ast::Expression::Infix{lhs, rhs, ..} => {
+                self.expr(lhs, hoisted)?;
+                self.expr(rhs, hoisted)?;
+            }
+            ast::Expression::Cast{expr, ..}
+                | ast::Expression::UnaryPost{expr, ..}
+                | ast::Expression::UnaryPre{expr, ..} => {
+                self.expr(expr, hoisted)?;
+            }
+            ast::Expression::StructInit{typed, fields, loc} => {
+                if is_closure(self.defs, typed) {
+                    return Err(Error::new(format!("closure {} must be created by a closure literal", typed), vec![
+                        (loc.clone(), "the environment of a closure cannot be set by hand".to_string()),
+                    ]));
+                }
+                for (_, expr) in fields {
+                    self.expr(expr, hoisted)?;
+                }
+            }
+            ast::Expression::ArrayInit{fields, ..} => {
+                for expr in fields {
+                    self.expr(expr, hoisted)?;
+                }
+            }
+            ast::Expression::Ternary{cond, then, otherwise, ..} => {
+                self.expr(cond, hoisted)?;
+                self.expr(then, hoisted)?;
+                self.expr(otherwise, hoisted)?;
+            }
+            ast::Expression::Quantifier{range, body, ..} => {
+                self.expr(range, hoisted)?;
+                self.expr(body, hoisted)?;
+            }
+            ast::Expression::Name(_)
+                | ast::Expression::Literal{..}
+                | ast::Expression::LiteralString{..}
+                | ast::Expression::LiteralChar{..} => (),
+        }
+
+        // calling a closure calls its function with its environment
+        if let ast::Expression::Call{name, args, loc, ..} = expr {
+            if let ast::Expression::Name(typed) = name.as_ref() {
+                if let ast::Type::Other(n) = &typed.t {
+                    if let Some(local) = self.lookup(n) {
+                        if local.typed.ptr.is_empty() && is_closure(self.defs, &local.typed) {
+                            let closure = name_expr(n, &typed.loc);
+                            args.insert(0, Box::new(member_expr(closure.clone(), ".", "env", loc)));
+                            **name = member_expr(closure, ".", "call", loc);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // the arguments the callee expects, if it can be known before symbolic execution
+    fn callee_args(&self, name: &ast::Expression) -> Option<Vec<ast::NamedArg>> {
+        match name {
+            ast::Expression::Name(typed) => {
+                let n = match &typed.t {
+                    ast::Type::Other(n) => n,
+                    _ => return None,
+                };
+                let n = match self.lookup(n) {
+                    Some(local) => match &local.typed.t {
+                        ast::Type::Other(n) if local.typed.ptr.is_empty() => n,
+                        _ => return None,
+                    },
+                    None => n,
+                };
+                match self.defs.get(n)? {
+                    ast::Def::Function{args, ..} | ast::Def::Fntype{args, ..} => Some(explicit_args(args)),
+                    ast::Def::Struct{closure: true, fields, ..} => {
+                        if let ast::Type::Other(call) = &fields[0].typed.t {
+                            if let Some(ast::Def::Fntype{args, ..}) = self.defs.get(call) {
+                                return Some(explicit_args(&args[1..]));
+                            }
+                        }
+                        None
+                    }
+                    _ => None,
+                }
+            }
+            // self calls, as desugared by symbolic
+            ast::Expression::MemberAccess{lhs, rhs, ..} => {
+                let lhs = match lhs.as_ref() {
+                    ast::Expression::Name(ast::Typed{t: ast::Type::Other(n), ..}) => self.lookup(n)?,
+                    _ => return None,
+                };
+                let mut n = match &lhs.typed.t {
+                    ast::Type::Other(n) => n.clone(),
+                    _ => return None,
+                };
+                n.pop();
+                n.push(rhs.clone());
+                match self.defs.get(&n)? {
+                    ast::Def::Function{args, ..} if !args.is_empty() => Some(explicit_args(&args[1..])),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn closure(
+        &mut self,
+        expr:       &mut ast::Expression,
+        param:      Option<&ast::NamedArg>,
+        hoisted:    &mut Vec<ast::Statement>,
+    ) -> Result<ast::Expression, Error> {
+        let (loc, args, ret, mut body) = match std::mem::replace(expr, ast::Expression::Literal{
+            loc:    ast::Location::builtin(),
+            v:      String::new(),
+        }) {
+            ast::Expression::Closure{loc, args, ret, body} => (loc, args, ret, body),
+            _ => unreachable!(),
+        };
+
+        let param = match param {
+            Some(param) if param.typed.ptr.is_empty() && is_closure(self.defs, &param.typed) => param,
+            Some(param) => {
+                return Err(Error::new("closure passed where no closure is expected".to_string(), vec![
+                    (loc.clone(), "this closure".to_string()),
+                    (param.loc.clone(), format!("is passed as {}", param.typed)),
+                ]));
+            }
+            None => {
+                return Err(Error::new("cannot infer the type of closure".to_string(), vec![
+                    (loc.clone(), "closures can only be passed to functions called by name".to_string()),
+                ]));
+            }
+        };
+
+        // the literal must match the signature of the closure type
+        let mut closure_typed = param.typed.clone();
+        closure_typed.loc = loc.clone();
+        let (fargs, fret) = match self.defs.get(match &param.typed.t {
+            ast::Type::Other(n) => n,
+            _ => unreachable!(),
+        }) {
+            Some(ast::Def::Struct{fields, ..}) => match &fields[0].typed.t {
+                ast::Type::Other(call) => match self.defs.get(call) {
+                    Some(ast::Def::Fntype{args, ret, ..}) => (args[1..].to_vec(), ret.clone()),
+                    _ => unreachable!("ICE: closure without call fntype"),
+                },
+                _ => unreachable!("ICE: closure without call fntype"),
+            },
+            _ => unreachable!(),
+        };
+        let args_match = fargs.len() == args.len() && fargs.iter().zip(args.iter()).all(|(a,b)|a.typed == b.typed);
+        let ret_match = match (&fret, &ret) {
+            (None, None) => true,
+            (Some(a), Some(b)) => a.typed == b.typed,
+            _ => false,
+        };
+        if !args_match || !ret_match {
+            let sig = |args: &[ast::NamedArg], ret: &Option<ast::AnonArg>| {
+                let args : Vec<String> = args.iter().map(|arg|format!("{} {}", arg.typed, arg.name)).collect();
+                match ret {
+                    Some(ret) => format!("({}) -> {}", args.join(", "), ret.typed),
+                    None => format!("({})", args.join(", ")),
+                }
+            };
+            return Err(Error::new(format!("closure does not match the signature of {}", param.typed), vec![
+                (loc.clone(), format!("this closure is {}", sig(&args, &ret))),
+                (param.loc.clone(), format!("but {} is {}", param.typed, sig(&fargs, &fret))),
+            ]));
+        }
+
+        // lower nested closures and calls first, so their captures become captures of this closure
+        self.scopes.push(HashMap::new());
+        for arg in &args {
+            self.declare(&arg.name, &arg.typed, &arg.tags, &arg.loc, false);
+        }
+        self.block(&mut body.statements)?;
+        self.scopes.pop();
+
+        let mut capture = Capture{
+            outer:      &self.scopes,
+            inner:      vec![args.iter().map(|arg|arg.name.clone()).collect()],
+            captured:   Vec::new(),
+        };
+        capture.block(&mut body.statements)?;
+        let captured = capture.captured;
+
+        let id = format!("{}_{}", self.function, self.count);
+        self.count += 1;
+        let mut fnname = self.module.clone();
+        fnname.push(format!("_zz_closure_{}", id));
+
+        let env = if captured.is_empty() {
+            ast::Expression::Literal{
+                loc:    loc.clone(),
+                v:      "0".to_string(),
+            }
+        } else {
+            let mut envname = self.module.clone();
+            envname.push(format!("_zz_closure_env_{}", id));
+            let envtyped = ast::Typed{
+                t:      ast::Type::Other(envname.clone()),
+                ptr:    Vec::new(),
+                loc:    loc.clone(),
+                tail:   ast::Tail::None,
+            };
+
+            self.generated.push(ast::Local{
+                name:   envname.to_string(),
+                vis:    ast::Visibility::Object,
+                loc:    loc.clone(),
+                def:    ast::Def::Struct{
+                    fields: captured.iter().map(|(name, local)|ast::Field{
+                        typed:  pointer_to(&local.typed, &local.tags, &loc),
+                        name:   name.clone(),
+                        array:  None,
+                        tags:   Tags::new(),
+                        loc:    loc.clone(),
+                    }).collect(),
+                    packed:     false,
+                    tail:       ast::Tail::None,
+                    union:      false,
+                    impls:      HashMap::new(),
+                    variants:   Vec::new(),
+                    generics:   Vec::new(),
+                    closure:    false,
+                    invariants: Vec::new(),
+                },
+            });
+
+            // the function casts its environment back and trusts it, since only the literal can create it
+            let mut envmut = Tags::new();
+            envmut.insert("mut".to_string(), String::new(), loc.clone());
+            let envptr = pointer_to(&envtyped, &envmut, &loc);
+            let mut prefix = vec![
+                ast::Statement::Var{
+                    loc:    loc.clone(),
+                    typed:  envptr.clone(),
+                    tags:   Tags::new(),
+                    name:   "_zz_env".to_string(),
+                    array:  None,
+                    assign: Some(ast::Expression::Cast{
+                        loc:    loc.clone(),
+                        into:   envptr,
+                        expr:   Box::new(name_expr(&Name::from("_zz_closure_env"), &loc)),
+                    }),
+                },
+                attest_safe(name_expr(&Name::from("_zz_env"), &loc), &loc),
+            ];
+            for (name, _) in &captured {
+                prefix.push(attest_safe(member_expr(name_expr(&Name::from("_zz_env"), &loc), "->", name, &loc), &loc));
+            }
+            body.statements.splice(0..0, prefix.into_iter().map(Box::new));
+
+            let local = format!("_zz_env_{}", id);
+            hoisted.push(ast::Statement::Var{
+                loc:    loc.clone(),
+                typed:  envtyped.clone(),
+                tags:   envmut.clone(),
+                name:   local.clone(),
+                array:  None,
+                assign: Some(ast::Expression::StructInit{
+                    loc:    loc.clone(),
+                    typed:  envtyped,
+                    fields: captured.iter().map(|(name, _)|(name.clone(), Box::new(ast::Expression::UnaryPre{
+                        loc:    loc.clone(),
+                        op:     ast::PrefixOperator::AddressOf,
+                        expr:   Box::new(name_expr(&Name::from(name.as_str()), &loc)),
+                    }))).collect(),
+                }),
+            });
+            ast::Expression::UnaryPre{
+                loc:    loc.clone(),
+                op:     ast::PrefixOperator::AddressOf,
+                expr:   Box::new(name_expr(&Name::from(local.as_str()), &loc)),
+            }
+        };
+
+        let mut envtags = Tags::new();
+        envtags.insert("unsafe".to_string(), String::new(), loc.clone());
+        let mut voidmut = Tags::new();
+        voidmut.insert("mut".to_string(), String::new(), loc.clone());
+        let mut fargs = vec![ast::NamedArg{
+            name:   "_zz_closure_env".to_string(),
+            typed:  pointer_to(&ast::Typed{
+                t:      ast::Type::Other(Name::from("void")),
+                ptr:    Vec::new(),
+                loc:    loc.clone(),
+                tail:   ast::Tail::None,
+            }, &voidmut, &loc),
+            tags:   envtags,
+            loc:    loc.clone(),
+        }];
+        fargs.extend(args);
+
+        self.generated.push(ast::Local{
+            name:   fnname.to_string(),
+            vis:    ast::Visibility::Object,
+            loc:    loc.clone(),
+            def:    ast::Def::Function{
+                nameloc:    loc.clone(),
+                ret,
+                args:       fargs,
+                hints:      HashMap::new(),
+                attr:       HashMap::new(),
+                body,
+                vararg:     false,
+                callassert: Vec::new(),
+                calleffect: Vec::new(),
+                decreases:  None,
+                modifies:   Vec::new(),
+                callattests: Vec::new(),
+                generics:   Vec::new(),
+            },
+        });
+
+        Ok(ast::Expression::StructInit{
+            loc:    loc.clone(),
+            typed:  closure_typed,
+            fields: vec![
+                ("call".to_string(), Box::new(name_expr(&fnname, &loc))),
+                ("env".to_string(),  Box::new(env)),
+            ],
+        })
+    }
+}
+
+// rewrites every use of a local from outside the closure into a deref of its environment
+struct Capture<'a> {
+    outer:      &'a Vec<HashMap<String, Local>>,
+    inner:      Vec<HashSet<String>>,
+    captured:   Vec<(String, Local)>,
+}
+
+impl<'a> Capture<'a> {
+    fn block(&mut self, body: &mut Vec<Box<ast::Statement>>) -> Result<(), Error> {
+        self.inner.push(HashSet::new());
+        for stm in body {
+            self.statement(stm)?;
+        }
+        self.inner.pop();
+        Ok(())
+    }
+
+    fn statement(&mut self, stm: &mut ast::Statement) -> Result<(), Error> {
+        match stm {
+            ast::Statement::Var{name, array, assign, ..} => {
+                if let Some(Some(expr)) = array {
+                    self.expr(expr)?;
+                }
+                if let Some(expr) = assign {
+                    self.expr(expr)?;
+                }
+                self.inner.last_mut().unwrap().insert(name.clone());
+            }
+            ast::Statement::Expr{expr, ..} | ast::Statement::Mark{lhs: expr, ..} => {
+                self.expr(expr)?;
+            }
+            ast::Statement::Return{expr, ..} => {
+                if let Some(expr) = expr {
+                    self.expr(expr)?;
+                }
+            }
+            ast::Statement::Assign{lhs, rhs, ..} => {
+                self.expr(lhs)?;
+                self.expr(rhs)?;
+            }
+            ast::Statement::If{branches} => {
+                for (_, expr, block) in branches {
+                    if let Some(expr) = expr {
+                        self.expr(expr)?;
+                    }
+                    self.block(&mut block.statements)?;
+                }
+            }
+            ast::Statement::While{expr, invariants, decreases, body} => {
+                self.expr(expr)?;
+                for expr in invariants.iter_mut().chain(decreases.iter_mut()) {
+                    self.expr(expr)?;
+                }
+                self.block(&mut body.statements)?;
+            }
+            ast::Statement::For{e1, e2, e3, invariants, decreases, body} => {
+                self.inner.push(HashSet::new());
+                for stm in e1.iter_mut() {
+                    self.statement(stm)?;
+                }
+                for expr in e2.iter_mut().chain(invariants.iter_mut()).chain(decreases.iter_mut()) {
+                    self.expr(expr)?;
+                }
+                for stm in e3.iter_mut() {
+                    self.statement(stm)?;
+                }
+                self.block(&mut body.statements)?;
+                self.inner.pop();
+            }
+            ast::Statement::ForIn{name, expr, invariants, body, ..} => {
+                self.expr(expr)?;
+                self.inner.push(HashSet::new());
+                self.inner.last_mut().unwrap().insert(name.clone());
+                for expr in invariants {
+                    self.expr(expr)?;
+                }
+                self.block(&mut body.statements)?;
+                self.inner.pop();
+            }
+            ast::Statement::Switch{expr, cases, default, ..} => {
+                self.expr(expr)?;
+                for (conds, block) in cases.iter_mut() {
+                    for expr in conds {
+                        self.expr(expr)?;
+                    }
+                    self.block(&mut block.statements)?;
+                }
+                if let Some(block) = default {
+                    self.block(&mut block.statements)?;
+                }
+            }
+            ast::Statement::Block(block) | ast::Statement::Unsafe(block) => {
+                self.block(&mut block.statements)?;
+            }
+            ast::Statement::Defer{stm, ..} | ast::Statement::Ghost{stm, ..} => {
+                self.statement(stm)?;
+            }
+            ast::Statement::Label{..}
+                | ast::Statement::Continue{..}
+                | ast::Statement::Break{..}
+                | ast::Statement::CBlock{..} => (),
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &mut ast::Expression) -> Result<(), Error> {
+        match expr {
+            ast::Expression::Name(typed) => {
+                let name = match &typed.t {
+                    ast::Type::Other(n) if n.len() == 1 => n.0[0].clone(),
+                    _ => return Ok(()),
+                };
+                if self.inner.iter().any(|scope|scope.contains(&name)) {
+                    return Ok(());
+                }
+                let local = match self.outer.iter().rev().find_map(|scope|scope.get(&name)) {
+                    Some(local) => local,
+                    None => return Ok(()),
+                };
+                if local.array {
+                    return Err(Error::new(format!("cannot capture array '{}' in a closure", name), vec![
+                        (typed.loc.clone(), "captured here".to_string()),
+                        (local.loc.clone(), "declared here".to_string()),
+                    ]));
+                }
+                if !self.captured.iter().any(|(n,_)|n == &name) {
+                    self.captured.push((name.clone(), local.clone()));
+                }
+                let loc = typed.loc.clone();
+                *expr = ast::Expression::UnaryPre{
+                    loc:    loc.clone(),
+                    op:     ast::PrefixOperator::Deref,
+                    expr:   Box::new(member_expr(name_expr(&Name::from("_zz_env"), &loc), "->", &name, &loc)),
+                };
+            }
+            ast::Expression::Call{name, args, ..} => {
+                self.expr(name)?;
+                for arg in args {
+                    self.expr(arg)?;
+                }
+            }
+            ast::Expression::MemberAccess{lhs, ..} => {
+                self.expr(lhs)?;
+            }
+            ast::Expression::ArrayAccess{lhs, rhs, ..} | ast::Expression::Infix{lhs, rhs, ..} => {
+                self.expr(lhs)?;
+                self.expr(rhs)?;
+            }
+            ast::Expression::Cast{expr, ..}
+                | ast::Expression::UnaryPost{expr, ..}
+                | ast::Expression::UnaryPre{expr, ..} => {
+                self.expr(expr)?;
+            }
+            ast::Expression::StructInit{fields, ..} => {
+                for (_, expr) in fields {
+                    self.expr(expr)?;
+                }
+            }
+            ast::Expression::ArrayInit{fields, ..} => {
+                for expr in fields {
+                    self.expr(expr)?;
+                }
+            }
+            ast::Expression::Ternary{cond, then, otherwise, ..} => {
+                self.expr(cond)?;
+                self.expr(then)?;
+                self.expr(otherwise)?;
+            }
+            ast::Expression::Quantifier{var, range, body, ..} => {
+                self.inner.push(HashSet::new());
+                self.inner.last_mut().unwrap().insert(var.name.clone());
+                let r = self.expr(range).and_then(|_|self.expr(body));
+                self.inner.pop();
+                r?;
+            }
+            ast::Expression::Closure{..}
+                | ast::Expression::Literal{..}
+                | ast::Expression::LiteralString{..}
+                | ast::Expression::LiteralChar{..} => (),
+        }
+        Ok(())
+    }
+}
@@ -3,8 +3,9 @@ use std::fs;
 use super::flatten;
 use super::ast;
 use super::make;
+use super::symbolic;
 use std::io::{Write, Read};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use super::name::Name;
 use super::parser::{self, emit_error};
@@ -20,13 +21,17 @@ pub struct CFile {
 pub struct Emitter{
     cxx:            bool,
     p:              String,
-    f:              fs::File,
+    out:            fs::File,
+    f:              Vec<u8>,
     module:         flatten::Module,
     header:         bool,
     inside_macro:   bool,
     cur_loc:        Option<ast::Location>,
     casedir:        String,
     emit_as_extern: HashSet<Name>,
+    counterexample: Option<symbolic::Counterexample>,
+    // C emitted for each function body, for the proof cache
+    bodies:         HashMap<String, String>,
 }
 
 pub fn outname(project: &Project, stage: &make::Stage, module: &flatten::Module, header: bool) -> (bool, String) {
@@ -52,13 +57,46 @@ impl Emitter {
     pub fn new(project: &Project, stage: make::Stage , module: flatten::Module, header: bool) -> Self {
 
         let (cxx, p) = outname(project, &stage, &module, header);
-        let mut f = fs::File::create(&p).expect(&format!("cannot create {}", p));
+        let f = fs::File::create(&p).expect(&format!("cannot create {}", p));
 
         let casedir = format!("target/{}/testcases/{}", stage, module.name);
         std::fs::remove_dir_all(&casedir).ok();
         std::fs::create_dir_all(&casedir).unwrap();
 
+        Self::with_file(cxx, p, f, header, casedir, module, None)
+    }
+
+    /// emit the module together with a main() that calls the failed function with the counterexample
+    pub fn new_counterexample(project: &Project, stage: make::Stage, module: flatten::Module, cx: symbolic::Counterexample) -> Self {
+        let (cxx, _) = outname(project, &stage, &module, false);
+
+        let dir = format!("target/{}/counterexamples", stage);
+        let mut fname = cx.function.clone();
+        fname.0.remove(0);
+        let p = format!("{}/{}.{}", dir, fname.0.join("_"), if cxx { "cpp" } else { "c" });
+
+        let casedir = format!("{}/testcases/{}", dir, module.name);
+        std::fs::create_dir_all(&casedir).unwrap();
+
+        let mut f = fs::File::create(&p).expect(&format!("cannot create {}", p));
+        write!(f, "// counterexample for {}\n", cx.function).unwrap();
+        write!(f, "// {} at {}:{}\n", cx.message, cx.loc.file, cx.loc.line()).unwrap();
+        write!(f, "// build with the other modules in target/{}/zz, for example with -g -fsanitize=address\n", stage).unwrap();
+        write!(f, "#define main zz_counterexample_main\n").unwrap();
+
+        Self::with_file(cxx, p, f, false, casedir, module, Some(cx))
+    }
 
+    fn with_file(
+        cxx:        bool,
+        p:          String,
+        out:        fs::File,
+        header:     bool,
+        casedir:    String,
+        module:     flatten::Module,
+        counterexample: Option<symbolic::Counterexample>,
+    ) -> Self {
+        let mut f = Vec::new();
         write!(f, "#include <stdint.h>\n").unwrap();
         write!(f, "#include <stddef.h>\n").unwrap();
         write!(f, "#include <stdbool.h>\n").unwrap();
@@ -66,6 +104,7 @@ impl Emitter {
         Emitter{
             cxx,
             p,
+            out,
             f,
             header,
             casedir,
@@ -73,6 +112,8 @@ impl Emitter {
             inside_macro: false,
             cur_loc: None,
             emit_as_extern: HashSet::new(),
+            counterexample,
+            bodies: HashMap::new(),
         }
     }
 
@@ -124,13 +165,20 @@ impl Emitter {
                 }
                 s
             }
-            ast::Type::ILiteral | ast::Type::ULiteral | ast::Type::Elided => {
+            ast::Type::ILiteral | ast::Type::ULiteral | ast::Type::FLiteral | ast::Type::Elided => {
                 parser::emit_error(
                     "ICE: untyped literal ended up in emitter",
                     &[(name.loc.clone(), format!("this should have been resolved earlier"))]
                     );
                 std::process::exit(9);
             }
+            ast::Type::Instance(_, _) => {
+                parser::emit_error(
+                    "ICE: generic instance ended up in emitter",
+                    &[(name.loc.clone(), format!("this should have been monomorphized by flatten"))]
+                    );
+                std::process::exit(9);
+            }
         }
     }
     fn to_local_name(&self, s: &Name) -> String {
@@ -163,7 +211,12 @@ impl Emitter {
         return s.0.join("_");
     }
 
-    pub fn emit(mut self) -> CFile {
+    pub fn emit(self) -> CFile {
+        self.emit_with_bodies().0
+    }
+
+    /// emit, and return the C of every function body that was emitted
+    pub fn emit_with_bodies(mut self) -> (CFile, HashMap<String, String>) {
         let module = self.module.clone();
         debug!("emitting {}", module.name);
 
@@ -222,8 +275,8 @@ impl Emitter {
                 ast::Def::Testcase {..} => {
                     self.emit_testcase(&d);
                 }
-                ast::Def::Function{..} => {
-                    if !d.name.ends_with("::main") {
+                ast::Def::Function{ref attr, ..} => {
+                    if !d.name.ends_with("::main") && !attr.contains_key("lemma") {
                         self.emit_decl(&d);
                     }
                 }
@@ -256,10 +309,10 @@ impl Emitter {
                     continue
                 }
                 match d.def {
-                    ast::Def::Function{..} => {
+                    ast::Def::Function{ref attr, ..} => {
                         let mut name = Name::from(&d.name);
                         name.pop();
-                        if name == module.name {
+                        if name == module.name && !attr.contains_key("lemma") {
                             self.emit_def(&d);
                         }
                     }
@@ -268,14 +321,19 @@ impl Emitter {
             }
         }
 
+        if let Some(cx) = self.counterexample.take() {
+            self.emit_counterexample(&cx);
+        }
+
 
+        self.out.write_all(&self.f).unwrap_or_else(|_| panic!("cannot write {}", self.p));
 
-        CFile {
+        (CFile {
             name:       module.name,
             filepath:   self.p,
             sources:    module.sources,
             deps:       module.deps,
-        }
+        }, self.bodies)
     }
 
 
@@ -526,12 +584,19 @@ impl Emitter {
     }
 
     pub fn emit_struct(&mut self, ast: &ast::Local, def_here: bool, tail_variant: Option<u64>) {
-        let (fields, packed, _tail, union) = match &ast.def {
-            ast::Def::Struct{fields, packed, tail, union, ..} => (fields, packed, tail, union),
+        let (fields, packed, _tail, union, variants) = match &ast.def {
+            ast::Def::Struct{fields, packed, tail, union, variants, ..} => (fields, packed, tail, union, variants),
             _ => unreachable!(),
         };
 
         self.emit_loc(&ast.loc);
+        if !variants.is_empty() {
+            write!(self.f, "enum {{\n").unwrap();
+            for (i, variant) in variants.iter().enumerate() {
+                write!(self.f, "    {}_{} = {},\n", self.to_local_name(&Name::from(&ast.name)), variant.name, i).unwrap();
+            }
+            write!(self.f, "}};\n").unwrap();
+        }
         if *union {
             write!(self.f, "union ").unwrap();
         } else {
@@ -546,37 +611,60 @@ impl Emitter {
         let mut emitted_tail = false;
         for i in 0..fields.len() {
             let field = &fields[i];
-            self.emit_loc(&field.loc);
-            write!(self.f, "   {}", self.to_local_typed_name(&field.typed)).unwrap();
-            self.emit_pointer(&field.typed.ptr);
-            if let Some(array) = &field.array {
-                if let Some(expr) = array {
-                    write!(self.f, " {}", field.name).unwrap();
-                    write!(self.f, "[").unwrap();
-                    self.emit_expr(expr);
-                    write!(self.f, "]").unwrap();
-                } else {
-                    if i != (fields.len() - 1) {
-                        parser::emit_error(
-                            "tail field has no be the last field in a struct",
-                            &[(field.loc.clone(), format!("tail field would displace next field"))]
-                            );
-                        std::process::exit(9);
-                    }
-                    if let Some(tt) = tail_variant {
-                        emitted_tail = true;
-                        write!(self.f, " {}[{}]", field.name, tt).unwrap();
-                    } else {
 
-                        //TODO emit as something else (not as pointer!)
-                        // nested flexible arrays are non standard
-                        write!(self.f, " {}[]", field.name).unwrap();
+            // the payloads of a tagged union overlap in an anonymous union, so they're accessed by their own name
+            if !variants.is_empty() {
+                if i == 1 {
+                    write!(self.f, "   union {{\n").unwrap();
+                }
+                if let Some(variant) = variants.iter().find(|v|v.fields.first() == Some(&field.name)) {
+                    write!(self.f, "   struct {{ /* {} */\n", variant.name).unwrap();
+                }
+            }
+
+            // ghost fields only exist in the prover
+            if !field.tags.contains("ghost") {
+                self.emit_loc(&field.loc);
+                write!(self.f, "   {}", self.to_local_typed_name(&field.typed)).unwrap();
+                self.emit_pointer(&field.typed.ptr);
+                if let Some(array) = &field.array {
+                    if let Some(expr) = array {
+                        write!(self.f, " {}", field.name).unwrap();
+                        write!(self.f, "[").unwrap();
+                        self.emit_expr(expr);
+                        write!(self.f, "]").unwrap();
+                    } else {
+                        if i != (fields.len() - 1) {
+                            parser::emit_error(
+                                "tail field has no be the last field in a struct",
+                                &[(field.loc.clone(), format!("tail field would displace next field"))]
+                                );
+                            std::process::exit(9);
+                        }
+                        if let Some(tt) = tail_variant {
+                            emitted_tail = true;
+                            write!(self.f, " {}[{}]", field.name, tt).unwrap();
+                        } else {
+
+                            //TODO emit as something else (not as pointer!)
+                            // nested flexible arrays are non standard
+                            write!(self.f, " {}[]", field.name).unwrap();
+                        }
                     }
+                } else {
+                    write!(self.f, " {}", field.name).unwrap();
+                }
+                write!(self.f, " ;\n").unwrap();
+            }
+
+            if !variants.is_empty() {
+                if variants.iter().any(|v|v.fields.last() == Some(&field.name)) {
+                    write!(self.f, "   }};\n").unwrap();
+                }
+                if i > 0 && i == fields.len() - 1 {
+                    write!(self.f, "   }};\n").unwrap();
                 }
-            } else {
-                write!(self.f, " {}", field.name).unwrap();
             }
-            write!(self.f, " ;\n").unwrap();
         }
         if let Some(tt) = tail_variant {
             if !emitted_tail {
@@ -848,13 +936,20 @@ impl Emitter {
             write!(self.f, ", ...").unwrap();
         }
         write!(self.f, ")\n").unwrap();
-        self.emit_zblock(&body, true);
+
+        writeln!(self.f, "{{").unwrap();
+        let start = self.f.len();
+        self.emit_zblock(&body, false);
+        let c = String::from_utf8_lossy(&self.f[start..]).into_owned();
+        writeln!(self.f, "\n}}").unwrap();
+        self.bodies.insert(ast.name.clone(), c);
         write!(self.f, "\n").unwrap();
     }
 
     fn emit_statement(&mut self, stm: &ast::Statement) -> bool /* ends with semicolon */ {
         match stm {
             ast::Statement::Mark{..} => {false},
+            ast::Statement::Ghost{..} => {false},
             ast::Statement::Break{loc} => {
                 self.emit_loc(&loc);
                 write!(self.f, "break").unwrap();
@@ -883,7 +978,7 @@ impl Emitter {
                 self.emit_zblock(b2, true);
                 false
             }
-            ast::Statement::For{e1, e2, e3, body}  => {
+            ast::Statement::For{e1, e2, e3, body, ..}  => {
                 write!(self.f, "  for (").unwrap();
                 let mut first = true;
                 for expr in e1 {
@@ -924,7 +1019,7 @@ impl Emitter {
                 self.emit_zblock(body, true);
                 false
             },
-            ast::Statement::While{expr, body}  => {
+            ast::Statement::While{expr, body, ..}  => {
                 write!(self.f, "while (").unwrap();
                 self.emit_expr(expr);
                 write!(self.f, ")").unwrap();
@@ -963,8 +1058,14 @@ impl Emitter {
                 write!(self.f, " {} ", match op {
                     ast::AssignOperator::Bitor  => "|=",
                     ast::AssignOperator::Bitand => "&=",
+                    ast::AssignOperator::Bitxor => "^=",
                     ast::AssignOperator::Add    => "+=",
                     ast::AssignOperator::Sub    => "-=",
+                    ast::AssignOperator::Mul    => "*=",
+                    ast::AssignOperator::Div    => "/=",
+                    ast::AssignOperator::Mod    => "%=",
+                    ast::AssignOperator::Shiftleft  => "<<=",
+                    ast::AssignOperator::Shiftright => ">>=",
                     ast::AssignOperator::Eq     => "=" ,
                 }).unwrap();
                 self.emit_expr(rhs);
@@ -1014,6 +1115,8 @@ impl Emitter {
                 }
                 true
             }
+            ast::Statement::Defer{..} => unreachable!("ICE: defer is removed by expand"),
+            ast::Statement::ForIn{..} => unreachable!("ICE: ranged for is lowered by expand"),
             ast::Statement::Switch{loc, expr, cases, default}  => {
                 self.emit_loc(&loc);
                 write!(self.f, "switch (\n").unwrap();
@@ -1069,6 +1172,96 @@ impl Emitter {
         }
     }
 
+    fn emit_counterexample(&mut self, cx: &symbolic::Counterexample) {
+        let mut callee = cx.function.0[1..].join("_");
+        for (d,_,_) in &self.module.d {
+            if let ast::Def::Function{attr, ..} = &d.def {
+                if Name::from(&d.name) != cx.function {
+                    continue;
+                }
+                if d.name.ends_with("::main") {
+                    callee = "zz_counterexample_main".to_string();
+                } else if attr.iter().any(|(a,_)| a == "extern") {
+                    callee = cx.function.0.last().unwrap().clone();
+                }
+            }
+        }
+
+        write!(self.f, "\n#undef main\n").unwrap();
+        write!(self.f, "int main() {{\n").unwrap();
+        let mut args = Vec::new();
+        for (arg, input) in &cx.args {
+            args.push(self.emit_counterexample_input(&format!("cx_{}", arg.name), &arg.typed, input));
+        }
+        write!(self.f, "    {}({});\n", callee, args.join(", ")).unwrap();
+        write!(self.f, "    return 0;\n}}\n").unwrap();
+    }
+
+    // names of the fields of a struct that are not emitted
+    fn ghost_fields(&self, typed: &ast::Typed) -> Vec<String> {
+        let n = match &typed.t {
+            ast::Type::Other(n) => n,
+            _ => return Vec::new(),
+        };
+        self.module.d.iter().find_map(|(d,_,_)| match &d.def {
+            ast::Def::Struct{fields, ..} if &Name::from(&d.name) == n => Some(
+                fields.iter().filter(|f|f.tags.contains("ghost")).map(|f|f.name.clone()).collect()
+            ),
+            _ => None,
+        }).unwrap_or_default()
+    }
+
+    // declares storage for an input if needed and returns the expression to pass it
+    fn emit_counterexample_input(&mut self, name: &str, typed: &ast::Typed, input: &symbolic::Input) -> String {
+        match input {
+            symbolic::Input::Value(v) => v.clone(),
+            symbolic::Input::Null => "0".to_string(),
+            symbolic::Input::Unknown if typed.ptr.len() > 0 => "0".to_string(),
+            symbolic::Input::Unknown => {
+                write!(self.f, "    {} {} = {{0}};\n", self.to_local_typed_name(typed), name).unwrap();
+                name.to_string()
+            }
+            symbolic::Input::Struct(members) => {
+                write!(self.f, "    {} {} = {{0}};\n", self.to_local_typed_name(typed), name).unwrap();
+                let fields = match &typed.t {
+                    ast::Type::Other(n) => self.module.d.iter().find_map(|(d,_,_)| match &d.def {
+                        ast::Def::Struct{fields, ..} if &Name::from(&d.name) == n => Some(fields.clone()),
+                        _ => None,
+                    }),
+                    _ => None,
+                }.unwrap_or_default();
+                for (member, input) in members {
+                    let field = match fields.iter().find(|f| &f.name == member) {
+                        Some(f) if f.array.is_none() && !f.tags.contains("ghost") => f,
+                        _ => continue,
+                    };
+                    if let symbolic::Input::Unknown = input {
+                        continue;
+                    }
+                    let v = self.emit_counterexample_input(&format!("{}_{}", name, member), &field.typed, input);
+                    write!(self.f, "    {}.{} = {};\n", name, member, v).unwrap();
+                }
+                name.to_string()
+            }
+            symbolic::Input::Buffer{len, first} => {
+                let mut element = typed.clone();
+                element.ptr.pop();
+                write!(self.f, "    static {} {}{}[{}];\n",
+                    self.to_local_typed_name(&element),
+                    "*".repeat(element.ptr.len()),
+                    name,
+                    len,
+                ).unwrap();
+                if let symbolic::Input::Unknown = **first {
+                } else {
+                    let v = self.emit_counterexample_input(&format!("{}_0", name), &element, first);
+                    write!(self.f, "    {}[0] = {};\n", name, v).unwrap();
+                }
+                name.to_string()
+            }
+        }
+    }
+
     fn emit_pointer(&mut self, v: &Vec<ast::Pointer>) {
         for ptr in v {
             if !ptr.tags.contains_key("mut") && !ptr.tags.contains_key("mut") {
@@ -1093,7 +1286,11 @@ impl Emitter {
                 self.emit_loc(&loc);
                 write!(self.f, "    ({}", self.to_local_typed_name(&typed)).unwrap();
                 write!(self.f, "){{").unwrap();
+                let ghosts = self.ghost_fields(typed);
                 for (name, field) in fields {
+                    if ghosts.contains(name) {
+                        continue;
+                    }
                     write!(self.f, ".{} = ", name).unwrap();
                     self.emit_expr(field);
                     write!(self.f, ",").unwrap();
@@ -1118,6 +1315,7 @@ impl Emitter {
                     ast::PrefixOperator::Bitnot    =>  "~",
                     ast::PrefixOperator::Increment =>  "++",
                     ast::PrefixOperator::Decrement =>  "--",
+                    ast::PrefixOperator::Negate    =>  "-",
                     ast::PrefixOperator::AddressOf =>  "&",
                     ast::PrefixOperator::Deref     =>  "*",
                 }).unwrap();
@@ -1208,6 +1406,23 @@ impl Emitter {
                 self.emit_expr(rhs);
                 write!(self.f, "  )").unwrap();
             }
+            ast::Expression::Ternary {loc, cond, then, otherwise} => {
+                write!(self.f, "(").unwrap();
+                self.emit_expr(cond);
+                self.emit_loc(&loc);
+                write!(self.f, " ? ").unwrap();
+                self.emit_expr(then);
+                write!(self.f, " : ").unwrap();
+                self.emit_expr(otherwise);
+                write!(self.f, "  )").unwrap();
+            }
+            ast::Expression::Closure{..} => unreachable!("ICE: closure is lowered by closure::lower"),
+            ast::Expression::Quantifier{loc, ..} => {
+                emit_error("quantifiers cannot be executed".to_string(), &[
+                    (loc.clone(), "only use forall and exists in where, model, static_assert and loop invariants")
+                ]);
+                std::process::exit(9);
+            }
             ast::Expression::MemberAccess {loc, lhs, rhs, op} => {
                 self.emit_loc(&loc);
                 self.emit_expr(lhs);
@@ -75,7 +75,7 @@ impl Emitter {
             ast::Type::Bool => "bool".to_string(),
             ast::Type::F32  => "f32".to_string(),
             ast::Type::F64  => "f64".to_string(),
-            ast::Type::Other(ref _n)   => "u8".to_string(),
+            ast::Type::Other(ref _n) | ast::Type::Instance(ref _n, _) => "u8".to_string(),
                 /*
                 let mut s = self.to_local_name(&n);
                 match &name.tail {
@@ -87,7 +87,7 @@ impl Emitter {
                 s
             }
                 */
-            ast::Type::ILiteral | ast::Type::ULiteral | ast::Type::Elided => {
+            ast::Type::ILiteral | ast::Type::ULiteral | ast::Type::FLiteral | ast::Type::Elided => {
                 parser::emit_error(
                     "ICE: untyped ended up in emitter",
                     &[(name.loc.clone(), format!("this should have been resolved earlier"))]
@@ -160,8 +160,8 @@ impl Emitter {
                 ast::Def::Enum{..} => {
                     self.emit_enum(&d)
                 }
-                ast::Def::Function{..} => {
-                    if !d.name.ends_with("::main") {
+                ast::Def::Function{ref attr, ..} => {
+                    if !d.name.ends_with("::main") && !attr.contains_key("lemma") {
                         self.emit_decl(&d);
                     }
                 }
@@ -328,6 +328,7 @@ impl Emitter {
                     ast::PrefixOperator::Bitnot    =>  "~",
                     ast::PrefixOperator::Increment =>  "++",
                     ast::PrefixOperator::Decrement =>  "--",
+                    ast::PrefixOperator::Negate    =>  "-",
                     ast::PrefixOperator::AddressOf =>  "&",
                     ast::PrefixOperator::Deref     =>  "*",
                 }).unwrap();
@@ -412,6 +413,23 @@ impl Emitter {
                 self.emit_expr(rhs);
                 write!(self.f, "  )").unwrap();
             }
+            ast::Expression::Ternary {loc, cond, then, otherwise} => {
+                write!(self.f, "(if ").unwrap();
+                self.emit_expr(cond);
+                self.emit_loc(&loc);
+                write!(self.f, " {{ ").unwrap();
+                self.emit_expr(then);
+                write!(self.f, " }} else {{ ").unwrap();
+                self.emit_expr(otherwise);
+                write!(self.f, " }})").unwrap();
+            }
+            ast::Expression::Closure{..} => unreachable!("ICE: closure is lowered by closure::lower"),
+            ast::Expression::Quantifier{loc, ..} => {
+                emit_error("quantifiers cannot be executed".to_string(), &[
+                    (loc.clone(), "only use forall and exists in where, model, static_assert and loop invariants")
+                ]);
+                std::process::exit(9);
+            }
             ast::Expression::MemberAccess {loc, lhs, rhs, op} => {
                 self.emit_loc(&loc);
                 self.emit_expr(lhs);
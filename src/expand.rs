@@ -14,17 +14,22 @@ struct Storage {
     name:           Name,
     typed:          ast::Typed,
     declared:       ast::Location,
+    // number of elements, if this is an array
+    len:            Option<ast::Expression>,
 }
 
 #[derive(Default)]
 struct Scope {
     name:       String,
     storage:    HashMap<Name, Storage>,
+    defers:     Vec<ast::Statement>,
 }
 
 struct Stack {
     defs:   HashMap<Name, ast::Def>,
     stack:  Vec<Scope>,
+    ret:    Option<ast::Typed>,
+    fresh:  usize,
 }
 
 impl Stack {
@@ -32,6 +37,8 @@ impl Stack {
         Self {
             defs:   HashMap::new(),
             stack:  Vec::new(),
+            ret:    None,
+            fresh:  0,
         }
     }
     fn push(&mut self, name: String) {
@@ -39,6 +46,7 @@ impl Stack {
         self.stack.push(Scope{
             name,
             storage: HashMap::new(),
+            defers:  Vec::new(),
         });
     }
 
@@ -50,6 +58,13 @@ impl Stack {
         self.stack.last_mut().unwrap()
     }
 
+    // a local name no other expansion in this module uses
+    fn fresh_name(&mut self, what: &str) -> String {
+        let name = format!("_zz_{}_{}", what, self.fresh);
+        self.fresh += 1;
+        name
+    }
+
     fn alloc(&mut self, name: Name, typed: ast::Typed, loc: ast::Location, _tags: ast::Tags) -> Result<(), Error> {
 
         match format!("{}", name).as_str() {
@@ -75,11 +90,30 @@ impl Stack {
             typed:      typed.clone(),
             name:       name,
             declared:   loc.clone(),
+            len:        None,
         });
 
         Ok(())
 
     }
+
+    fn alloc_array(&mut self, name: Name, typed: ast::Typed, loc: ast::Location, tags: ast::Tags, len: Option<ast::Expression>) -> Result<(), Error> {
+        self.alloc(name.clone(), typed, loc, tags)?;
+        self.cur().storage.get_mut(&name).unwrap().len = len;
+        Ok(())
+    }
+}
+
+// the number of elements of an array declared with array size and initializer
+fn array_len(array: &Option<ast::Expression>, init: Option<&ast::Expression>) -> Option<ast::Expression> {
+    match (array, init) {
+        (Some(expr), _) => Some(expr.clone()),
+        (None, Some(ast::Expression::ArrayInit{loc, fields})) => Some(ast::Expression::Literal{
+            loc:    loc.clone(),
+            v:      format!("{}", fields.len()),
+        }),
+        _ => None,
+    }
 }
 
 pub fn expand(module: &mut flatten::Module) -> Result<(), Error> {
@@ -120,7 +154,7 @@ pub fn expand(module: &mut flatten::Module) -> Result<(), Error> {
                 )?;
 
             },
-            ast::Def::Static {tags, typed, array, ..} => {
+            ast::Def::Static {tags, typed, array, expr, ..} => {
                 let mut typed = typed.clone();
                 if array.is_some() {
                     typed.ptr.push(ast::Pointer{
@@ -128,11 +162,12 @@ pub fn expand(module: &mut flatten::Module) -> Result<(), Error> {
                         tags: Tags::new(),
                     });
                 }
-                stack.alloc(
+                stack.alloc_array(
                     Name::from(&d.name),
                     typed.clone(),
                     d.loc.clone(),
                     tags.clone(),
+                    array.as_ref().and_then(|array|array_len(array, Some(expr))),
                 )?;
             },
             ast::Def::Const { typed, .. } => {
@@ -154,7 +189,7 @@ pub fn expand(module: &mut flatten::Module) -> Result<(), Error> {
                     d.loc.clone(), Tags::new()
                 )?;
             },
-            ast::Def::Struct {fields, union, ..} => {
+            ast::Def::Struct {fields, union, variants, ..} => {
                 stack.alloc(
                     Name::from(&d.name),
                     ast::Typed{
@@ -166,7 +201,12 @@ pub fn expand(module: &mut flatten::Module) -> Result<(), Error> {
                     d.loc.clone(), Tags::new()
                 )?;
                 if *union {
-                    for field in fields {
+                    for field in fields.iter() {
+                        stack.cannot_drop_union(&field, &field.loc)?;
+                    }
+                }
+                for field in fields.iter() {
+                    if variants.iter().any(|v|v.fields.contains(&field.name)) {
                         stack.cannot_drop_union(&field, &field.loc)?;
                     }
                 }
@@ -231,7 +271,7 @@ pub fn expand(module: &mut flatten::Module) -> Result<(), Error> {
     for (d,_,defined_here) in &mut module.d {
         match &mut d.def {
             ast::Def::Theory{..} => {},
-            ast::Def::Function{args, body, callassert, callattests, calleffect, ..} => {
+            ast::Def::Function{args, ret, body, callassert, callattests, calleffect, ..} => {
 
                 for farg in args.iter_mut() {
                     if farg.typed.ptr.len() > 0 {
@@ -313,6 +353,34 @@ pub fn expand(module: &mut flatten::Module) -> Result<(), Error> {
                         }
 
 
+                    } else if let ast::Type::Other(name) = &farg.typed.t {
+                        // so is the function of a closure
+                        if let Some(ast::Def::Struct{closure: true, ..}) = stack.defs.get(name) {
+                            let loc = farg.typed.loc.clone();
+                            let ast_call = ast::Expression::Call{
+                                loc:    loc.clone(),
+                                name:   Box::new(ast::Expression::Name(ast::Typed{
+                                    t:      ast::Type::Other(Name::from("safe")),
+                                    ptr:    Vec::new(),
+                                    loc:    loc.clone(),
+                                    tail:   ast::Tail::None,
+                                })),
+                                args:   vec![Box::new(ast::Expression::MemberAccess{
+                                    loc:    loc.clone(),
+                                    lhs:    Box::new(ast::Expression::Name(ast::Typed{
+                                        t:      ast::Type::Other(Name::from(&farg.name)),
+                                        ptr:    Vec::new(),
+                                        loc:    loc.clone(),
+                                        tail:   ast::Tail::None,
+                                    })),
+                                    op:     ".".to_string(),
+                                    rhs:    "call".to_string(),
+                                })],
+                                expanded:   true,
+                                emit:       ast::EmitBehaviour::Default,
+                            };
+                            callassert.insert(0, ast_call);
+                        }
                     }
                 }
 
@@ -324,6 +392,7 @@ pub fn expand(module: &mut flatten::Module) -> Result<(), Error> {
 
 
                 stack.push(format!("{}", d.name));
+                stack.ret = ret.as_ref().map(|ret|ret.typed.clone());
 
                 for i in 0..args.len() {
                     let argname = Name::from(&args[i].name);
@@ -413,7 +482,13 @@ impl Stack {
                             tags: Tags::new(),
                         });
                     }
-                    self.alloc(Name::from(name.as_str()), typed, loc.clone(), tags.clone())?;
+                    if typed.t == ast::Type::Elided {
+                        if let Some(assigned) = assign.as_ref().and_then(|assign|self.typeof_expr(assign)) {
+                            typed = assigned;
+                        }
+                    }
+                    let len = array.as_ref().and_then(|array|array_len(array, assign.as_ref()));
+                    self.alloc_array(Name::from(name.as_str()), typed, loc.clone(), tags.clone(), len)?;
                     if let Some(expr) = assign {
                         self.expand_expr(expr)?;
                     }
@@ -423,6 +498,7 @@ impl Stack {
                     for (_loc, _expr, block) in branches {
                         self.push("branch".to_string());
                         self.expand_scope(&mut block.statements)?;
+                        block.statements.extend(self.drop(&block.end)?);
                         self.pop();
                     }
                     self.pop();
@@ -435,32 +511,142 @@ impl Stack {
                         self.expand_expr(expr)?;
                     }
 
-                    let r = self.drop_fn(&loc)?;
-                    for stm in r.into_iter().rev() {
+                    let mut r = self.drop_fn(&loc)?;
+
+                    // the return value is computed before anything is dropped
+                    if let (false, Some(expr), Some(ret)) = (r.is_empty(), expr.as_mut(), self.ret.clone()) {
+                        let name = self.fresh_name("return");
+                        let value = std::mem::replace(expr, ast::Expression::Name(ast::Typed{
+                            t:      ast::Type::Other(Name::from(name.as_str())),
+                            ptr:    Vec::new(),
+                            loc:    loc.clone(),
+                            tail:   ast::Tail::None,
+                        }));
+                        r.insert(0, Box::new(ast::Statement::Var{
+                            loc:    loc.clone(),
+                            typed:  ret,
+                            tags:   Tags::new(),
+                            name,
+                            array:  None,
+                            assign: Some(value),
+                        }));
+                    }
+
+                    for stm in r {
                         body.insert(i, stm);
                         i   += 1;
                         len += 1;
                     }
 
                 }
+                ast::Statement::Defer{..} => {
+                    if let ast::Statement::Defer{loc, stm} = *body.remove(i) {
+                        len -= 1;
+                        if let Some(escape) = defer_escapes(&stm, false, false) {
+                            return Err(Error::new("deferred statement cannot leave its scope".to_string(), vec![
+                                (escape.clone(), "this would jump out of the deferred statement".to_string()),
+                                (loc.clone(), "deferred here".to_string()),
+                            ]));
+                        }
+                        let mut deferred = vec![stm];
+                        self.expand_scope(&mut deferred)?;
+                        self.cur().defers.extend(deferred.into_iter().map(|stm|*stm));
+                    }
+                    continue;
+                }
                 ast::Statement::Label{..}           => {}
                 ast::Statement::Mark{..} => {},
-                ast::Statement::Switch{cases,  ..} => {
-                    for (_, block) in cases {
+                ast::Statement::Ghost{..} => {},
+                ast::Statement::Switch{loc, expr, cases, default} => {
+                    self.expand_expr(expr)?;
+
+                    let mut hoisted = None;
+                    if let Some((union, variants)) = self.tagged_union(expr) {
+                        check_exhaustive(&union, &variants, cases, default.is_some(), loc)?;
+
+                        let typed = ast::Typed{
+                            t:      ast::Type::Other(union.clone()),
+                            ptr:    Vec::new(),
+                            loc:    expr.loc().clone(),
+                            tail:   ast::Tail::None,
+                        };
+
+                        // the case bindings read from the switch expression, which is only evaluated once
+                        if !matches!(expr, ast::Expression::Name(_)) {
+                            let name = self.fresh_name("switch");
+                            let value = std::mem::replace(expr, ast::Expression::Name(ast::Typed{
+                                t:      ast::Type::Other(Name::from(name.as_str())),
+                                ..typed.clone()
+                            }));
+                            hoisted = Some(Box::new(ast::Statement::Var{
+                                loc:    loc.clone(),
+                                typed,
+                                tags:   Tags::new(),
+                                name,
+                                array:  None,
+                                assign: Some(value),
+                            }));
+                        }
+
+                        let fields = match self.defs.get(&union) {
+                            Some(ast::Def::Struct{fields, ..}) => fields.clone(),
+                            _ => Vec::new(),
+                        };
+                        for (_, block) in cases.iter_mut() {
+                            for stm in block.statements.iter_mut() {
+                                if let ast::Statement::Var{tags, typed, assign: Some(ast::Expression::MemberAccess{lhs, rhs, ..}), ..} = stm.as_mut() {
+                                    if !tags.contains("case-bind") {
+                                        continue;
+                                    }
+                                    **lhs = expr.clone();
+                                    if let Some(field) = fields.iter().find(|f|&f.name == rhs && f.array.is_none()) {
+                                        *typed = ast::Typed{
+                                            loc: typed.loc.clone(),
+                                            ..field.typed.clone()
+                                        };
+                                    }
+                                }
+                            }
+                        }
+
+                        // C switches over the tag
+                        *expr = ast::Expression::MemberAccess{
+                            loc:    expr.loc().clone(),
+                            lhs:    Box::new(expr.clone()),
+                            op:     ".".to_string(),
+                            rhs:    "tag".to_string(),
+                        };
+                    }
+
+                    for block in cases.iter_mut().map(|(_, block)|block).chain(default.iter_mut()) {
                         self.push("case".to_string());
                         self.expand_scope(&mut block.statements)?;
+                        block.statements.extend(self.drop(&block.end)?);
                         self.pop();
                     }
+
+                    if let Some(hoisted) = hoisted {
+                        body.insert(i, hoisted);
+                        i   += 1;
+                        len += 1;
+                    }
                 }
                 ast::Statement::Assign{lhs, rhs,..} => {
                     self.expand_expr(lhs)?;
                     self.expand_expr(rhs)?;
                 }
 
-                ast::Statement::Continue{..} => {}
+                ast::Statement::Continue{loc} => {
+                    let r = self.drop_until(loc, self.exit_frame(true))?;
+                    for stm in r {
+                        body.insert(i, stm);
+                        i   += 1;
+                        len += 1;
+                    }
+                }
                 ast::Statement::Break{loc} => {
-                    let r = self.drop(&loc)?;
-                    for stm in r.into_iter().rev() {
+                    let r = self.drop_until(loc, self.exit_frame(false))?;
+                    for stm in r {
                         body.insert(i, stm);
                         i   += 1;
                         len += 1;
@@ -472,23 +658,33 @@ impl Stack {
                     block.statements.extend(self.drop(&block.end)?);
                     self.pop();
                 }
-                ast::Statement::For{e1,e2,e3,body, ..} => {
+                ast::Statement::For{e1,e2,e3,invariants,decreases,body} => {
                     self.push("for loop".to_string());
                     self.expand_scope(e1)?;
                     if let Some(expr) = e2 {
                         self.expand_expr(expr)?;
                     }
+                    for expr in invariants.iter_mut().chain(decreases.iter_mut()) {
+                        self.expand_expr(expr)?;
+                    }
                     self.expand_scope(e3)?;
                     self.expand_scope(&mut body.statements)?;
                     body.statements.extend(self.drop(&body.end)?);
                     self.pop();
                 }
-                ast::Statement::While{body, expr, ..} => {
+                ast::Statement::ForIn{..} => {
+                    *body[i] = self.lower_for_in(&body[i])?;
+                    continue;
+                }
+                ast::Statement::While{body, expr, invariants, decreases} => {
                     self.push("while loop".to_string());
                     self.expand_scope(&mut body.statements)?;
                     body.statements.extend(self.drop(&body.end)?);
                     self.pop();
                     self.expand_expr(expr)?;
+                    for expr in invariants.iter_mut().chain(decreases.iter_mut()) {
+                        self.expand_expr(expr)?;
+                    }
                 }
                 ast::Statement::CBlock{..} => {}
             }
@@ -500,18 +696,41 @@ impl Stack {
     }
 
     fn drop_fn(&mut self, loc: &ast::Location) -> Result<Vec<Box<ast::Statement>>, Error> {
+        self.drop_until(loc, 1)
+    }
+    fn drop(&mut self, loc: &ast::Location) -> Result<Vec<Box<ast::Statement>>, Error> {
+        self.drop_frame(loc, self.stack.len() - 1)
+    }
+
+    // drops all frames from the current one down to and including frame
+    fn drop_until(&mut self, loc: &ast::Location, frame: usize) -> Result<Vec<Box<ast::Statement>>, Error> {
         let mut r = Vec::new();
-        for i in (1..self.stack.len()).rev() {
+        for i in (frame..self.stack.len()).rev() {
             r.extend(self.drop_frame(loc, i)?);
         }
         Ok(r)
     }
-    fn drop(&mut self, loc: &ast::Location) -> Result<Vec<Box<ast::Statement>>, Error> {
-        self.drop_frame(loc, self.stack.len() - 1)
+
+    // the frame left by a break or continue
+    fn exit_frame(&self, is_continue: bool) -> usize {
+        for i in (1..self.stack.len()).rev() {
+            match self.stack[i].name.as_str() {
+                "for loop" | "while loop" => return i,
+                "case" if !is_continue => return i,
+                _ => (),
+            }
+        }
+        self.stack.len() - 1
     }
 
     fn drop_frame(&mut self, loc: &ast::Location, frame: usize) -> Result<Vec<Box<ast::Statement>>, Error> {
         let mut r = Vec::new();
+
+        // deferred statements run before locals are dropped, last one first
+        for stm in self.stack[frame].defers.iter().rev() {
+            r.push(Box::new(stm.clone()));
+        }
+
         for (name, storage) in &self.stack[frame].storage {
 
             //TODO also drop owned pointers some day
@@ -579,6 +798,208 @@ impl Stack {
     }
 
 
+    // for (T item in expr) becomes
+    // for (usize mut i = 0; i < len; i++) { T item = expr[i]; ... }
+    // where len is the C expression of len(expr) depending on what expr is
+    fn lower_for_in(&mut self, stm: &ast::Statement) -> Result<ast::Statement, Error> {
+        let (loc, typed, tags, name, expr, invariants, body) = match stm {
+            ast::Statement::ForIn{loc, typed, tags, name, expr, invariants, body}
+                => (loc, typed, tags, name, expr, invariants, body),
+            _ => unreachable!(),
+        };
+
+        let cannot = |what: String| Err(Error::new(format!("cannot iterate over {}", what), vec![
+            (expr.loc().clone(), "expected an array of known size, a tail member or a slice::Slice".to_string()),
+        ]));
+        let iterated = match self.typeof_expr(expr) {
+            Some(iterated) => iterated,
+            None => return cannot("this expression".to_string()),
+        };
+
+        let is_slice = match &iterated.t {
+            ast::Type::Other(n) => iterated.ptr.len() <= 1 && format!("{}", n) == "::slice::slice::Slice",
+            _ => false,
+        };
+
+        let (array, len) = if is_slice {
+            let op = if iterated.ptr.is_empty() { "." } else { "->" };
+            let member = |rhs: &str| ast::Expression::MemberAccess{
+                loc:    loc.clone(),
+                lhs:    Box::new(expr.clone()),
+                op:     op.to_string(),
+                rhs:    rhs.to_string(),
+            };
+            (member("mem"), member("size"))
+        } else if let Some(len) = self.array_len_of(expr) {
+            (expr.clone(), len)
+        } else {
+            return cannot(format!("{}", iterated));
+        };
+
+        let index_name = self.fresh_name("i");
+        let index = |loc: &ast::Location| ast::Expression::Name(ast::Typed{
+            t:      ast::Type::Other(Name::from(&index_name)),
+            ptr:    Vec::new(),
+            loc:    loc.clone(),
+            tail:   ast::Tail::None,
+        });
+
+        let mut index_tags = Tags::new();
+        index_tags.insert("mut".to_string(), String::new(), loc.clone());
+        let e1 = vec![Box::new(ast::Statement::Var{
+            loc:    loc.clone(),
+            typed:  ast::Typed{
+                t:      ast::Type::USize,
+                ptr:    Vec::new(),
+                loc:    loc.clone(),
+                tail:   ast::Tail::None,
+            },
+            tags:   index_tags,
+            name:   index_name.clone(),
+            array:  None,
+            assign: Some(ast::Expression::Literal{loc: loc.clone(), v: "0".to_string()}),
+        })];
+
+        let e2 = ast::Expression::Infix{
+            loc:    loc.clone(),
+            lhs:    Box::new(index(loc)),
+            rhs:    Box::new(len),
+            op:     ast::InfixOperator::Lessthan,
+        };
+
+        let e3 = vec![Box::new(ast::Statement::Expr{
+            loc:    loc.clone(),
+            expr:   ast::Expression::UnaryPost{
+                loc:    loc.clone(),
+                op:     ast::PostfixOperator::Increment,
+                expr:   Box::new(index(loc)),
+            },
+        })];
+
+        let mut body = body.clone();
+        body.statements.insert(0, Box::new(ast::Statement::Var{
+            loc:    loc.clone(),
+            typed:  typed.clone(),
+            tags:   tags.clone(),
+            name:   name.clone(),
+            array:  None,
+            assign: Some(ast::Expression::ArrayAccess{
+                loc:    expr.loc().clone(),
+                lhs:    Box::new(array),
+                rhs:    Box::new(index(loc)),
+            }),
+        }));
+
+        Ok(ast::Statement::For{
+            e1,
+            e2: Some(e2),
+            e3,
+            invariants: invariants.to_vec(),
+            decreases:  None,
+            body,
+        })
+    }
+
+    // the number of elements of a local array, a fixed size array member or a tail member
+    fn array_len_of(&self, expr: &ast::Expression) -> Option<ast::Expression> {
+        match expr {
+            ast::Expression::Name(ast::Typed{t: ast::Type::Other(name), ..}) => {
+                self.stack.iter().rev().find_map(|scope|scope.storage.get(name)).and_then(|s|s.len.clone())
+            }
+            ast::Expression::MemberAccess{loc, lhs, op, rhs} => {
+                let mut typed = self.typeof_expr(lhs)?;
+                if op == "->" {
+                    typed.ptr.pop()?;
+                }
+                let fields = match &typed.t {
+                    ast::Type::Other(name) if typed.ptr.is_empty() => match self.defs.get(name) {
+                        Some(ast::Def::Struct{fields, ..}) => fields,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                let field = fields.iter().find(|f|&f.name == rhs)?;
+                match &field.array {
+                    Some(Some(len)) => Some(len.clone()),
+                    // only the last member can be the tail
+                    Some(None) if fields.last().map(|f|&f.name) == Some(rhs) => match &typed.tail {
+                        ast::Tail::Bind(n, tail_loc) => Some(ast::Expression::Name(ast::Typed{
+                            t:      ast::Type::Other(Name::from(n.as_str())),
+                            ptr:    Vec::new(),
+                            loc:    tail_loc.clone(),
+                            tail:   ast::Tail::None,
+                        })),
+                        ast::Tail::Static(v, _) => Some(ast::Expression::Literal{loc: loc.clone(), v: format!("{}", v)}),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // the type of an expression, as far as it can be known before execution
+    fn typeof_expr(&self, expr: &ast::Expression) -> Option<ast::Typed> {
+        match expr {
+            ast::Expression::Name(ast::Typed{t: ast::Type::Other(name), ..}) => {
+                self.stack.iter().rev().find_map(|scope|scope.storage.get(name)).map(|s|s.typed.clone())
+            }
+            ast::Expression::MemberAccess{lhs, op, rhs, ..} => {
+                let mut typed = self.typeof_expr(lhs)?;
+                if op == "->" {
+                    typed.ptr.pop()?;
+                }
+                if !typed.ptr.is_empty() {
+                    return None;
+                }
+                let fields = match &typed.t {
+                    ast::Type::Other(name) => match self.defs.get(name) {
+                        Some(ast::Def::Struct{fields, ..}) => fields,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                let field = fields.iter().find(|f|&f.name == rhs)?;
+                let mut typed = field.typed.clone();
+                if field.array.is_some() {
+                    typed.ptr.push(ast::Pointer{
+                        loc:  field.loc.clone(),
+                        tags: Tags::new(),
+                    });
+                }
+                Some(typed)
+            }
+            ast::Expression::ArrayAccess{lhs, ..} | ast::Expression::UnaryPre{op: ast::PrefixOperator::Deref, expr: lhs, ..} => {
+                let mut typed = self.typeof_expr(lhs)?;
+                typed.ptr.pop()?;
+                Some(typed)
+            }
+            ast::Expression::Call{name, ..} => match name.as_ref() {
+                ast::Expression::Name(ast::Typed{t: ast::Type::Other(name), ..}) => match self.defs.get(name) {
+                    Some(ast::Def::Function{ret: Some(ret), ..}) => Some(ret.typed.clone()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            ast::Expression::Cast{into, ..} => Some(into.clone()),
+            _ => None,
+        }
+    }
+
+    fn tagged_union(&self, expr: &ast::Expression) -> Option<(Name, Vec<ast::Variant>)> {
+        let typed = self.typeof_expr(expr)?;
+        if !typed.ptr.is_empty() {
+            return None;
+        }
+        match &typed.t {
+            ast::Type::Other(name) => {
+                self.defs.get(name).and_then(ast::Variant::of).map(|variants|(name.clone(), variants.clone()))
+            }
+            _ => None,
+        }
+    }
+
     fn cannot_drop_union(&self, field: &ast::Field, in_union: &ast::Location) -> Result<(), Error> {
         if let ast::Type::Other(name) = &field.typed.t {
             if let Some(ast::Def::Struct{impls,fields,..}) = self.defs.get(name) {
@@ -603,6 +1024,68 @@ impl Stack {
 
 
 
+fn check_exhaustive(name: &Name, variants: &[ast::Variant], cases: &[(Vec<ast::Expression>, ast::Block)], default: bool, loc: &ast::Location)
+    -> Result<(), Error>
+{
+    let short = name.0.last().unwrap();
+    let mut covered = std::collections::HashSet::new();
+    for (conds, _) in cases {
+        for cond in conds {
+            let variant = match cond {
+                ast::Expression::Name(ast::Typed{t: ast::Type::Other(n), ..})
+                    if n.len() == name.len() + 1 && n.0.starts_with(&name.0) => n.0.last(),
+                _ => None,
+            };
+            match variant {
+                Some(v) if variants.iter().any(|x|&x.name == v) => {
+                    covered.insert(v.clone());
+                }
+                _ => {
+                    return Err(Error::new(format!("case is not a variant of {}", short), vec![
+                        (cond.loc().clone(), format!("expected {}::<variant>", short)),
+                    ]));
+                }
+            }
+        }
+    }
+    if default {
+        return Ok(());
+    }
+    let missing : Vec<String> = variants.iter()
+        .filter(|v|!covered.contains(&v.name))
+        .map(|v|format!("{}::{}", short, v.name))
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::new(format!("switch over {} is not exhaustive", short), vec![
+            (loc.clone(), format!("missing {}", missing.join(", "))),
+        ]));
+    }
+    Ok(())
+}
+
+// a return, or a break or continue outside of a loop within the deferred statement
+fn defer_escapes(stm: &ast::Statement, in_loop: bool, in_case: bool) -> Option<&ast::Location> {
+    match stm {
+        ast::Statement::Return{loc, ..} => Some(loc),
+        ast::Statement::Break{loc} if !in_loop && !in_case => Some(loc),
+        ast::Statement::Continue{loc} if !in_loop => Some(loc),
+        ast::Statement::Block(block) | ast::Statement::Unsafe(block) => {
+            block.statements.iter().find_map(|stm|defer_escapes(stm, in_loop, in_case))
+        }
+        ast::Statement::If{branches} => {
+            branches.iter().flat_map(|(_,_,block)|block.statements.iter()).find_map(|stm|defer_escapes(stm, in_loop, in_case))
+        }
+        ast::Statement::Switch{cases, default, ..} => {
+            cases.iter().map(|(_, block)|block).chain(default.iter())
+                .flat_map(|block|block.statements.iter()).find_map(|stm|defer_escapes(stm, in_loop, true))
+        }
+        ast::Statement::For{body, ..} | ast::Statement::While{body, ..} => {
+            body.statements.iter().find_map(|stm|defer_escapes(stm, true, false))
+        }
+        _ => None,
+    }
+}
+
 fn replace_named(expr: &mut ast::Expression, replacefrom: &ast::Type, replacewith: &ast::Type) {
     match expr {
         ast::Expression::Name(ref mut t) => {
@@ -649,5 +1132,15 @@ fn replace_named(expr: &mut ast::Expression, replacefrom: &ast::Type, replacewit
                 replace_named(expr, replacefrom, replacewith);
             }
         },
+        ast::Expression::Ternary {ref mut cond, ref mut then, ref mut otherwise, ..} => {
+            replace_named(cond, replacefrom, replacewith);
+            replace_named(then, replacefrom, replacewith);
+            replace_named(otherwise, replacefrom, replacewith);
+        },
+        ast::Expression::Closure {..} => {},
+        ast::Expression::Quantifier {ref mut range, ref mut body, ..} => {
+            replace_named(range, replacefrom, replacewith);
+            replace_named(body, replacefrom, replacewith);
+        },
     }
 }
@@ -37,6 +37,19 @@ struct Local {
 #[derive(Default)]
 struct Collector {
     typevariants:   HashMap<Name, HashSet<u64>>,
+
+    // monomorphized copies of generics, by the name they are emitted as
+    instances:      HashMap<Name, Instance>,
+    // generic functions are instantiated into the module that uses them
+    generic_fns:    HashSet<Name>,
+    module:         Name,
+}
+
+#[derive(Clone)]
+struct Instance {
+    generic:    Name,
+    args:       Vec<ast::Typed>,
+    loc:        ast::Location,
 }
 
 #[derive(Default)]
@@ -98,7 +111,7 @@ fn stm_deps(cr: &mut Collector, stm: &ast::Statement) -> Vec<(Name, ast::Locatio
             }
             deps
         },
-        ast::Statement::For{e1,e2,e3, body} => {
+        ast::Statement::For{e1,e2,e3, invariants, decreases, body} => {
             let mut deps = Vec::new();
             for s in e1 {
                 deps.extend(stm_deps(cr, s));
@@ -109,12 +122,28 @@ fn stm_deps(cr: &mut Collector, stm: &ast::Statement) -> Vec<(Name, ast::Locatio
             for s in e3 {
                 deps.extend(stm_deps(cr, s));
             }
+            for s in invariants.iter().chain(decreases.iter()) {
+                deps.extend(expr_deps(cr, s));
+            }
             deps.extend(block_deps(cr, body));
             deps
         },
-        ast::Statement::While{expr,body} => {
+        ast::Statement::While{expr, invariants, decreases, body} => {
             let mut deps = Vec::new();
             deps.extend(expr_deps(cr, expr));
+            for s in invariants.iter().chain(decreases.iter()) {
+                deps.extend(expr_deps(cr, s));
+            }
+            deps.extend(block_deps(cr, body));
+            deps
+        },
+        ast::Statement::ForIn{typed, expr, invariants, body, ..} => {
+            let mut deps = Vec::new();
+            deps.extend(type_deps(cr, typed));
+            deps.extend(expr_deps(cr, expr));
+            for s in invariants {
+                deps.extend(expr_deps(cr, s));
+            }
             deps.extend(block_deps(cr, body));
             deps
         },
@@ -157,6 +186,9 @@ fn stm_deps(cr: &mut Collector, stm: &ast::Statement) -> Vec<(Name, ast::Locatio
                 Vec::new()
             }
         },
+        ast::Statement::Defer {stm, ..} | ast::Statement::Ghost {stm, ..} => {
+            stm_deps(cr, stm)
+        },
         ast::Statement::Continue{..} => {
             Vec::new()
         },
@@ -246,6 +278,310 @@ fn expr_deps(cr: &mut Collector, expr: &ast::Expression) -> Vec<(Name, ast::Loca
             v.extend(expr_deps(cr, rhs));
             v
         }
+        ast::Expression::Ternary {cond, then, otherwise, ..} => {
+            let mut v = Vec::new();
+            v.extend(expr_deps(cr, cond));
+            v.extend(expr_deps(cr, then));
+            v.extend(expr_deps(cr, otherwise));
+            v
+        }
+        ast::Expression::Closure {args, ret, body, ..} => {
+            let mut v = Vec::new();
+            for arg in args {
+                v.extend(type_deps(cr, &arg.typed));
+            }
+            if let Some(ret) = ret {
+                v.extend(type_deps(cr, &ret.typed));
+            }
+            v.extend(block_deps(cr, body));
+            v
+        }
+        ast::Expression::Quantifier {var, range, body, ..} => {
+            let mut v = Vec::new();
+            v.extend(type_deps(cr, &var.typed));
+            v.extend(expr_deps(cr, range));
+            v.extend(expr_deps(cr, body));
+            v
+        }
+    }
+}
+
+// the name of a generic instance, which must be a valid c identifier
+fn mangle(typed: &ast::Typed) -> String {
+    let mut s = match &typed.t {
+        ast::Type::Other(name) if name.0.get(1).map(|s|s.as_str()) == Some("ext") => {
+            name.0.last().unwrap().clone()
+        }
+        ast::Type::Other(name) => {
+            name.0.iter().filter(|s|!s.is_empty()).cloned().collect::<Vec<String>>().join("_")
+        }
+        t => format!("{}", ast::Typed{
+            t:      t.clone(),
+            loc:    typed.loc.clone(),
+            ptr:    Vec::new(),
+            tail:   ast::Tail::None,
+        }),
+    };
+    for _ in &typed.ptr {
+        s.push_str("ptr");
+    }
+    s
+}
+
+fn specialize_typed(cr: &mut Collector, typed: &mut ast::Typed, params: &HashMap<String, ast::Typed>) {
+    if let ast::Type::Other(name) = &typed.t {
+        if name.len() == 1 {
+            if let Some(arg) = params.get(&name.0[0]) {
+                let mut ptr = arg.ptr.clone();
+                ptr.extend(std::mem::replace(&mut typed.ptr, Vec::new()));
+                typed.ptr = ptr;
+                typed.t   = arg.t.clone();
+                if let ast::Tail::None = typed.tail {
+                    typed.tail = arg.tail.clone();
+                }
+            }
+        }
+    }
+
+    let (generic, mut args) = match std::mem::replace(&mut typed.t, ast::Type::Elided) {
+        ast::Type::Instance(generic, args) => (generic, args),
+        t => {
+            typed.t = t;
+            return;
+        }
+    };
+
+    for arg in &mut args {
+        specialize_typed(cr, arg, params);
+    }
+
+    let mut name = if cr.generic_fns.contains(&generic) {
+        cr.module.clone()
+    } else {
+        let mut name = generic.clone();
+        name.pop();
+        name
+    };
+    name.push(format!("{}_{}",
+        generic.0.last().unwrap(),
+        args.iter().map(mangle).collect::<Vec<String>>().join("_"),
+    ));
+
+    cr.instances.entry(name.clone()).or_insert(Instance{
+        generic,
+        args,
+        loc:    typed.loc.clone(),
+    });
+    typed.t = ast::Type::Other(name);
+}
+
+fn specialize_stm(cr: &mut Collector, stm: &mut ast::Statement, params: &HashMap<String, ast::Typed>) {
+    match stm {
+        ast::Statement::Mark{lhs, ..} => {
+            specialize_expr(cr, lhs, params);
+        },
+        ast::Statement::Label{..}
+        | ast::Statement::Continue{..}
+        | ast::Statement::Break{..}
+        | ast::Statement::CBlock{..} => {
+        },
+        ast::Statement::Block(b2) | ast::Statement::Unsafe(b2) => {
+            specialize_block(cr, b2, params);
+        },
+        ast::Statement::Switch{expr, cases, default, ..} => {
+            specialize_expr(cr, expr, params);
+            for (exprs, body) in cases {
+                for expr in exprs {
+                    specialize_expr(cr, expr, params);
+                }
+                specialize_block(cr, body, params);
+            }
+            if let Some(default) = default {
+                specialize_block(cr, default, params);
+            }
+        },
+        ast::Statement::For{e1, e2, e3, invariants, decreases, body} => {
+            for s in e1.iter_mut().chain(e3.iter_mut()) {
+                specialize_stm(cr, s, params);
+            }
+            for s in e2.iter_mut().chain(invariants.iter_mut()).chain(decreases.iter_mut()) {
+                specialize_expr(cr, s, params);
+            }
+            specialize_block(cr, body, params);
+        },
+        ast::Statement::While{expr, invariants, decreases, body} => {
+            specialize_expr(cr, expr, params);
+            for s in invariants.iter_mut().chain(decreases.iter_mut()) {
+                specialize_expr(cr, s, params);
+            }
+            specialize_block(cr, body, params);
+        },
+        ast::Statement::ForIn{typed, expr, invariants, body, ..} => {
+            specialize_typed(cr, typed, params);
+            specialize_expr(cr, expr, params);
+            for s in invariants {
+                specialize_expr(cr, s, params);
+            }
+            specialize_block(cr, body, params);
+        },
+        ast::Statement::If{branches} => {
+            for (_, expr, body) in branches {
+                if let Some(expr) = expr {
+                    specialize_expr(cr, expr, params);
+                }
+                specialize_block(cr, body, params);
+            }
+        },
+        ast::Statement::Assign{lhs, rhs, ..} => {
+            specialize_expr(cr, lhs, params);
+            specialize_expr(cr, rhs, params);
+        },
+        ast::Statement::Var{assign, typed, array, ..} => {
+            if let Some(Some(array)) = array {
+                specialize_expr(cr, array, params);
+            }
+            if let Some(assign) = assign {
+                specialize_expr(cr, assign, params);
+            }
+            specialize_typed(cr, typed, params);
+        },
+        ast::Statement::Expr{expr, ..} => {
+            specialize_expr(cr, expr, params);
+        }
+        ast::Statement::Return{expr, ..} => {
+            if let Some(expr) = expr {
+                specialize_expr(cr, expr, params);
+            }
+        },
+        ast::Statement::Defer{stm, ..} | ast::Statement::Ghost{stm, ..} => {
+            specialize_stm(cr, stm, params);
+        },
+    }
+}
+
+fn specialize_block(cr: &mut Collector, block: &mut ast::Block, params: &HashMap<String, ast::Typed>) {
+    for stm in &mut block.statements {
+        specialize_stm(cr, stm, params);
+    }
+}
+
+fn specialize_expr(cr: &mut Collector, expr: &mut ast::Expression, params: &HashMap<String, ast::Typed>) {
+    match expr {
+        ast::Expression::ArrayInit{fields, ..} => {
+            for expr in fields {
+                specialize_expr(cr, expr, params);
+            }
+        },
+        ast::Expression::StructInit{typed, fields, ..} => {
+            specialize_typed(cr, typed, params);
+            for (_, expr) in fields {
+                specialize_expr(cr, expr, params);
+            }
+        },
+        ast::Expression::Name(typed) => {
+            specialize_typed(cr, typed, params);
+        },
+        ast::Expression::Cast{expr, into, ..} => {
+            specialize_typed(cr, into, params);
+            specialize_expr(cr, expr, params);
+        },
+        ast::Expression::Literal{..} | ast::Expression::LiteralString{..} | ast::Expression::LiteralChar{..} => {
+        },
+        ast::Expression::Call{name, args, ..} => {
+            specialize_expr(cr, name, params);
+            for arg in args {
+                specialize_expr(cr, arg, params);
+            }
+        },
+        ast::Expression::UnaryPost{expr, ..} | ast::Expression::UnaryPre{expr, ..} => {
+            specialize_expr(cr, expr, params);
+        },
+        ast::Expression::MemberAccess{lhs, ..} => {
+            specialize_expr(cr, lhs, params);
+        },
+        ast::Expression::ArrayAccess{lhs, rhs, ..} | ast::Expression::Infix{lhs, rhs, ..} => {
+            specialize_expr(cr, lhs, params);
+            specialize_expr(cr, rhs, params);
+        },
+        ast::Expression::Ternary{cond, then, otherwise, ..} => {
+            specialize_expr(cr, cond, params);
+            specialize_expr(cr, then, params);
+            specialize_expr(cr, otherwise, params);
+        },
+        ast::Expression::Closure{args, ret, body, ..} => {
+            for arg in args {
+                specialize_typed(cr, &mut arg.typed, params);
+            }
+            if let Some(ret) = ret {
+                specialize_typed(cr, &mut ret.typed, params);
+            }
+            specialize_block(cr, body, params);
+        },
+        ast::Expression::Quantifier{var, range, body, ..} => {
+            specialize_typed(cr, &mut var.typed, params);
+            specialize_expr(cr, range, params);
+            specialize_expr(cr, body, params);
+        },
+    }
+}
+
+/// replace type parameters with the arguments of an instance, and instances of generics with their names
+fn specialize(cr: &mut Collector, local: &mut ast::Local, params: &HashMap<String, ast::Typed>) {
+    match &mut local.def {
+        ast::Def::Function{ret, args, body, callassert, calleffect, decreases, modifies, generics, ..} => {
+            generics.clear();
+            if let Some(ret) = ret {
+                specialize_typed(cr, &mut ret.typed, params);
+            }
+            for arg in args {
+                specialize_typed(cr, &mut arg.typed, params);
+            }
+            for expr in callassert.iter_mut().chain(calleffect.iter_mut()).chain(decreases.iter_mut()).chain(modifies.iter_mut()) {
+                specialize_expr(cr, expr, params);
+            }
+            specialize_block(cr, body, params);
+        }
+        ast::Def::Struct{fields, generics, invariants, ..} => {
+            generics.clear();
+            for field in fields {
+                specialize_typed(cr, &mut field.typed, params);
+                if let Some(Some(expr)) = &mut field.array {
+                    specialize_expr(cr, expr, params);
+                }
+            }
+            for invariant in invariants {
+                specialize_expr(cr, invariant, params);
+            }
+        }
+        ast::Def::Static{typed, expr, ..} | ast::Def::Const{typed, expr} => {
+            specialize_typed(cr, typed, params);
+            specialize_expr(cr, expr, params);
+        }
+        ast::Def::Fntype{ret, args, ..} | ast::Def::Theory{ret, args, ..} => {
+            if let Some(ret) = ret {
+                specialize_typed(cr, &mut ret.typed, params);
+            }
+            for arg in args {
+                specialize_typed(cr, &mut arg.typed, params);
+            }
+        }
+        ast::Def::Macro{body, ..} => {
+            specialize_block(cr, body, params);
+        }
+        ast::Def::Testcase{fields} => {
+            for (_, expr) in fields {
+                specialize_expr(cr, expr, params);
+            }
+        }
+        ast::Def::Enum{..} | ast::Def::Include{..} => {
+        }
+    }
+}
+
+fn generics(def: &ast::Def) -> Option<&Vec<String>> {
+    match def {
+        ast::Def::Function{generics, ..} | ast::Def::Struct{generics, ..} if !generics.is_empty() => Some(generics),
+        _ => None,
     }
 }
 
@@ -262,6 +598,22 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
     let mut thisobject  = HashSet::new();
     let mut collector   = Collector::default();
     let cr = &mut collector;
+    cr.module = md.name.clone();
+
+    for module in std::iter::once(&*md).chain(all_modules.values().filter_map(|m|match m {
+        loader::Module::ZZ(ast) => Some(ast),
+        loader::Module::C(_) => None,
+    })) {
+        for local in &module.locals {
+            if let ast::Def::Function{generics, ..} = &local.def {
+                if !generics.is_empty() {
+                    let mut ns = module.name.clone();
+                    ns.push(local.name.clone());
+                    cr.generic_fns.insert(ns);
+                }
+            }
+        }
+    }
 
 
     for local in &md.locals {
@@ -286,7 +638,13 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
             }
             debug!("  localizing {}", name);
 
-            if !name.is_absolute() {
+            let instance = cr.instances.get(&name).cloned();
+            let lookup = match &instance {
+                Some(instance) => instance.generic.clone(),
+                None => name.clone(),
+            };
+
+            if !lookup.is_absolute() {
                 emit_error(format!("undefined type '{}' during flatten of '{}'", name, md.name), &[
                        (loc.clone(), &format!("type '{}' unavailable in this scope", name)),
                 ]);
@@ -294,7 +652,7 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
             }
 
 
-            let mut module_name = name.clone();
+            let mut module_name = lookup.clone();
             let mut local_name = module_name.pop().unwrap();
             let mut expecting_sub_type = false;
             let mut local = None;
@@ -367,13 +725,56 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
             };
 
             let mut ast = local.clone();
-            let ast_name = local.name.clone();
+            let mut ast_name = local.name.clone();
+
+            match (&instance, generics(&local.def)) {
+                (None, None) => {
+                    specialize(cr, &mut ast, &HashMap::new());
+                }
+                // only instances are emitted
+                (None, Some(_)) => {
+                    let mut ns = module_name.clone();
+                    ns.push(ast_name.clone());
+                    collected.0.insert(ns, Local{
+                        decl_deps:      Vec::new(),
+                        impl_deps:      Vec::new(),
+                        use_deps:       Vec::new(),
+                        ast:            None,
+                        in_scope_here:  loc,
+                    });
+                    continue;
+                }
+                (Some(instance), None) => {
+                    emit_error(format!("'{}' is not generic", lookup.human_name()), &[
+                        (instance.loc.clone(), "used with type arguments here".to_string()),
+                        (local.loc.clone(), "declared here".to_string()),
+                    ]);
+                    std::process::exit(9);
+                }
+                (Some(instance), Some(generics)) => {
+                    if generics.len() != instance.args.len() {
+                        emit_error(format!("'{}' takes {} type arguments, not {}", lookup.human_name(), generics.len(), instance.args.len()), &[
+                            (instance.loc.clone(), "used here".to_string()),
+                            (local.loc.clone(), "declared here".to_string()),
+                        ]);
+                        std::process::exit(9);
+                    }
+                    let params = generics.iter().cloned().zip(instance.args.iter().cloned()).collect();
+                    specialize(cr, &mut ast, &params);
+                    ast_name = name.0.last().unwrap().clone();
+                    module_name = name.clone();
+                    module_name.pop();
+                    if let ast::Def::Function{..} = ast.def {
+                        ast.vis = ast::Visibility::Object;
+                    }
+                }
+            }
 
             let mut decl_deps       : Vec<(Name, ast::Location)> = Vec::new();
             let mut impl_deps       : Vec<(Name, ast::Location)> = Vec::new();
             let mut weak_deps       : Vec<(Name, ast::Location)> = Vec::new();
 
-            match &local.def {
+            match &ast.def {
                 ast::Def::Enum{names, ..} => {
                     let mut ns = module_name.clone();
                     ns.push(ast_name.clone());
@@ -401,7 +802,7 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
                     decl_deps.extend(type_deps(cr, &typed));
                     decl_deps.extend(expr_deps(cr, expr));
                 }
-                ast::Def::Function{ret, args, body, callassert, calleffect, .. } => {
+                ast::Def::Function{ret, args, body, callassert, calleffect, decreases, modifies, .. } => {
                     if let Some(ret) = ret {
                         decl_deps.extend(type_deps(cr, &ret.typed));
                     }
@@ -421,7 +822,7 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
                             if let ast::Type::Other(name) = &arg.typed.t {
 
                                 let mut ns = module_name.clone();
-                                ns.push(local.name.clone());
+                                ns.push(ast_name.clone());
 
                                 injected.entry(name.clone()).or_default()
                                     .push((ns, local.loc.clone()));
@@ -433,10 +834,14 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
                         decl_deps.extend(expr_deps(cr, expr));
                     }
 
-                    for expr in calleffect {
+                    for expr in calleffect.iter().chain(modifies.iter()) {
                         decl_deps.extend(expr_deps(cr, expr));
                     }
 
+                    if let Some(expr) = decreases {
+                        impl_deps.extend(expr_deps(cr, expr));
+                    }
+
                     impl_deps.extend(block_deps(cr, body));
                 }
                 ast::Def::Fntype{ret, args, ..} => {
@@ -457,7 +862,26 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
                         decl_deps.extend(tag_deps(cr, &arg.tags));
                     }
                 }
-                ast::Def::Struct{fields,..} => {
+                ast::Def::Struct{fields, variants, invariants, ..} => {
+                    for invariant in invariants {
+                        decl_deps.extend(expr_deps(cr, invariant));
+                    }
+                    if !variants.is_empty() {
+                        let mut ns = module_name.clone();
+                        ns.push(ast_name.clone());
+                        expecting_sub_type = false;
+                        for variant in variants {
+                            let mut name = name.clone();
+                            name.push(variant.name.clone());
+                            collected.0.insert(name, Local{
+                                impl_deps:      Vec::new(),
+                                decl_deps:      vec![(ns.clone(), loc.clone())],
+                                use_deps:       Vec::new(),
+                                ast:            None,
+                                in_scope_here:  loc.clone(),
+                            });
+                        }
+                    }
                     for field in fields {
                         if field.typed.ptr.len() > 0 {
                             weak_deps.extend(type_deps(cr, &field.typed));
@@ -612,9 +1036,10 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
     }
 
 
-    let impl_in_thisobject = std::mem::replace(&mut thisobject, HashSet::new());
+    let mut impl_in_thisobject = std::mem::replace(&mut thisobject, HashSet::new());
+    let mut pending : Vec<Name> = impl_in_thisobject.iter().cloned().collect();
     // collect implementation dependencies into this object
-    for name in &impl_in_thisobject {
+    while let Some(name) = pending.pop() {
         let n = collected.0.get(&name).unwrap();
         debug!("  thisobj from impl_in_thisobject: {}", name);
         thisobject.insert(name.clone());
@@ -637,6 +1062,18 @@ pub fn flatten(md: &mut ast::Module, all_modules: &HashMap<Name, loader::Module>
             debug!("      < : {}", dep);
             thisobject.insert(dep.clone());
         }
+
+        // generic functions are defined in every object that uses them
+        if pending.is_empty() {
+            for name in cr.instances.keys() {
+                if name.0[..name.len() - 1] == md.name.0[..]
+                    && thisobject.contains(name)
+                    && impl_in_thisobject.insert(name.clone())
+                {
+                    pending.push(name.clone());
+                }
+            }
+        }
     }
 
 
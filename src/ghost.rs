@@ -0,0 +1,183 @@
+use super::flatten;
+use crate::name::Name;
+use std::collections::HashMap;
+use crate::ast;
+
+// ghost statements are marked by what they declare or write, not by how they were executed,
+// so code the prover never reached is erased the same way as code it did.
+// ghost fields and lemma functions are left out by the emitter itself.
+
+struct Erase<'a> {
+    defs:       &'a HashMap<Name, ast::Def>,
+    // type and ghost tag of every local in scope
+    scopes:     Vec<HashMap<String, (ast::Typed, bool)>>,
+}
+
+pub fn erase(module: &mut flatten::Module) {
+    let mut defs = HashMap::new();
+    for (d,_,_) in &module.d {
+        defs.insert(Name::from(&d.name), d.def.clone());
+    }
+
+    for (d, _, defined_here) in &mut module.d {
+        if !*defined_here {
+            continue;
+        }
+        if let ast::Def::Function{args, body, attr, ..} = &mut d.def {
+            if attr.contains_key("lemma") {
+                continue;
+            }
+            let mut erase = Erase{
+                defs:   &defs,
+                scopes: vec![HashMap::new()],
+            };
+            for arg in args.iter() {
+                erase.declare(&arg.name, &arg.typed, arg.tags.contains("ghost"));
+            }
+            erase.block(&mut body.statements);
+        }
+    }
+}
+
+impl<'a> Erase<'a> {
+    fn declare(&mut self, name: &str, typed: &ast::Typed, ghost: bool) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), (typed.clone(), ghost));
+    }
+
+    fn block(&mut self, body: &mut [Box<ast::Statement>]) {
+        self.scopes.push(HashMap::new());
+        for stm in body {
+            self.statement(stm);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, stm: &mut ast::Statement) {
+        let ghost = match stm {
+            ast::Statement::Var{typed, tags, name, array, ..} => {
+                let mut typed = typed.clone();
+                if array.is_some() {
+                    typed.ptr.push(ast::Pointer{
+                        loc:  typed.loc.clone(),
+                        tags: ast::Tags::new(),
+                    });
+                }
+                let ghost = tags.contains("ghost");
+                self.declare(name, &typed, ghost);
+                ghost
+            }
+            ast::Statement::Assign{lhs, ..} => self.ghost_place(lhs),
+            ast::Statement::Expr{expr, ..} => match expr {
+                ast::Expression::UnaryPost{expr, ..} => self.ghost_place(expr),
+                ast::Expression::UnaryPre{expr, op: ast::PrefixOperator::Increment, ..}
+                    | ast::Expression::UnaryPre{expr, op: ast::PrefixOperator::Decrement, ..} => self.ghost_place(expr),
+                ast::Expression::Call{name, ..} => self.lemma(name),
+                _ => false,
+            },
+            ast::Statement::If{branches} => {
+                for (_, _, block) in branches {
+                    self.block(&mut block.statements);
+                }
+                false
+            }
+            ast::Statement::While{body, ..} => {
+                self.block(&mut body.statements);
+                false
+            }
+            ast::Statement::For{e1, body, ..} => {
+                self.scopes.push(HashMap::new());
+                for stm in e1.iter() {
+                    if let ast::Statement::Var{typed, tags, name, ..} = stm.as_ref() {
+                        self.declare(name, typed, tags.contains("ghost"));
+                    }
+                }
+                self.block(&mut body.statements);
+                self.scopes.pop();
+                false
+            }
+            ast::Statement::Switch{cases, default, ..} => {
+                for block in cases.iter_mut().map(|(_, block)|block).chain(default.iter_mut()) {
+                    self.block(&mut block.statements);
+                }
+                false
+            }
+            ast::Statement::Block(block) | ast::Statement::Unsafe(block) => {
+                self.block(&mut block.statements);
+                false
+            }
+            ast::Statement::ForIn{..} => unreachable!("ICE: ranged for is lowered by expand"),
+            ast::Statement::Defer{..} => unreachable!("ICE: defer is removed by expand"),
+            ast::Statement::Ghost{..}
+                | ast::Statement::Mark{..}
+                | ast::Statement::Return{..}
+                | ast::Statement::Label{..}
+                | ast::Statement::Continue{..}
+                | ast::Statement::Break{..}
+                | ast::Statement::CBlock{..} => false,
+        };
+
+        if ghost {
+            let loc = match stm {
+                ast::Statement::Var{loc, ..} | ast::Statement::Assign{loc, ..} | ast::Statement::Expr{loc, ..} => loc.clone(),
+                _ => unreachable!(),
+            };
+            let inner = std::mem::replace(stm, ast::Statement::Break{loc: loc.clone()});
+            *stm = ast::Statement::Ghost{loc, stm: Box::new(inner)};
+        }
+    }
+
+    // a call to a lemma only assumes its model
+    fn lemma(&self, name: &ast::Expression) -> bool {
+        if let ast::Expression::Name(ast::Typed{t: ast::Type::Other(name), ..}) = name {
+            if let Some(ast::Def::Function{attr, ..}) = self.defs.get(name) {
+                return attr.contains_key("lemma");
+            }
+        }
+        false
+    }
+
+    fn ghost_place(&self, expr: &ast::Expression) -> bool {
+        self.place(expr).map(|(_, ghost)|ghost).unwrap_or(false)
+    }
+
+    // the type of a place and whether any part of it is ghost
+    fn place(&self, expr: &ast::Expression) -> Option<(ast::Typed, bool)> {
+        match expr {
+            ast::Expression::Name(ast::Typed{t: ast::Type::Other(name), ..}) if name.len() == 1 => {
+                self.scopes.iter().rev().find_map(|scope|scope.get(&name.0[0])).cloned()
+            }
+            ast::Expression::MemberAccess{lhs, op, rhs, ..} => {
+                let (mut typed, ghost) = self.place(lhs)?;
+                if op == "->" {
+                    typed.ptr.pop()?;
+                }
+                let fields = match &typed.t {
+                    ast::Type::Other(name) if typed.ptr.is_empty() => match self.defs.get(name) {
+                        Some(ast::Def::Struct{fields, ..}) => fields,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                let field = fields.iter().find(|f|&f.name == rhs)?;
+                let mut typed = field.typed.clone();
+                if field.array.is_some() {
+                    typed.ptr.push(ast::Pointer{
+                        loc:  field.loc.clone(),
+                        tags: ast::Tags::new(),
+                    });
+                }
+                Some((typed, ghost || field.tags.contains("ghost")))
+            }
+            ast::Expression::ArrayAccess{lhs, ..} | ast::Expression::UnaryPre{op: ast::PrefixOperator::Deref, expr: lhs, ..} => {
+                let (mut typed, ghost) = self.place(lhs)?;
+                typed.ptr.pop()?;
+                Some((typed, ghost))
+            }
+            ast::Expression::Cast{into, expr, ..} => {
+                let ghost = self.ghost_place(expr);
+                Some((into.clone(), ghost))
+            }
+            _ => None,
+        }
+    }
+}
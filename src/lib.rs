@@ -19,7 +19,13 @@ pub mod name;
 pub mod pp;
 pub mod symbolic;
 pub mod expand;
+pub mod closure;
+pub mod ghost;
 pub mod smt;
+pub mod report;
+pub mod audit;
+#[cfg(feature = "native-z3")]
+pub mod z3;
 
 use std::path::Path;
 use name::Name;
@@ -71,47 +77,16 @@ pub fn build(tests: bool, check: bool, variant: &str, stage: make::Stage, slow:
     std::fs::create_dir_all(format!("./target/{}/zz/", stage)).expect("create target dir");
     std::fs::create_dir_all(format!("./target/{}/include/", stage)).expect("create target dir");
 
-    let project_name        = Name(vec![String::new(), project.project.name.clone()]);
-    let project_tests_name  = Name(vec![String::new(), project.project.name.clone(), "tests".to_string()]);
-
+    let (mut modules, features) = load(&mut project, variant, &stage);
 
-
-    let mut modules = HashMap::new();
-    let features = project.features(variant).into_iter().map(|(n,(e,_))|(n,e)).collect();
-    if std::path::Path::new("./src").exists() {
-        loader::load(&mut modules, &project_name, &Path::new("./src"), &features, &stage);
+    // --smt-timeout wins over zz.toml
+    if let Some(timeout) = project.project.smt_timeout {
+        let _ = smt::TIMEOUT.compare_exchange(0, timeout, Ordering::Relaxed, Ordering::Relaxed);
     }
-    if std::path::Path::new("./tests").exists() {
-        loader::load(&mut modules, &project_tests_name, &Path::new("./tests"), &features, &stage);
-    }
-
-
-
-    let mut searchpaths = HashSet::new();
-    searchpaths.insert(std::env::current_exe().expect("self path")
-        .canonicalize().expect("self path")
-        .parent().expect("self path")
-        .parent().expect("self path")
-        .parent().expect("self path")
-        .join("modules"));
-    searchpaths.insert(
-        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("modules")
-    );
-
-
-    if let Some(deps) = &project.dependencies {
-        for (name, dep) in deps {
-            match dep {
-                toml::Value::String(_) => {
-                    getdep(name, &mut modules, &mut project.project, &mut searchpaths, &stage);
-                },
-                _ => (),
-            }
-        }
+    if project.project.unsigned_overflow {
+        symbolic::UNSIGNED_OVERFLOW.store(true, Ordering::Relaxed);
     }
 
-
-
     let mut ext = abs::Ext::new();
 
     let mut names : Vec<Name> = modules.keys().cloned().collect();
@@ -146,13 +121,15 @@ pub fn build(tests: bool, check: bool, variant: &str, stage: make::Stage, slow:
 
 
     let working_on_these = Arc::new(Mutex::new(HashSet::new()));
+    let proofs = symbolic::ProofCache::new(&stage, variant, &features);
+    let reports = Mutex::new(Vec::new());
 
 
     let iterf =  |mut module| {
 
         //only emit if any source file is newer than the c file
         let (_, outname) = emitter::outname(&project.project, &stage, &module, false);
-        if module.is_newer_than(&outname) {
+        if module.is_newer_than(&outname) || report::ENABLED.load(Ordering::Relaxed) {
             let module_human_name = module.name.human_name();
             if !silent {
                 working_on_these.lock().unwrap().insert(module_human_name.clone());
@@ -172,8 +149,19 @@ pub fn build(tests: bool, check: bool, variant: &str, stage: make::Stage, slow:
                 pb.lock().unwrap().tick();
             }
 
+            closure::lower(&mut module)?;
             expand::expand(&mut module)?;
-            if !symbolic::execute(&mut module) {
+            let mut counterexamples = Vec::new();
+            let mut function_reports = Vec::new();
+            let ok = symbolic::execute(&mut module, &proofs, &mut counterexamples, &mut function_reports);
+            reports.lock().unwrap().extend(function_reports);
+            ghost::erase(&mut module);
+            if !ok {
+                for cx in counterexamples {
+                    let em = emitter::Emitter::new_counterexample(&project.project, stage.clone(), module.clone(), cx);
+                    let cf = em.emit();
+                    info!("counterexample written to {}", cf.filepath);
+                }
                 ABORT.store(true, Ordering::Relaxed);
                 return Ok(None);
             }
@@ -185,7 +173,8 @@ pub fn build(tests: bool, check: bool, variant: &str, stage: make::Stage, slow:
             rsbridge.emit();
 
             let em = emitter::Emitter::new(&project.project, stage.clone(), module, false);
-            let cf = em.emit();
+            let (cf, bodies) = em.emit_with_bodies();
+            proofs.store(&bodies);
 
             if !silent {
                 working_on_these.lock().unwrap().remove(&module_human_name);
@@ -241,6 +230,11 @@ pub fn build(tests: bool, check: bool, variant: &str, stage: make::Stage, slow:
     };
 
 
+    if report::ENABLED.load(Ordering::Relaxed) {
+        report::write(&stage, reports.into_inner().unwrap());
+    }
+    smt::portfolio_stats();
+
     if ABORT.load(Ordering::Relaxed) {
         std::process::exit(9);
     }
@@ -301,6 +295,60 @@ pub fn build(tests: bool, check: bool, variant: &str, stage: make::Stage, slow:
     };
 }
 
+/// list every assumption the proofs rely on. with since, mark the ones added after that git revision
+pub fn audit(variant: &str, since: Option<&str>) {
+    let (root, mut project) = project::load_cwd();
+    std::env::set_current_dir(root).unwrap();
+
+    let (modules, _) = load(&mut project, variant, &make::Stage::test());
+    let assumptions = audit::collect(&modules);
+    audit::print(&assumptions, since);
+}
+
+// parse the project and its dependencies
+fn load(project: &mut project::Config, variant: &str, stage: &make::Stage) -> (HashMap<Name, loader::Module>, HashMap<String, bool>) {
+    let project_name        = Name(vec![String::new(), project.project.name.clone()]);
+    let project_tests_name  = Name(vec![String::new(), project.project.name.clone(), "tests".to_string()]);
+
+
+
+    let mut modules = HashMap::new();
+    let features = project.features(variant).into_iter().map(|(n,(e,_))|(n,e)).collect();
+    if std::path::Path::new("./src").exists() {
+        loader::load(&mut modules, &project_name, &Path::new("./src"), &features, stage);
+    }
+    if std::path::Path::new("./tests").exists() {
+        loader::load(&mut modules, &project_tests_name, &Path::new("./tests"), &features, stage);
+    }
+
+
+
+    let mut searchpaths = HashSet::new();
+    searchpaths.insert(std::env::current_exe().expect("self path")
+        .canonicalize().expect("self path")
+        .parent().expect("self path")
+        .parent().expect("self path")
+        .parent().expect("self path")
+        .join("modules"));
+    searchpaths.insert(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("modules")
+    );
+
+
+    if let Some(deps) = &project.dependencies {
+        for (name, dep) in deps {
+            match dep {
+                toml::Value::String(_) => {
+                    getdep(name, &mut modules, &mut project.project, &mut searchpaths, stage);
+                },
+                _ => (),
+            }
+        }
+    }
+
+    (modules, features)
+}
+
 fn getdep(
         name: &str,
         modules: &mut HashMap<Name, loader::Module>,
@@ -26,7 +26,15 @@ fn main() {
         .version(clap::crate_version!())
         .setting(clap::AppSettings::UnifiedHelpMessage)
         .arg(Arg::with_name("smt-timeout").takes_value(true).required(false).long("smt-timeout"))
-        .subcommand(SubCommand::with_name("check").about("check the current project"))
+        .arg(Arg::with_name("portfolio").takes_value(false).required(false).long("portfolio")
+            .help("race all solvers in PATH on every query"))
+        .subcommand(SubCommand::with_name("check").about("check the current project")
+            .arg(Arg::with_name("emit-counterexample").takes_value(false).required(false).long("emit-counterexample"))
+        )
+        .subcommand(SubCommand::with_name("prove").about("prove the current project and report every proof obligation")
+            .arg(Arg::with_name("report").takes_value(false).required(false).long("report"))
+            .arg(Arg::with_name("variant").takes_value(true).required(false).long("variant").short("s"))
+        )
         .subcommand(SubCommand::with_name("export").about("emit c files without building them"))
             .arg(Arg::with_name("slow").takes_value(false).required(false).long("slow").short("0"))
             .arg(Arg::with_name("variant").takes_value(true).required(false).long("variant").short("s"))
@@ -38,6 +46,10 @@ fn main() {
             .arg(Arg::with_name("release").takes_value(false).required(false).long("release"))
             .arg(Arg::with_name("debug").takes_value(false).required(false).long("debug"))
         )
+        .subcommand(SubCommand::with_name("audit").about("list everything the proofs trust without checking")
+            .arg(Arg::with_name("since").takes_value(true).required(false).long("since"))
+            .arg(Arg::with_name("variant").takes_value(true).required(false).long("variant").short("s"))
+        )
         .subcommand(SubCommand::with_name("clean").about("remove the target directory"))
         .subcommand(SubCommand::with_name("bench").about("benchmark tests/*.zz")
                     .arg(Arg::with_name("testname").takes_value(true).required(false).index(1)),
@@ -61,6 +73,9 @@ fn main() {
     if let Some(t) = matches.value_of("smt-timeout") {
         zz::smt::TIMEOUT.store(t.parse().unwrap(), Ordering::Relaxed);
     }
+    if matches.is_present("portfolio") {
+        zz::smt::PORTFOLIO.store(true, Ordering::Relaxed);
+    }
 
     match matches.subcommand() {
         ("init", Some(_submatches)) => {
@@ -339,6 +354,18 @@ fn main() {
         },
         ("check", Some(submatches)) => {
             zz::parser::ERRORS_AS_JSON.store(true, Ordering::SeqCst);
+            if submatches.is_present("emit-counterexample") {
+                zz::symbolic::COUNTEREXAMPLES.store(true, Ordering::SeqCst);
+            }
+            zz::build(false, true, submatches.value_of("variant").unwrap_or("default"), zz::make::Stage::test(), false)
+        },
+        ("audit", Some(submatches)) => {
+            zz::audit(submatches.value_of("variant").unwrap_or("default"), submatches.value_of("since"));
+        },
+        ("prove", Some(submatches)) => {
+            if submatches.is_present("report") {
+                zz::report::ENABLED.store(true, Ordering::SeqCst);
+            }
             zz::build(false, true, submatches.value_of("variant").unwrap_or("default"), zz::make::Stage::test(), false)
         },
         ("build", Some(submatches)) => {
@@ -116,7 +116,7 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                 });
 
             }
-            Rule::function | Rule::fntype | Rule::theory => {
+            Rule::function | Rule::fntype | Rule::theory | Rule::closure_d => {
                 let loc = Location{
                     file: n.to_string_lossy().into(),
                     span: decl.as_span(),
@@ -132,8 +132,11 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                 let mut vararg = false;
                 let mut callassert = Vec::new();
                 let mut calleffect = Vec::new();
+                let mut decreases = None;
+                let mut modifies = Vec::new();
                 let mut vis = Visibility::Object;
                 let mut hints = HashMap::new();
+                let mut generics = Vec::new();
 
                 for part in decl {
                     match part.as_rule() {
@@ -143,6 +146,7 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                         Rule::exported => {
                             vis = Visibility::Export;
                         }
+                        Rule::key_closure => {}
                         Rule::ident => {
                             nameloc = Location{
                                 file: n.to_string_lossy().into(),
@@ -150,6 +154,9 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                             };
                             name = part.as_str().into();
                         }
+                        Rule::type_params => {
+                            generics = parse_type_params(part);
+                        }
                         Rule::ret_arg => {
                             let part = part.into_inner().next().unwrap();
                             ret = Some(AnonArg{
@@ -187,11 +194,19 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                         },
                         Rule::call_assert => {
                             let part = part.into_inner().next().unwrap();
-                            callassert.push(parse_expr((file_str, n), part));
+                            callassert.push(parse_expr((file_str, n), features, stage, part));
                         },
                         Rule::call_effect => {
                             let part = part.into_inner().next().unwrap();
-                            calleffect.push(parse_expr((file_str, n), part));
+                            calleffect.push(parse_expr((file_str, n), features, stage, part));
+                        },
+                        Rule::decreases => {
+                            let part = part.into_inner().next().unwrap();
+                            parse_decreases(&mut decreases, parse_expr((file_str, n), features, stage, part));
+                        },
+                        Rule::modifies => {
+                            let part = part.into_inner().next().unwrap();
+                            modifies.push(parse_expr((file_str, n), features, stage, part));
                         },
                         Rule::block => {
                             body = Some(parse_block((file_str, n), features, stage, part));
@@ -222,7 +237,10 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                                 vararg,
                                 callassert,
                                 calleffect,
+                                decreases,
+                                modifies,
                                 callattests: Vec::new(),
+                                generics,
                             }
                         });
                     }
@@ -252,6 +270,70 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                             }
                         });
                     },
+                    Rule::closure_d => {
+                        // a closure is a struct of a function and the environment it is called with
+                        let callname = format!("{}_fn", name);
+                        let env = NamedArg{
+                            name:   "env".to_string(),
+                            typed:  closure_env_type(&nameloc),
+                            tags:   Tags::new(),
+                            loc:    nameloc.clone(),
+                        };
+                        let mut envtags = Tags::new();
+                        envtags.insert("unsafe".to_string(), String::new(), nameloc.clone());
+                        args.insert(0, NamedArg{
+                            tags: envtags,
+                            ..env.clone()
+                        });
+                        module.locals.push(Local{
+                            name: callname.clone(),
+                            vis: vis.clone(),
+                            loc: loc.clone(),
+                            def:Def::Fntype{
+                                nameloc: nameloc.clone(),
+                                ret,
+                                attr,
+                                args,
+                                vararg,
+                            }
+                        });
+                        module.locals.push(Local{
+                            name,
+                            vis,
+                            loc,
+                            def: Def::Struct {
+                                fields: vec![
+                                    Field{
+                                        typed: Typed{
+                                            t:      Type::Other(Name::from(callname.as_str())),
+                                            loc:    nameloc.clone(),
+                                            ptr:    Vec::new(),
+                                            tail:   Tail::None,
+                                        },
+                                        name:   "call".to_string(),
+                                        array:  None,
+                                        tags:   Tags::new(),
+                                        loc:    nameloc.clone(),
+                                    },
+                                    Field{
+                                        typed:  env.typed,
+                                        name:   env.name,
+                                        array:  None,
+                                        tags:   env.tags,
+                                        loc:    nameloc,
+                                    },
+                                ],
+                                packed: false,
+                                tail:   Tail::None,
+                                union:  false,
+                                impls:  HashMap::new(),
+                                variants: Vec::new(),
+                                generics: Vec::new(),
+                                closure: true,
+                                invariants: Vec::new(),
+                            }
+                        });
+                    },
                     _ => unreachable!()
                 }
             },
@@ -263,6 +345,8 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                 let mut name   = None;
                 let mut names  = Vec::new();
                 let mut loc    = None;
+                let mut variants = Vec::new();
+                let mut payload  = Vec::new();
 
                 for part in PP::new(n, features.clone(), stage.clone(), decl) {
                     match part.as_rule() {
@@ -281,10 +365,21 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
 
                         }
                         Rule::enum_i => {
+                            let vloc = Location{
+                                file: n.to_string_lossy().into(),
+                                span: part.as_span(),
+                            };
                             let mut part = part.into_inner();
                             let name = part.next().unwrap().as_str().to_string();
                             let mut literal = None;
-                            if let Some(part) = part.next() {
+                            let mut fields  = Vec::new();
+                            for part in PP::new(n, features.clone(), stage.clone(), part) {
+                                if part.as_rule() == Rule::struct_f {
+                                    let field = parse_struct_field((file_str, n), features, stage, part);
+                                    fields.push(field.name.clone());
+                                    payload.push(field);
+                                    continue;
+                                }
                                 literal = Some(match part.as_str().to_string().parse() {
                                     Err(e) => {
                                         let loc  = Location{
@@ -301,6 +396,11 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                                 });
                             }
 
+                            variants.push(Variant{
+                                name:   name.clone(),
+                                fields,
+                                loc:    vloc,
+                            });
                             names.push((name, literal));
 
 
@@ -309,14 +409,67 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                     }
                 };
 
-                module.locals.push(Local{
-                    name: name.unwrap(),
-                    vis,
-                    loc: loc.unwrap(),
-                    def: Def::Enum{
-                        names,
+                let loc = loc.unwrap();
+
+                // with payload it's a tagged union, which is a struct of the tag and the fields of all variants
+                if payload.is_empty() {
+                    module.locals.push(Local{
+                        name: name.unwrap(),
+                        vis,
+                        loc,
+                        def: Def::Enum{
+                            names,
+                        }
+                    });
+                } else {
+                    for (variant, (_, literal)) in variants.iter().zip(names.iter()) {
+                        if literal.is_some() {
+                            emit_error("variants of a tagged union cannot have values", &[
+                                (variant.loc.clone(), "the tag is assigned in order of declaration")
+                            ]);
+                            std::process::exit(9);
+                        }
+                    }
+                    let mut fields = vec![Field{
+                        typed: Typed{
+                            t:      Type::Int,
+                            loc:    loc.clone(),
+                            ptr:    Vec::new(),
+                            tail:   Tail::None,
+                        },
+                        name:   "tag".to_string(),
+                        array:  None,
+                        tags:   Tags::new(),
+                        loc:    loc.clone(),
+                    }];
+                    for field in payload {
+                        if let Some(previous) = fields.iter().find(|f|f.name == field.name) {
+                            emit_error(format!("duplicate field {} in tagged union", field.name), &[
+                                (field.loc.clone(), "declared here".to_string()),
+                                (previous.loc.clone(), "also declared here".to_string()),
+                            ]);
+                            std::process::exit(9);
+                        }
+                        fields.push(field);
                     }
-                });
+
+                    module.locals.push(Local{
+                        name: name.unwrap(),
+                        vis,
+                        loc,
+                        def: Def::Struct {
+                            fields,
+                            packed: false,
+                            tail:   Tail::None,
+                            union:  false,
+                            impls:  HashMap::new(),
+                            variants,
+                            generics: Vec::new(),
+                            closure: false,
+                            invariants: Vec::new(),
+                        }
+                    });
+                }
 
             },
             Rule::testcase => {
@@ -341,7 +494,7 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                             let mut part = part.into_inner();
                             let fname   = part.next().unwrap().as_str().to_string();
                             let _op      = part.next().unwrap().as_str().to_string();
-                            let expr    = parse_expr((file_str, n), part.next().unwrap());
+                            let expr    = parse_expr((file_str, n), features, stage, part.next().unwrap());
                             fields.push((fname,expr));
                         }
                         e => panic!("unexpected rule {:?} in testcase", e),
@@ -369,9 +522,14 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                 let mut packed = false;
                 let mut tail   = Tail::None;
                 let mut union  = false;
+                let mut generics = Vec::new();
+                let mut invariants = Vec::new();
 
                 for part in PP::new(n,features.clone(), stage.clone(), decl) {
                     match part.as_rule() {
+                        Rule::type_params => {
+                            generics = parse_type_params(part);
+                        }
                         Rule::tail => {
                             tail = Tail::Dynamic;
                         }
@@ -397,40 +555,12 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                             });
                             name= Some(part.as_str().into());
                         }
+                        Rule::struct_invariant => {
+                            let part = part.into_inner().next().unwrap();
+                            invariants.push(parse_expr((file_str, n), features, stage, part));
+                        }
                         Rule::struct_f => {
-
-                            let loc  = Location{
-                                file: n.to_string_lossy().into(),
-                                span: part.as_span(),
-                            };
-
-
-                            let mut part = part.into_inner();
-
-                            let TypedName{typed, name, tags} = parse_named_type((file_str, n), part.next().unwrap());
-
-                            let array = match part.next() {
-                                None => None,
-                                Some(array) => {
-                                    match array.into_inner().next() {
-                                        Some(expr) => {
-                                            Some(Some(parse_expr((file_str, n), expr)))
-                                        },
-                                        None => {
-                                            Some(None)
-                                        }
-                                    }
-                                }
-                            };
-
-
-                            fields.push(Field{
-                                typed,
-                                array,
-                                tags,
-                                name,
-                                loc,
-                            });
+                            fields.push(parse_struct_field((file_str, n), features, stage, part));
                         }
                         e => panic!("unexpected rule {:?} in struct ", e),
                     }
@@ -448,6 +578,10 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                         tail,
                         union,
                         impls: HashMap::new(),
+                        variants: Vec::new(),
+                        generics,
+                        closure: false,
+                        invariants,
                     }
                 });
             }
@@ -565,11 +699,11 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
                             typed = Some(parse_named_type((file_str, n), part));
                         },
                         Rule::expr if expr.is_none() => {
-                            expr = Some(parse_expr((file_str, n), part));
+                            expr = Some(parse_expr((file_str, n), features, stage, part));
                         }
                         Rule::array => {
                             if let Some(expr) = part.into_inner().next() {
-                                array = Some(Some(parse_expr((file_str, n), expr)));
+                                array = Some(Some(parse_expr((file_str, n), features, stage, expr)));
                             } else {
                                 array = Some(None);
                             }
@@ -625,7 +759,7 @@ fn p(n: &Path, features: &HashMap<String, bool> , stage: &Stage) -> Result<Modul
     Ok(module)
 }
 
-pub(crate) fn parse_expr(n: (&'static str, &Path), decl: pest::iterators::Pair<'static, Rule>) -> Expression {
+pub(crate) fn parse_expr(n: (&'static str, &Path), features: &HashMap<String, bool>, stage: &Stage, decl: pest::iterators::Pair<'static, Rule>) -> Expression {
     match decl.as_rule() {
         Rule::expr  => { }
         Rule::expr_to_precedence_2 => {}
@@ -757,11 +891,34 @@ pub(crate) fn parse_expr(n: (&'static str, &Path), decl: pest::iterators::Pair<'
             },
         }
     };
-    climber.climb(decl.into_inner(), |pair|parse_expr_inner(n, pair), reduce)
+    let loc = Location{
+        file: n.1.to_string_lossy().into(),
+        span: decl.as_span(),
+    };
+    let mut pairs : Vec<pest::iterators::Pair<'static, Rule>> = decl.into_inner().collect();
+    let ternary = match pairs.last().map(|pair|pair.as_rule()) {
+        Some(Rule::ternary) => pairs.pop(),
+        _ => None,
+    };
+
+    let expr = climber.climb(pairs.into_iter(), |pair|parse_expr_inner(n, features, stage, pair), reduce);
+
+    match ternary {
+        None => expr,
+        Some(ternary) => {
+            let mut ternary = ternary.into_inner();
+            Expression::Ternary {
+                loc,
+                cond:       Box::new(expr),
+                then:       Box::new(parse_expr(n, features, stage, ternary.next().unwrap())),
+                otherwise:  Box::new(parse_expr(n, features, stage, ternary.next().unwrap())),
+            }
+        }
+    }
 }
 
 
-pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::Pair<'static, Rule>) -> Expression {
+pub(crate) fn parse_expr_inner(n: (&'static str, &Path), features: &HashMap<String, bool>, stage: &Stage, expr: pest::iterators::Pair<'static, Rule>) -> Expression {
     let loc = Location{
         file: n.1.to_string_lossy().into(),
         span: expr.as_span(),
@@ -777,6 +934,7 @@ pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::
                 Rule::bitnot    => crate::ast::PrefixOperator::Bitnot,
                 Rule::increment => crate::ast::PrefixOperator::Increment,
                 Rule::decrement => crate::ast::PrefixOperator::Decrement,
+                Rule::negate    => crate::ast::PrefixOperator::Negate,
                 _ => {
                     emit_error("ICE: unexpected operator", &[
                                (loc.clone(), "in this expr")
@@ -786,21 +944,20 @@ pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::
             };
             let part   = expr.next().unwrap();
             let iexpr   = match part.as_rule() {
-                Rule::type_name => {
+                Rule::expr_name => {
                     let loc = Location{
                         file: n.1.to_string_lossy().into(),
                         span: part.as_span(),
                     };
-                    let name = Name::from(part.as_str());
                     Expression::Name(Typed{
-                        t:   Type::Other(name),
+                        t:   parse_type_name(n, part),
                         ptr: Vec::new(),
                         loc,
                         tail: Tail::None,
                     })
                 },
                 Rule::expr_to_precedence_2 => {
-                    parse_expr(n, part)
+                    parse_expr(n, features, stage, part)
                 }
                 e => panic!("unexpected rule {:?} in unary pre lhs", e),
             };
@@ -814,21 +971,20 @@ pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::
             let mut expr = expr.into_inner();
             let part   = expr.next().unwrap();
             let iexpr   = match part.as_rule() {
-                Rule::type_name => {
+                Rule::expr_name => {
                     let loc = Location{
                         file: n.1.to_string_lossy().into(),
                         span: part.as_span(),
                     };
-                    let name = Name::from(part.as_str());
                     Expression::Name(Typed{
-                        t:   Type::Other(name),
+                        t:   parse_type_name(n, part),
                         ptr: Vec::new(),
                         loc,
                         tail: Tail::None,
                     })
                 },
                 Rule::expr => {
-                    parse_expr(n, part)
+                    parse_expr(n, features, stage, part)
                 }
                 e => panic!("unexpected rule {:?} in unary post lhs", e),
             };
@@ -856,17 +1012,16 @@ pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::
             let part  = expr.next().unwrap();
             let into = parse_anon_type(n, part);
             let part  = expr.next().unwrap();
-            let expr = parse_expr(n, part);
+            let expr = parse_expr(n, features, stage, part);
             Expression::Cast{
                 loc,
                 into,
                 expr: Box::new(expr),
             }
         },
-        Rule::type_name => {
-            let name = Name::from(expr.as_str());
+        Rule::expr_name => {
             Expression::Name(Typed{
-                t:   Type::Other(name),
+                t:   parse_type_name(n, expr),
                 ptr: Vec::new(),
                 loc,
                 tail: Tail::None,
@@ -910,7 +1065,7 @@ pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::
             }
         },
         Rule::expr => {
-            parse_expr(n, expr)
+            parse_expr(n, features, stage, expr)
         },
         Rule::deref | Rule::takeref => {
             let op = match expr.as_rule() {
@@ -921,21 +1076,20 @@ pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::
 
             let part = expr.into_inner().next().unwrap();
             let expr = match part.as_rule() {
-                Rule::type_name => {
+                Rule::expr_name => {
                     let loc = Location{
                         file: n.1.to_string_lossy().into(),
                         span: part.as_span(),
                     };
-                    let name = Name::from(part.as_str());
                     Expression::Name(Typed{
-                        t:   Type::Other(name),
+                        t:   parse_type_name(n, part),
                         ptr: Vec::new(),
                         loc,
                         tail: Tail::None,
                     })
                 },
                 Rule::expr_to_precedence_2 => {
-                    parse_expr(n, part)
+                    parse_expr(n, features, stage, part)
                 }
                 e => panic!("unexpected rule {:?} in deref lhs", e),
             };
@@ -946,7 +1100,78 @@ pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::
             }
         },
         Rule::call => {
-            parse_call(n, expr)
+            parse_call(n, features, stage, expr)
+        },
+        Rule::closure => {
+            let mut args = Vec::new();
+            let mut ret  = None;
+            let mut body = None;
+            for part in expr.into_inner() {
+                match part.as_rule() {
+                    Rule::key_closure => {}
+                    Rule::fn_args => {
+                        for arg in part.into_inner() {
+                            let argloc  = Location{
+                                file: n.1.to_string_lossy().into(),
+                                span: arg.as_span(),
+                            };
+                            if arg.as_rule() == Rule::vararg {
+                                emit_error("closures cannot be variadic", &[
+                                    (argloc, "remove this argument"),
+                                ]);
+                                std::process::exit(9);
+                            }
+                            let TypedName{typed, name, tags} = parse_named_type(n, arg);
+                            args.push(NamedArg{
+                                name,
+                                typed,
+                                tags,
+                                loc: argloc,
+                            });
+                        }
+                    }
+                    Rule::ret_arg => {
+                        let part = part.into_inner().next().unwrap();
+                        ret = Some(AnonArg{
+                            typed: parse_anon_type(n, part),
+                        });
+                    }
+                    Rule::block => {
+                        body = Some(parse_block(n, features, stage, part));
+                    }
+                    e => panic!("unexpected rule {:?} in closure", e),
+                }
+            }
+            Expression::Closure{
+                loc,
+                args,
+                ret,
+                body: body.unwrap(),
+            }
+        },
+        Rule::quantifier => {
+            let mut parts = expr.into_inner();
+            let forall = parts.next().unwrap().as_rule() == Rule::key_forall;
+            let arg = parts.next().unwrap();
+            let argloc = Location{
+                file: n.1.to_string_lossy().into(),
+                span: arg.as_span(),
+            };
+            let TypedName{typed, name, tags} = parse_named_type(n, arg);
+            let range = parse_expr(n, features, stage, parts.next().unwrap());
+            let body  = parse_expr(n, features, stage, parts.next().unwrap());
+            Expression::Quantifier{
+                loc,
+                forall,
+                var: NamedArg{
+                    name,
+                    typed,
+                    tags,
+                    loc: argloc,
+                },
+                range: Box::new(range),
+                body:  Box::new(body),
+            }
         },
         Rule::array_init => {
             let mut fields = Vec::new();
@@ -954,7 +1179,7 @@ pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::
             for part in expr {
                 match part.as_rule()  {
                     Rule::expr => {
-                        let expr = parse_expr(n, part);
+                        let expr = parse_expr(n, features, stage, part);
                         fields.push(Box::new(expr));
                     }
                     e => panic!("unexpected rule {:?} in struct init", e),
@@ -978,7 +1203,7 @@ pub(crate) fn parse_expr_inner(n: (&'static str, &Path), expr: pest::iterators::
                     Rule::struct_init_field => {
                         let mut part = part.into_inner();
                         let name = part.next().unwrap().as_str().to_string();
-                        let expr = parse_expr(n, part.next().unwrap());
+                        let expr = parse_expr(n, features, stage, part.next().unwrap());
                         fields.push((name, Box::new(expr)));
                     }
                     e => panic!("unexpected rule {:?} in struct init", e),
@@ -1013,7 +1238,7 @@ pub(crate) fn parse_statement(
         Rule::mark_stm => {
             let mut stm = stm.into_inner();
             let part    = stm.next().unwrap();
-            let lhs     = parse_expr(n, part);
+            let lhs     = parse_expr(n, features, stage, part);
             let part    = stm.next().unwrap();
             let mut part = part.into_inner();
             let key   = part.next().unwrap().as_str().into();
@@ -1044,6 +1269,16 @@ pub(crate) fn parse_statement(
                 loc,
             }));
         },
+        Rule::defer_stm => {
+            let mut stm = stm.into_inner();
+            stm.next(); // key_defer
+            let mut deferred = Vec::new();
+            parse_statement(n, features, stage, stm.next().unwrap(), &mut deferred, &mut None);
+            into.push(Box::new(Statement::Defer{
+                loc,
+                stm: deferred.pop().unwrap(),
+            }));
+        },
         Rule::block => {
             into.push(Box::new(Statement::Block(Box::new(parse_block(n, features, stage, stm)))))
         },
@@ -1055,7 +1290,7 @@ pub(crate) fn parse_statement(
                 a => { panic!("expected key_return instead of {:?}", a );}
             };
             let expr = if let Some(expr) = stm.next() {
-                Some(parse_expr(n, expr))
+                Some(parse_expr(n, features, stage, expr))
             } else {
                 None
             };
@@ -1065,7 +1300,7 @@ pub(crate) fn parse_statement(
             }));
         },
         Rule::expr => {
-            let expr = parse_expr(n, stm);
+            let expr = parse_expr(n, features, stage, stm);
             into.push(Box::new(Statement::Expr{
                 expr,
                 loc: loc.clone(),
@@ -1074,18 +1309,37 @@ pub(crate) fn parse_statement(
         Rule::while_stm => {
             let mut stm = stm.into_inner();
             let part    = stm.next().unwrap();
-            let expr    = parse_expr(n, part);
-            let part    = stm.next().unwrap();
-            let body    = parse_block(n, features, stage, part);
+            let expr    = parse_expr(n, features, stage, part);
+
+            let mut invariants = Vec::new();
+            let mut decreases = None;
+            let mut body = None;
+            for part in stm {
+                match part.as_rule() {
+                    Rule::loop_invariant => {
+                        invariants.push(parse_expr(n, features, stage, part.into_inner().next().unwrap()));
+                    },
+                    Rule::decreases => {
+                        parse_decreases(&mut decreases, parse_expr(n, features, stage, part.into_inner().next().unwrap()));
+                    },
+                    Rule::block => {
+                        body = Some(parse_block(n, features, stage, part));
+                    },
+                    e => panic!("unexpected rule {:?} in while", e),
+                }
+            }
+
             into.push(Box::new(Statement::While {
                 expr,
-                body,
+                invariants,
+                decreases,
+                body: body.unwrap(),
             }));
         }
         Rule::if_stm => {
             let mut stm = stm.into_inner();
             let part    = stm.next().unwrap();
-            let expr    = parse_expr(n, part);
+            let expr    = parse_expr(n, features, stage, part);
             let part    = stm.next().unwrap();
             let body    = parse_block(n, features, stage, part);
             *current_if_statement = Some(into.len());
@@ -1096,7 +1350,7 @@ pub(crate) fn parse_statement(
         Rule::elseif_stm => {
             let mut stm = stm.into_inner();
             let part    = stm.next().unwrap();
-            let expr    = parse_expr(n, part);
+            let expr    = parse_expr(n, features, stage, part);
             let part    = stm.next().unwrap();
             let body    = parse_block(n, features, stage, part);
             match *current_if_statement {
@@ -1141,6 +1395,8 @@ pub(crate) fn parse_statement(
             let mut expr1 = Vec::new();
             let mut expr2 = None;
             let mut expr3 = Vec::new();
+            let mut invariants = Vec::new();
+            let mut decreases = None;
             let mut block = None;
 
             let mut cur = 1;
@@ -1153,12 +1409,18 @@ pub(crate) fn parse_statement(
                     Rule::block if cur == 3 && block.is_none() => {
                         block = Some(parse_block(n, features, stage, part));
                     },
+                    Rule::loop_invariant if cur == 3 => {
+                        invariants.push(parse_expr(n, features, stage, part.into_inner().next().unwrap()));
+                    },
+                    Rule::decreases if cur == 3 => {
+                        parse_decreases(&mut decreases, parse_expr(n, features, stage, part.into_inner().next().unwrap()));
+                    },
                     _ if cur == 1 => {
                         let mut cif = None;
                         parse_statement(n, features, stage, part, &mut expr1, &mut cif);
                     },
                     _ if cur == 2 => {
-                        expr2 = Some(parse_expr(n, part));
+                        expr2 = Some(parse_expr(n, features, stage, part));
                     },
                     _ if cur == 3 => {
                         let mut cif = None;
@@ -1172,9 +1434,41 @@ pub(crate) fn parse_statement(
                 e1:     expr1,
                 e2:     expr2,
                 e3:     expr3,
+                invariants,
+                decreases,
                 body:   block.unwrap(),
             }));
         }
+        Rule::for_in_stm => {
+            let mut stm = stm.into_inner();
+            let TypedName{typed, name, tags} = parse_named_type(n, stm.next().unwrap());
+            stm.next(); // key_in
+            let expr = parse_expr(n, features, stage, stm.next().unwrap());
+
+            let mut invariants = Vec::new();
+            let mut body = None;
+            for part in stm {
+                match part.as_rule() {
+                    Rule::loop_invariant => {
+                        invariants.push(parse_expr(n, features, stage, part.into_inner().next().unwrap()));
+                    },
+                    Rule::block => {
+                        body = Some(parse_block(n, features, stage, part));
+                    },
+                    e => panic!("unexpected rule {:?} in for", e),
+                }
+            }
+
+            into.push(Box::new(Statement::ForIn{
+                loc,
+                typed,
+                tags,
+                name,
+                expr,
+                invariants,
+                body: body.unwrap(),
+            }));
+        }
         Rule::vardecl => {
             let stm = stm.into_inner();
             let mut typed   = None;
@@ -1187,11 +1481,11 @@ pub(crate) fn parse_statement(
                         typed = Some(parse_named_type(n, part));
                     },
                     Rule::expr => {
-                        assign = Some(parse_expr(n, part));
+                        assign = Some(parse_expr(n, features, stage, part));
                     }
                     Rule::array => {
                         if let Some(expr) = part.into_inner().next() {
-                            array = Some(Some(parse_expr(n, expr)));
+                            array = Some(Some(parse_expr(n, features, stage, expr)));
                         } else {
                             array = Some(None);
                         }
@@ -1220,14 +1514,20 @@ pub(crate) fn parse_statement(
             for part in stm {
                 match part.as_rule() {
                     Rule::expr if lhs.is_none() => {
-                        lhs = Some(parse_expr(n, part));
+                        lhs = Some(parse_expr(n, features, stage, part));
                     }
                     Rule::assignop => {
                         op = Some(match part.into_inner().next().unwrap().as_rule() {
                             Rule::assignbitor  => AssignOperator::Bitor,
                             Rule::assignbitand => AssignOperator::Bitand,
+                            Rule::assignbitxor => AssignOperator::Bitxor,
                             Rule::assignadd    => AssignOperator::Add,
                             Rule::assignsub    => AssignOperator::Sub,
+                            Rule::assignmul    => AssignOperator::Mul,
+                            Rule::assigndiv    => AssignOperator::Div,
+                            Rule::assignmod    => AssignOperator::Mod,
+                            Rule::assignshl    => AssignOperator::Shiftleft,
+                            Rule::assignshr    => AssignOperator::Shiftright,
                             Rule::assigneq     => AssignOperator::Eq,
                             _ => {
                                 emit_error("ICE: unexpected operator", &[
@@ -1238,7 +1538,7 @@ pub(crate) fn parse_statement(
                         });
                     }
                     Rule::expr if rhs.is_none() => {
-                        rhs = Some(parse_expr(n, part));
+                        rhs = Some(parse_expr(n, features, stage, part));
                     }
                     e => panic!("unexpected rule {:?} in assign", e),
                 }
@@ -1254,7 +1554,7 @@ pub(crate) fn parse_statement(
         Rule::switch_stm => {
             let mut stm  = stm.into_inner();
             let mut default = None;
-            let expr = parse_expr(n, stm.next().unwrap());
+            let expr = parse_expr(n, features, stage, stm.next().unwrap());
 
             let mut cases = Vec::new();
 
@@ -1273,10 +1573,53 @@ pub(crate) fn parse_statement(
                 } else {
                     let mut case_cond = Vec::new();
                     for case in ppart.into_inner() {
-                        case_cond.push(parse_expr(n, case));
+                        case_cond.push(parse_expr(n, features, stage, case));
+                    }
+
+                    let mut next = part.next().unwrap();
+                    let mut bind = Vec::new();
+                    if next.as_rule() == Rule::case_bind {
+                        if case_cond.len() != 1 {
+                            emit_error("cannot bind fields of multiple variants", &[
+                                (loc.clone(), "in this switch")
+                            ]);
+                            std::process::exit(9);
+                        }
+                        for ident in next.into_inner() {
+                            bind.push((ident.as_str().to_string(), Location{
+                                file: n.1.to_string_lossy().into(),
+                                span: ident.as_span(),
+                            }));
+                        }
+                        next = part.next().unwrap();
                     }
 
-                    let block = parse_block(n, features,  stage,part.next().unwrap());
+                    let mut block = parse_block(n, features,  stage, next);
+
+                    // Shape::Rect{w, h} => {} is let w = expr.w; let h = expr.h; in the block.
+                    // expand evaluates expr only once
+                    for (name, loc) in bind.into_iter().rev() {
+                        let mut tags = Tags::new();
+                        tags.insert("case-bind".to_string(), String::new(), loc.clone());
+                        block.statements.insert(0, Box::new(Statement::Var{
+                            loc:    loc.clone(),
+                            typed:  Typed{
+                                t:      Type::Other(Name::from("let")),
+                                loc:    loc.clone(),
+                                ptr:    Vec::new(),
+                                tail:   Tail::None,
+                            },
+                            tags,
+                            name:   name.clone(),
+                            array:  None,
+                            assign: Some(Expression::MemberAccess{
+                                loc,
+                                lhs:    Box::new(expr.clone()),
+                                op:     ".".to_string(),
+                                rhs:    name,
+                            }),
+                        }));
+                    }
                     cases.push((case_cond,block));
                 }
             }
@@ -1344,9 +1687,73 @@ pub(crate) struct TypedName {
     tags:   Tags,
 }
 
+// the environment of a closure is passed as void mut*
+fn closure_env_type(loc: &Location) -> Typed {
+    let mut tags = Tags::new();
+    tags.insert("mut".to_string(), String::new(), loc.clone());
+    Typed{
+        t:      Type::Other(Name::from("void")),
+        loc:    loc.clone(),
+        ptr:    vec![Pointer{
+            tags,
+            loc: loc.clone(),
+        }],
+        tail:   Tail::None,
+    }
+}
+
+pub(crate) fn parse_struct_field(n: (&'static str, &Path), features: &HashMap<String, bool>, stage: &Stage, decl: pest::iterators::Pair<'static, Rule>) -> Field {
+    let loc  = Location{
+        file: n.1.to_string_lossy().into(),
+        span: decl.as_span(),
+    };
+
+    let mut decl = decl.into_inner();
+
+    let TypedName{typed, name, tags} = parse_named_type(n, decl.next().unwrap());
+
+    let array = match decl.next() {
+        None => None,
+        Some(array) => {
+            match array.into_inner().next() {
+                Some(expr) => {
+                    Some(Some(parse_expr(n, features, stage, expr)))
+                },
+                None => {
+                    Some(None)
+                }
+            }
+        }
+    };
+
+    Field{
+        typed,
+        array,
+        tags,
+        name,
+        loc,
+    }
+}
+
+pub(crate) fn parse_type_params(decl: pest::iterators::Pair<'static, Rule>) -> Vec<String> {
+    decl.into_inner().map(|part|part.as_str().to_string()).collect()
+}
+
+/// a type name, with type arguments if it names an instance of a generic
+pub(crate) fn parse_type_name(n: (&'static str, &Path), decl: pest::iterators::Pair<'static, Rule>) -> Type {
+    let name = Name::from(decl.as_str().split('<').next().unwrap().trim_end_matches("::"));
+    for part in decl.into_inner() {
+        if part.as_rule() == Rule::type_args {
+            let args = part.into_inner().map(|arg|parse_anon_type(n, arg)).collect();
+            return Type::Instance(name, args);
+        }
+    }
+    Type::Other(name)
+}
+
 pub(crate) fn parse_named_type(n: (&'static str, &Path), decl: pest::iterators::Pair<'static, Rule>) -> TypedName {
     match decl.as_rule() {
-        Rule::named_type => { }
+        Rule::named_type | Rule::for_in_type => { }
         _ => { panic!("parse_named_type called with {:?}", decl); }
     };
 
@@ -1360,7 +1767,7 @@ pub(crate) fn parse_named_type(n: (&'static str, &Path), decl: pest::iterators::
     //the actual type name is always on the left hand side
     let mut decl = decl.into_inner();
     let mut lhsdecl = decl.next().unwrap().into_inner();
-    let typename = Name::from(lhsdecl.next().unwrap().as_str());
+    let typename = parse_type_name(n, lhsdecl.next().unwrap());
     for lhs in lhsdecl {
         match lhs.as_rule() {
             Rule::tail => {
@@ -1448,7 +1855,7 @@ pub(crate) fn parse_named_type(n: (&'static str, &Path), decl: pest::iterators::
     TypedName {
         name,
         typed: Typed {
-            t:   Type::Other(typename),
+            t:   typename,
             loc: loc.clone(),
             ptr,
             tail,
@@ -1469,7 +1876,7 @@ pub(crate) fn parse_anon_type(n: (&'static str, &Path), decl: pest::iterators::P
     };
     //the actual type name is always on the left hand side
     let mut decl = decl.into_inner();
-    let name = Name::from(decl.next().unwrap().as_str());
+    let t = parse_type_name(n, decl.next().unwrap());
 
     let mut tags = Tags::new();
     let mut ptr  = Vec::new();
@@ -1525,7 +1932,7 @@ pub(crate) fn parse_anon_type(n: (&'static str, &Path), decl: pest::iterators::P
     }
 
     Typed {
-        t: Type::Other(name),
+        t,
         loc, ptr, tail,
     }
 }
@@ -1579,7 +1986,19 @@ pub(crate) fn parse_importname(decl: pest::iterators::Pair<Rule>) -> (Name, Vec<
     (Name(v), locals)
 }
 
-fn parse_call(n: (&'static str, &Path), expr: pest::iterators::Pair<'static, Rule>) -> Expression {
+// a loop or function has a single termination measure
+fn parse_decreases(decreases: &mut Option<Expression>, measure: Expression) {
+    if let Some(previous) = decreases {
+        emit_error("duplicate decreases clause", &[
+            (measure.loc().clone(), "declared here".to_string()),
+            (previous.loc().clone(), "also declared here".to_string()),
+        ]);
+        std::process::exit(9);
+    }
+    *decreases = Some(measure);
+}
+
+fn parse_call(n: (&'static str, &Path), features: &HashMap<String, bool>, stage: &Stage, expr: pest::iterators::Pair<'static, Rule>) -> Expression {
     let loc = Location{
         file: n.1.to_string_lossy().into(),
         span: expr.as_span(),
@@ -1590,7 +2009,7 @@ fn parse_call(n: (&'static str, &Path), expr: pest::iterators::Pair<'static, Rul
     //    file: n.1.to_string_lossy().into(),
     //    span: name.as_span(),
     //};
-    //let name = Box::new(parse_expr(n, name));
+    //let name = Box::new(parse_expr(n, features, stage, name));
 
 
     let mut args = Vec::new();
@@ -1600,7 +2019,7 @@ fn parse_call(n: (&'static str, &Path), expr: pest::iterators::Pair<'static, Rul
         match part.as_rule() {
             Rule::call_args => {
                 args = part.into_inner().into_iter().map(|arg|{
-                    Box::new(parse_expr(n, arg))
+                    Box::new(parse_expr(n, features, stage, arg))
                 }).collect();
             },
             e => panic!("unexpected rule {:?} in function call", e),
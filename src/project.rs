@@ -59,6 +59,11 @@ pub struct Project {
     pub cflags:     Vec<String>,
     #[serde(default)]
     pub lflags:     Vec<String>,
+    // milliseconds per solver query
+    pub smt_timeout: Option<usize>,
+    // unsigned wraparound is defined in C, but usually not intended
+    #[serde(default)]
+    pub unsigned_overflow: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -0,0 +1,100 @@
+use super::ast;
+use super::make;
+use serde::{Serialize};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool};
+use std::time::Duration;
+
+/// record every proof obligation for zz prove --report
+pub static ENABLED: AtomicBool = AtomicBool::new(false);
+
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Proven,
+    Failed,
+    Timeout,
+    Assumed,
+}
+
+#[derive(Serialize)]
+pub struct Obligation {
+    pub kind:       String,
+    pub expr:       String,
+    pub file:       String,
+    pub line:       usize,
+    pub result:     Outcome,
+    pub time_ms:    f64,
+}
+
+impl Obligation {
+    pub fn new(kind: &str, loc: &ast::Location, result: Outcome, time: Duration) -> Self {
+        Obligation {
+            kind:       kind.to_string(),
+            expr:       loc.span.as_str().to_string(),
+            file:       loc.file.clone(),
+            line:       loc.line(),
+            result,
+            time_ms:    time.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Function {
+    pub module:         String,
+    pub function:       String,
+    pub file:           String,
+    pub line:           usize,
+    pub time_ms:        f64,
+    pub obligations:    Vec<Obligation>,
+}
+
+
+/// writes target/<stage>/report.json and target/<stage>/report.html
+pub fn write(stage: &make::Stage, mut functions: Vec<Function>) {
+    functions.sort_by(|a,b|(&a.module, &a.function).cmp(&(&b.module, &b.function)));
+
+    let p = format!("target/{}/report.json", stage);
+    let f = std::fs::File::create(&p).expect(&format!("cannot create {}", p));
+    serde_json::to_writer_pretty(f, &functions).expect(&format!("cannot write {}", p));
+    info!("proof report written to {}", p);
+
+    let p = format!("target/{}/report.html", stage);
+    let mut f = std::fs::File::create(&p).expect(&format!("cannot create {}", p));
+    write!(f, "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>proof obligations</title>\n").unwrap();
+    write!(f, "<style>\n\
+               body {{ font-family: sans-serif; }}\n\
+               table {{ border-collapse: collapse; margin-bottom: 2em; }}\n\
+               td, th {{ border: 1px solid #ccc; padding: 2px 8px; text-align: left; }}\n\
+               .proven {{ color: #070; }} .failed {{ color: #a00; }} .timeout {{ color: #a60; }} .assumed {{ color: #00a; }}\n\
+               </style></head><body>\n").unwrap();
+
+    for function in &functions {
+        write!(f, "<h3>{} <small>{}:{} {:.1}ms</small></h3>\n",
+            escape(&function.function), escape(&function.file), function.line, function.time_ms).unwrap();
+        if function.obligations.is_empty() {
+            write!(f, "<p>no obligations</p>\n").unwrap();
+            continue;
+        }
+        write!(f, "<table><tr><th>kind</th><th>location</th><th>expression</th><th>result</th><th>time</th></tr>\n").unwrap();
+        for o in &function.obligations {
+            let result = match o.result {
+                Outcome::Proven  => "proven",
+                Outcome::Failed  => "failed",
+                Outcome::Timeout => "timeout",
+                Outcome::Assumed => "assumed",
+            };
+            write!(f, "<tr><td>{}</td><td>{}:{}</td><td><code>{}</code></td><td class=\"{}\">{}</td><td>{:.1}ms</td></tr>\n",
+                escape(&o.kind), escape(&o.file), o.line, escape(&o.expr), result, result, o.time_ms).unwrap();
+        }
+        write!(f, "</table>\n").unwrap();
+    }
+    write!(f, "</body></html>\n").unwrap();
+    info!("proof report written to {}", p);
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
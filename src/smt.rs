@@ -2,16 +2,23 @@ use crate::symbolic::{Symbol, TemporalSymbol};
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::io::Write;
-use std::cell::{RefCell};
-use std::sync::atomic::{AtomicUsize};
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use super::parser::{emit_warn};
 use rsmt2_zz as rsmt2;
-pub static TIMEOUT: AtomicUsize = AtomicUsize::new(5000);
+/// race all available solvers on every query, as if every function had @solver = portfolio
+pub static PORTFOLIO: AtomicBool = AtomicBool::new(false);
+
+/// milliseconds per solver query. 0 until set by --smt-timeout or smt_timeout in zz.toml
+pub static TIMEOUT: AtomicUsize = AtomicUsize::new(0);
+pub const DEFAULT_TIMEOUT: usize = 5000;
 
 pub enum Assertion<T> {
     Constrained(T),
     Unconstrained(T),
     Unsolveable,
+    // the solver gave up, so there is no model to read a value from
+    Unknown,
 }
 
 
@@ -22,7 +29,7 @@ pub struct Var {
 }
 
 pub struct Solver {
-    solver:         RefCell<rsmt2::Solver<Rsmt2Junk>>,
+    solver:         RefCell<Backend>,
     vars:           RefCell<HashMap<Symbol, Var>>,
     theories:       HashMap<Symbol, String>,
     debug_loc:      crate::ast::Location,
@@ -33,17 +40,53 @@ pub struct Solver {
     ded_syms:       HashMap<Symbol, String>,
 
     assert_counter: usize,
+
+    floats:         bool,
+    trusted:        bool,
+    timeout:        usize,
+    // the last query ran into the timeout
+    unknown:        Cell<bool>,
+
+    quantifiers:    bool,
+    captures:       RefCell<Vec<Capture>>,
 }
 
+// definitions made while a quantifier body is executed.
+// the quantified variable and everything defined from it is bound by the quantifier
+struct Capture {
+    var:        Symbol,
+    depth:      usize,
+    declared:   Vec<(Symbol, String, Type)>,
+    defs:       Vec<String>,
+}
 
 
-#[derive(Clone, Debug, PartialEq)]
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Type {
     Bool,
     Signed(u32),
     Unsigned(u32),
+    // IEEE-754 with exponent and significand bits, as in (_ FloatingPoint 8 24)
+    Float(u32, u32),
+    // contents of memory, indexed by usize
+    Array(Box<Type>),
 }
 
+impl Type {
+    fn sort(&self) -> String {
+        match self {
+            Type::Bool => "Bool".to_string(),
+            Type::Signed(s) | Type::Unsigned(s) => format!("(_ BitVec {})", s),
+            Type::Float(e, s) => format!("(_ FloatingPoint {} {})", e, s),
+            Type::Array(t) => format!("(Array (_ BitVec 64) {})", t.sort()),
+        }
+    }
+}
+
+// C rounds to nearest for arithmetic and conversion to float
+const ROUNDING: &str = "RNE";
+
 pub struct ModelRef(());
 
 impl Solver {
@@ -53,15 +96,10 @@ impl Solver {
         let var = fuckyourust.get_mut(&sym.0).unwrap();
         let name = format!("{}__t{}", var.smtname, sym.1);
         if !var.temp.contains(&sym.1) {
-            write!(self.solver.borrow_mut(), "(declare-fun {} () {})\n",
-                name,
-                match var.typ {
-                    Type::Bool => format!("Bool"),
-                    Type::Signed(s) | Type::Unsigned(s) => format!("(_ BitVec {})", s),
-                },
-            ).unwrap();
+            self.solver.borrow_mut().declare(&name, &[], &var.typ).unwrap();
 
             var.temp.insert(sym.1);
+            self.capture_var(sym, &name, &var.typ);
 
             self.symbol_stack.borrow_mut().last_mut().as_mut().unwrap().push((sym.clone(), name.to_string(), var.typ.clone()));
         }
@@ -73,15 +111,12 @@ impl Solver {
         let var = fuckyourust.get_mut(&sym.0).unwrap();
         let name = format!("{}__t{}", var.smtname, sym.1);
         if !var.temp.contains(&sym.1) {
-            write!(self.solver.borrow_mut(), "(declare-fun {} () {})\n",
-                name,
-                match var.typ {
-                    Type::Bool => format!("Bool"),
-                    Type::Signed(s) | Type::Unsigned(s) => format!("(_ BitVec {})", s),
-                },
-            ).unwrap();
+            self.solver.borrow_mut().declare(&name, &[], &var.typ).unwrap();
 
             var.temp.insert(sym.1);
+            self.capture_var(sym, &name, &var.typ);
+
+            self.symbol_stack.borrow_mut().last_mut().as_mut().unwrap().push((*sym, name.to_string(), var.typ.clone()));
         }
 
         match (&var.typ, t2) {
@@ -106,9 +141,63 @@ impl Solver {
                         name
                     }
                 }
+            (Type::Float(e1, s1), Type::Float(e2, s2)) => {
+                if *e1 == e2 && *s1 == s2 {
+                    name
+                } else {
+                    format!("((_ to_fp {} {}) {} {})", e2, s2, ROUNDING, name)
+                }
+            }
+            (Type::Bool, Type::Float(e, s)) => {
+                format!("(ite {} ((_ to_fp {} {}) {} (_ bv1 8)) (_ +zero {} {}))", name, e, s, ROUNDING, e, s)
+            }
+            (Type::Float(..), Type::Bool) => {
+                format!("(not (fp.isZero {}))", name)
+            }
+            (Type::Signed(_), Type::Float(e, s)) => {
+                format!("((_ to_fp {} {}) {} {})", e, s, ROUNDING, name)
+            }
+            (Type::Unsigned(_), Type::Float(e, s)) => {
+                format!("((_ to_fp_unsigned {} {}) {} {})", e, s, ROUNDING, name)
+            }
+            // C truncates towards zero when converting float to integer
+            (Type::Float(..), Type::Signed(size)) => {
+                format!("((_ fp.to_sbv {}) RTZ {})", size, name)
+            }
+            (Type::Float(..), Type::Unsigned(size)) => {
+                format!("((_ fp.to_ubv {}) RTZ {})", size, name)
+            }
+            (Type::Array(_), _) | (_, Type::Array(_)) => {
+                name
+            }
         }
     }
 
+    fn capture_var(&self, sym: &TemporalSymbol, name: &str, typ: &Type) {
+        if let Some(capture) = self.captures.borrow_mut().last_mut() {
+            capture.declared.push((sym.0, name.to_string(), typ.clone()));
+        }
+    }
+
+    fn capture(&self, smt: &str) {
+        if let Some(capture) = self.captures.borrow_mut().last_mut() {
+            // anything deeper is gone when the body is done
+            if capture.depth == self.symbol_stack.borrow().len() {
+                capture.defs.push(smt.to_string());
+            }
+        }
+    }
+
+    // asserts that define a symbol, as opposed to asking the solver something
+    fn define(&self, smt: &str) -> Result<(), String> {
+        self.capture(smt);
+        self.solver.borrow_mut().assert(smt)
+    }
+
+    fn is_float(&self, sym: &TemporalSymbol) -> bool {
+        matches!(self.vars.borrow().get(&sym.0).map(|v|&v.typ), Some(Type::Float(..)))
+    }
+
 
     pub fn branch(&mut self) {
         self.branches.push(Vec::new());
@@ -143,27 +232,10 @@ impl Solver {
     pub fn theory(&mut self, sym: Symbol, args: Vec<Type>, name: &str, t: Type) {
         let lname = format!("theory{}_{}", sym, name.replace(|c: char| !c.is_ascii_alphanumeric(), "_"));
 
-        let mut debug_args = Vec::new();
-        for t in args {
-            match t {
-                Type::Bool => {
-                    debug_args.push("Bool".to_string());
-                }
-                Type::Signed(size) | Type::Unsigned(size) =>  {
-                    debug_args.push(format!("(_ BitVec {})", size));
-                }
-            }
-        }
-        let debug_args = debug_args.join(" ");
+        let debug_args = args.iter().map(|t|t.sort()).collect::<Vec<String>>().join(" ");
 
-        match t {
-            Type::Signed(size) | Type::Unsigned(size) => {
-                write!(self.solver.borrow_mut(), "(declare-fun {} ({}) (_ BitVec {})); theory {}\n", lname, debug_args, size, name).unwrap();
-            }
-            Type::Bool => {
-                write!(self.solver.borrow_mut(), "(declare-fun {} ({}) Bool); theory {}\n", lname, debug_args, name).unwrap();
-            }
-        };
+        writeln!(self.solver.borrow_mut(), "; theory {} ({}) {}", name, debug_args, t.sort()).unwrap();
+        self.solver.borrow_mut().declare(&lname, &args, &t).unwrap();
         self.theories.insert(sym, lname);
         self.checkpoint();
     }
@@ -179,7 +251,7 @@ impl Solver {
         let tmp_debug  = self.var(&tmp);
 
         let theory = &self.theories[&theory];
-        self.solver.borrow_mut().assert(&format!("(= {} ({} {}) )", tmp_debug, theory, debug_args)).unwrap();
+        self.define(&format!("(= {} ({} {}) )", tmp_debug, theory, debug_args)).unwrap();
         self.checkpoint();
     }
 
@@ -227,7 +299,7 @@ impl Solver {
         let smt_if   = self.var_as(&rhs_if, t.clone());
         let smt_else = self.var_as(&rhs_else, t.clone());
 
-        self.solver.borrow_mut().assert(&format!("(= {}  (ite {} {} {})  )",
+        self.define(&format!("(= {}  (ite {} {} {})  )",
             smt_lhs,
             branch_smt,
             smt_if,
@@ -241,11 +313,12 @@ impl Solver {
         let smt_lhs = self.var_as(&lhs, t.clone());
         let smt_rhs = self.var_as(&rhs, t.clone());
 
-        write!(self.solver.borrow_mut(), "(assert (! (= {} {}) :named A{}))",
+        self.capture(&format!("(= {} {})", smt_lhs, smt_rhs));
+        self.solver.borrow_mut().assert(&format!("(! (= {} {}) :named A{})",
             smt_lhs,
             smt_rhs,
             self.assert_counter,
-        ).unwrap();
+        )).unwrap();
         self.assert_counter += 1;
 
         self.checkpoint();
@@ -257,7 +330,7 @@ impl Solver {
 
         match typ {
             Type::Unsigned(size) | Type::Signed(size) => {
-                self.solver.borrow_mut().assert(&format!("(= {} (_ bv{} {}))\n",
+                self.define(&format!("(= {} (_ bv{} {}))\n",
                     smt_lhs,
                     val,
                     size
@@ -265,59 +338,131 @@ impl Solver {
             }
             Type::Bool => {
                 if val > 0 {
-                    self.solver.borrow_mut().assert(&smt_lhs).unwrap();
+                    self.define(&smt_lhs).unwrap();
                 } else {
-                    self.solver.borrow_mut().assert(&format!("(not {})", smt_lhs)).unwrap();
+                    self.define(&format!("(not {})", smt_lhs)).unwrap();
                 }
             }
+            Type::Float(..) => {
+                let smt_val = format!("((_ to_fp_unsigned 11 53) {} (_ bv{} 64))", ROUNDING, val);
+                self.define(&format!("(= {} {})\n",
+                    smt_lhs,
+                    Self::float_into(smt_val, &typ),
+                )).unwrap();
+            }
+            Type::Array(_) => panic!("ICE: literal of array type"),
+        }
+        self.checkpoint();
+    }
+
+    pub fn literal_float(&mut self, tmp: Symbol, val: f64, typ: Type) {
+        let smt_lhs = self.var(&(tmp,0));
+
+        let smt_val = format!("((_ to_fp 11 53) #x{:016x})", val.to_bits());
+        self.define(&format!("(= {} {})\n",
+            smt_lhs,
+            Self::float_into(smt_val, &typ),
+        )).unwrap();
+        self.checkpoint();
+    }
+
+    // round a double into a float of different precision
+    fn float_into(smt_double: String, typ: &Type) -> String {
+        match typ {
+            Type::Float(11, 53) => smt_double,
+            Type::Float(e, s) => format!("((_ to_fp {} {}) {} {})", e, s, ROUNDING, smt_double),
+            _ => panic!("ICE: float literal into {:?}", typ),
         }
+    }
+
+    /// tmp is one of the fp classification predicates like fp.isNaN over arg
+    pub fn float_class(&mut self, tmp: Symbol, arg: TemporalSymbol, predicate: &str) {
+        let smt_tmp = self.var(&(tmp,0));
+        let smt_arg = self.var(&arg);
+        self.define(&format!("(= {} ({} {}))", smt_tmp, predicate, smt_arg)).unwrap();
         self.checkpoint();
     }
 
+    /// tmp is true if lhs op rhs fits into t.
+    /// the operation is repeated on operands widened by one bit, or twice the bits for multiply,
+    /// which only gives the same result if nothing was lost.
+    pub fn no_overflow(
+        &mut self,
+        tmp:    Symbol,
+        lhs:    TemporalSymbol,
+        rhs:    TemporalSymbol,
+        op:     crate::ast::InfixOperator,
+        t:      Type,
+    ) {
+        let smt_tmp  = self.var_as(&(tmp,0), Type::Bool);
+        let smt_lhs  = self.var(&lhs);
+        let smt_rhs  = self.var(&rhs);
+
+        let (size, extend) = match t {
+            Type::Signed(size)   => (size, "sign_extend"),
+            Type::Unsigned(size) => (size, "zero_extend"),
+            _ => panic!("ICE: no_overflow undefined on {:?}", t),
+        };
+        let (smt_op, by) = match op {
+            crate::ast::InfixOperator::Add       => ("bvadd", 1),
+            crate::ast::InfixOperator::Subtract  => ("bvsub", 1),
+            crate::ast::InfixOperator::Multiply  => ("bvmul", size),
+            _ => panic!("ICE: no_overflow undefined for {:?}", op),
+        };
+
+        self.define(&format!("(= {} (= ((_ {} {}) ({} {} {})) ({} ((_ {} {}) {}) ((_ {} {}) {}))))",
+            smt_tmp,
+            extend, by, smt_op, smt_lhs, smt_rhs,
+            smt_op, extend, by, smt_lhs, extend, by, smt_rhs,
+        )).unwrap();
+        self.checkpoint();
+    }
+
+    // must call from within assert or value
     pub fn infix_op_will_wrap(
         &self,
-        _lhs:    TemporalSymbol,
-        _rhs:    TemporalSymbol,
-        _op:     crate::ast::InfixOperator,
-        _t:      Type,
+        model:  &ModelRef,
+        lhs:    TemporalSymbol,
+        rhs:    TemporalSymbol,
+        op:     crate::ast::InfixOperator,
+        t:      Type,
     ) -> bool {
-        return false;
-
-        // Int isn't supported anymore
-        /*
-
-        let smt_lhs = self.var_as(&lhs, t);
-        let smt_rhs = self.var_as(&rhs, t);
+        let (size, signed) = match t {
+            Type::Signed(size)   => (size, true),
+            Type::Unsigned(size) => (size, false),
+            _ => return false,
+        };
+        let (lhs, rhs) = match (self.extract(model, lhs), self.extract(model, rhs)) {
+            (Some(lhs), Some(rhs)) => (lhs, rhs),
+            _ => return false,
+        };
 
-        match op {
-            crate::ast::InfixOperator::Add       => {
-                self.solver.borrow_mut().push();
-                self.solver.borrow_mut().assert(&capi_lhs.to_int(false).add(&[&capi_rhs.to_int(false)])
-                     ._eq(&capi_lhs.bvadd(&capi_rhs).to_int(false)));
-                let r = self.solve();
-                self.solver.borrow_mut().pop(1);
-                !r
-            }
-            crate::ast::InfixOperator::Subtract  => {
-                self.solver.borrow_mut().push();
-                self.solver.borrow_mut().assert(&capi_lhs.to_int(false).sub(&[&capi_rhs.to_int(false)])
-                    ._eq(&capi_lhs.bvsub(&capi_rhs).to_int(false)));
-                let r = self.solve();
-                self.solver.borrow_mut().pop(1);
-                !r
-            }
-            crate::ast::InfixOperator::Multiply  => {
-                self.solver.borrow_mut().push();
-                self.solver.borrow_mut().assert(&capi_lhs.to_int(false).mul(&[&capi_rhs.to_int(false)])
-                    ._eq(&capi_lhs.bvmul(&capi_rhs).to_int(false)));
-                let r = self.solve();
-                self.solver.borrow_mut().pop(1);
-                !r
+        let widen = |v: u64| -> i128 {
+            if signed && size < 64 {
+                (((v << (64 - size)) as i64) >> (64 - size)) as i128
+            } else if signed {
+                v as i64 as i128
+            } else {
+                v as i128
             }
-            _ => false,
+        };
+        let (lhs, rhs) = (widen(lhs), widen(rhs));
 
+        let v = match op {
+            crate::ast::InfixOperator::Add       => lhs.checked_add(rhs),
+            crate::ast::InfixOperator::Subtract  => lhs.checked_sub(rhs),
+            crate::ast::InfixOperator::Multiply  => lhs.checked_mul(rhs),
+            _ => return false,
+        };
+        let (min, max) = if signed {
+            (-(1i128 << (size - 1)), (1i128 << (size - 1)) - 1)
+        } else {
+            (0, (1i128 << size) - 1)
+        };
+        match v {
+            Some(v) => v < min || v > max,
+            None    => true,
         }
-        */
     }
 
     pub fn infix_op(
@@ -334,99 +479,123 @@ impl Solver {
         let smt_lhs  = self.var(&lhs);
         let smt_rhs  = self.var(&rhs);
 
+        if self.is_float(&lhs) {
+            let smt_op = match op {
+                crate::ast::InfixOperator::Equals    => format!("(fp.eq {} {})", smt_lhs, smt_rhs),
+                crate::ast::InfixOperator::Nequals   => format!("(not (fp.eq {} {}))", smt_lhs, smt_rhs),
+                crate::ast::InfixOperator::Moreeq    => format!("(fp.geq {} {})", smt_lhs, smt_rhs),
+                crate::ast::InfixOperator::Lesseq    => format!("(fp.leq {} {})", smt_lhs, smt_rhs),
+                crate::ast::InfixOperator::Lessthan  => format!("(fp.lt {} {})", smt_lhs, smt_rhs),
+                crate::ast::InfixOperator::Morethan  => format!("(fp.gt {} {})", smt_lhs, smt_rhs),
+                crate::ast::InfixOperator::Add       => format!("(fp.add {} {} {})", ROUNDING, smt_lhs, smt_rhs),
+                crate::ast::InfixOperator::Subtract  => format!("(fp.sub {} {} {})", ROUNDING, smt_lhs, smt_rhs),
+                crate::ast::InfixOperator::Multiply  => format!("(fp.mul {} {} {})", ROUNDING, smt_lhs, smt_rhs),
+                crate::ast::InfixOperator::Divide    => format!("(fp.div {} {} {})", ROUNDING, smt_lhs, smt_rhs),
+                _ => panic!("ICE: {:?} undefined on floating point", op),
+            };
+            self.define(&format!("(= {} {})", smt_tmp, smt_op)).unwrap();
+            self.checkpoint();
+            return;
+        }
+
         match op {
             crate::ast::InfixOperator::Equals    => {
                 assert!(t == Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (= {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (= {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             },
             crate::ast::InfixOperator::Nequals   => {
                 assert!(t == Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (not (= {} {})))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (not (= {} {})))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Add       => {
                 assert!(t != Type::Bool);
-                self.solver.borrow_mut().assert(&format!(" (=  {} (bvadd {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!(" (=  {} (bvadd {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Subtract  => {
                 assert!(t != Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (bvsub {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (bvsub {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Multiply  => {
                 assert!(t != Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (bvmul {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (bvmul {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Divide    => {
                 assert!(t != Type::Bool);
 
                 if signed {
-                    self.solver.borrow_mut().assert(&format!(" (=  {} (bvsdiv {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!(" (=  {} (bvsdiv {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 } else {
-                    self.solver.borrow_mut().assert(&format!("(=  {} (bvudiv {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!("(=  {} (bvudiv {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 }
             }
             crate::ast::InfixOperator::Bitxor => {
                 assert!(t != Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (bvxnor {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (bvxor {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Booland => {
                 assert!(t == Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (and {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (and {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Boolor => {
                 assert!(t == Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (or {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (or {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Moreeq => {
                 assert!(t == Type::Bool);
                 if signed {
-                    self.solver.borrow_mut().assert(&format!("(=  {} (bvsge {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!("(=  {} (bvsge {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 } else {
-                    self.solver.borrow_mut().assert(&format!("(=  {} (bvuge {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!("(=  {} (bvuge {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 }
             }
             crate::ast::InfixOperator::Lesseq => {
                 assert!(t == Type::Bool);
                 if signed {
-                    self.solver.borrow_mut().assert(&format!("(=  {} (bvsle {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!("(=  {} (bvsle {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 } else {
-                    self.solver.borrow_mut().assert(&format!("(=  {} (bvule {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!("(=  {} (bvule {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 }
             }
             crate::ast::InfixOperator::Lessthan => {
                 assert!(t == Type::Bool);
                 if signed {
-                    self.solver.borrow_mut().assert(&format!("(= {} (bvslt {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!("(= {} (bvslt {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 } else {
-                    self.solver.borrow_mut().assert(&format!("(=  {} (bvult {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!("(=  {} (bvult {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 }
             }
             crate::ast::InfixOperator::Morethan => {
                 assert!(t == Type::Bool);
                 if signed {
-                    self.solver.borrow_mut().assert(&format!("(=  {} (bvsgt {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!("(=  {} (bvsgt {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 } else {
-                    self.solver.borrow_mut().assert(&format!("(=  {} (bvugt {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                    self.define(&format!("(=  {} (bvugt {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
                 }
             }
             crate::ast::InfixOperator::Shiftleft => {
                 assert!(t != Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (bvshl {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (bvshl {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Shiftright => {
                 assert!(t != Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (bvlshr {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (bvlshr {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Modulo => {
                 assert!(t != Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (bvsmod {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                // C rounds towards zero, so the sign follows the dividend as in bvsrem, not the divisor as in bvsmod
+                if signed {
+                    self.define(&format!("(=  {} (bvsrem {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                } else {
+                    self.define(&format!("(=  {} (bvurem {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                }
             }
             crate::ast::InfixOperator::Bitand => {
                 assert!(t != Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (bvand {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (bvand {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
             crate::ast::InfixOperator::Bitor => {
                 assert!(t != Type::Bool);
-                self.solver.borrow_mut().assert(&format!("(=  {} (bvor {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
+                self.define(&format!("(=  {} (bvor {} {}))", smt_tmp, smt_lhs, smt_rhs)).unwrap();
             }
         };
         self.checkpoint();
@@ -444,19 +613,22 @@ impl Solver {
                      t:   Type,
     ) {
 
-        let size =  match  t {
-            Type::Signed(v) | Type::Unsigned(v) => v,
-            Type::Bool => panic!("ICE: postfix_op undefined on bool"),
-        };
-
         let smt_to    = self.var_as(&to, t.clone());
         let smt_from  = self.var_as(&from, t.clone());
 
-        let smt_op = match op {
-            crate::ast::PostfixOperator::Increment  => format!("(bvadd {} (_ bv1 {}))", smt_from, size),
-            crate::ast::PostfixOperator::Decrement  => format!("(bvsub {} (_ bv1 {}))", smt_from, size),
+        let smt_op = match (t, op) {
+            (Type::Signed(size), crate::ast::PostfixOperator::Increment) |
+            (Type::Unsigned(size), crate::ast::PostfixOperator::Increment) => format!("(bvadd {} (_ bv1 {}))", smt_from, size),
+            (Type::Signed(size), crate::ast::PostfixOperator::Decrement) |
+            (Type::Unsigned(size), crate::ast::PostfixOperator::Decrement) => format!("(bvsub {} (_ bv1 {}))", smt_from, size),
+            (Type::Float(e, s), crate::ast::PostfixOperator::Increment) =>
+                format!("(fp.add {} {} ((_ to_fp {} {}) {} (_ bv1 8)))", ROUNDING, smt_from, e, s, ROUNDING),
+            (Type::Float(e, s), crate::ast::PostfixOperator::Decrement) =>
+                format!("(fp.sub {} {} ((_ to_fp {} {}) {} (_ bv1 8)))", ROUNDING, smt_from, e, s, ROUNDING),
+            (Type::Bool, _) => panic!("ICE: postfix_op undefined on bool"),
+            (Type::Array(_), _) => panic!("ICE: postfix_op undefined on array"),
         };
-        self.solver.borrow_mut().assert(&format!("(= {} {} )", smt_to, smt_op)).unwrap();
+        self.define(&format!("(= {} {} )", smt_to, smt_op)).unwrap();
 
         self.checkpoint();
     }
@@ -478,14 +650,25 @@ impl Solver {
                     crate::ast::PrefixOperator::Bitnot     => format!("(bvxnor {} #x{} )", smt_from, "ff".repeat(size as usize/8)),
                     crate::ast::PrefixOperator::Increment  => format!("(bvadd {} (_ bv1 {}))", smt_from, size),
                     crate::ast::PrefixOperator::Decrement  => format!("(bvsub {} (_ bv1 {}))", smt_from, size),
+                    crate::ast::PrefixOperator::Negate     => format!("(bvneg {})", smt_from),
                     _ => unreachable!(),
                 };
-                self.solver.borrow_mut().assert(&format!("(= {} {} )", smt_to, smt_op)).unwrap();
+                self.define(&format!("(= {} {} )", smt_to, smt_op)).unwrap();
             }
             Type::Bool => {
                 assert!(op == crate::ast::PrefixOperator::Boolnot);
-                self.solver.borrow_mut().assert(&format!("(= {} (not {} ))", smt_to, smt_from)).unwrap();
+                self.define(&format!("(= {} (not {} ))", smt_to, smt_from)).unwrap();
+            }
+            Type::Float(e, s) => {
+                let smt_op = match op {
+                    crate::ast::PrefixOperator::Increment  => format!("(fp.add {} {} ((_ to_fp {} {}) {} (_ bv1 8)))", ROUNDING, smt_from, e, s, ROUNDING),
+                    crate::ast::PrefixOperator::Decrement  => format!("(fp.sub {} {} ((_ to_fp {} {}) {} (_ bv1 8)))", ROUNDING, smt_from, e, s, ROUNDING),
+                    crate::ast::PrefixOperator::Negate     => format!("(fp.neg {})", smt_from),
+                    _ => panic!("ICE: {:?} undefined on floating point", op),
+                };
+                self.define(&format!("(= {} {} )", smt_to, smt_op)).unwrap();
             }
+            Type::Array(_) => panic!("ICE: prefix_op undefined on array"),
         }
         self.checkpoint();
     }
@@ -500,6 +683,130 @@ impl Solver {
         };
     }
 
+    /// read one element of the memory ptr points to
+    pub fn select(&mut self, tmp: Symbol, heap: TemporalSymbol, ptr: TemporalSymbol, index: TemporalSymbol) {
+        let smt_tmp   = self.var(&(tmp, 0));
+        let smt_heap  = self.var(&heap);
+        let smt_ptr   = self.var_as(&ptr, Type::Unsigned(64));
+        let smt_index = self.var_as(&index, Type::Unsigned(64));
+        self.define(&format!("(= {} (select (select {} {}) {}))", smt_tmp, smt_heap, smt_ptr, smt_index)).unwrap();
+        self.checkpoint();
+    }
+
+    /// write one element of the memory ptr points to, if the current branch happens
+    pub fn store(&mut self, heap: TemporalSymbol, prev: TemporalSymbol, ptr: TemporalSymbol, index: TemporalSymbol, value: TemporalSymbol, t: Type) {
+        let branch_smt = self.build_branch_bundle();
+        let smt_heap  = self.var(&heap);
+        let smt_prev  = self.var(&prev);
+        let smt_ptr   = self.var_as(&ptr, Type::Unsigned(64));
+        let smt_index = self.var_as(&index, Type::Unsigned(64));
+        let smt_value = self.var_as(&value, t);
+        self.define(&format!("(= {} (ite {} (store {} {} (store (select {} {}) {} {})) {}))",
+            smt_heap,
+            branch_smt,
+            smt_prev,
+            smt_ptr,
+            smt_prev,
+            smt_ptr,
+            smt_index,
+            smt_value,
+            smt_prev,
+        )).unwrap();
+        self.checkpoint();
+    }
+
+    /// the memory ptr points to may be anything, if the current branch happens.
+    /// all other memory stays the same
+    pub fn forget(&mut self, heap: TemporalSymbol, prev: TemporalSymbol, ptr: TemporalSymbol) {
+        let branch_smt = self.build_branch_bundle();
+        let smt_heap  = self.var(&heap);
+        let smt_prev  = self.var(&prev);
+        let smt_ptr   = self.var_as(&ptr, Type::Unsigned(64));
+        self.define(&format!("(= {} (ite {} (store {} {} (select {} {})) {}))",
+            smt_heap,
+            branch_smt,
+            smt_prev,
+            smt_ptr,
+            smt_heap,
+            smt_ptr,
+            smt_prev,
+        )).unwrap();
+        self.checkpoint();
+    }
+
+    /// var is bound by the quantifier ended with quantifier_end,
+    /// and so is everything defined from it in between
+    pub fn quantifier_begin(&mut self, var: Symbol) {
+        self.push("quantifier");
+        self.captures.borrow_mut().push(Capture{
+            var,
+            depth:      self.symbol_stack.borrow().len(),
+            declared:   Vec::new(),
+            defs:       Vec::new(),
+        });
+    }
+
+    /// tmp is true if body holds for every (forall) or some (exists) value in range
+    pub fn quantifier_end(&mut self, tmp: Symbol, forall: bool, range: TemporalSymbol, body: TemporalSymbol) {
+        let smt_range = self.var_as(&range, Type::Bool);
+        let smt_body  = self.var_as(&body, Type::Bool);
+        let capture = self.captures.borrow_mut().pop().expect("ICE: quantifier_end without quantifier_begin");
+        self.pop("end of quantifier");
+
+        let mut bound : HashSet<String> = capture.declared.iter()
+            .filter(|(sym,_,_)|*sym == capture.var)
+            .map(|(_,name,_)|name.clone())
+            .collect();
+
+        // definitions that don't depend on the quantified variable hold outside of it too
+        let mut defs = Vec::new();
+        let mut free = Vec::new();
+        for def in capture.defs {
+            let mut words = def.split(|c: char| c.is_whitespace() || c == '(' || c == ')').filter(|w|!w.is_empty());
+            let defined = match words.next() {
+                Some("=") => words.next().map(|w|w.to_string()),
+                _ => None,
+            };
+            if def.split(|c: char| c.is_whitespace() || c == '(' || c == ')').any(|w|bound.contains(w)) {
+                if let Some(defined) = defined {
+                    bound.insert(defined);
+                }
+                defs.push(def);
+            } else {
+                free.push(def);
+            }
+        }
+        for def in free {
+            self.define(&def).unwrap();
+        }
+
+        let vars = capture.declared.iter()
+            .filter(|(_,name,_)|bound.contains(name))
+            .map(|(_,name,t)|format!("({} {})", name, t.sort()))
+            .collect::<Vec<String>>();
+
+        let defs = defs.join(" ");
+        let smt = if forall {
+            format!("(=> (and true {} {}) {})", defs, smt_range, smt_body)
+        } else {
+            format!("(and true {} {} {})", defs, smt_range, smt_body)
+        };
+        let smt = if vars.is_empty() {
+            smt
+        } else {
+            format!("({} ({}) {})", if forall { "forall" } else { "exists" }, vars.join(" "), smt)
+        };
+
+        let smt_tmp = self.var(&(tmp, 0));
+        self.define(&format!("(= {} {})", smt_tmp, smt)).unwrap();
+        self.checkpoint();
+    }
+
+    /// contents of memory and quantifiers are only modelled if the module uses quantifiers
+    pub fn quantifiers(&self) -> bool {
+        self.quantifiers
+    }
+
     pub fn attest(&mut self, lhs: TemporalSymbol, compare: bool) -> bool {
         let mut smt = self.var(&lhs);
 
@@ -507,12 +814,15 @@ impl Solver {
             smt = format!("(not {})", smt);
         }
 
-        write!(self.solver.borrow_mut(), "(assert (! {} :named A{}))",
+        self.solver.borrow_mut().assert(&format!("(! {} :named A{})",
             smt,
             self.assert_counter,
-        ).unwrap();
+        )).unwrap();
         self.assert_counter += 1;
 
+        if self.trusted {
+            return true;
+        }
         self.solve()
         //#[cfg(debug_assertions)]
         //{
@@ -529,23 +839,29 @@ impl Solver {
     // must call from within assert or value
     pub fn extract(&self, _model: &ModelRef, lhs: TemporalSymbol) -> Option<u64> {
         let smt_lhs  = self.var(&lhs);
-        let value = self.solver.borrow_mut().get_values(&[smt_lhs]).unwrap().get(0).unwrap().1.clone();
+        let value = self.solver.borrow_mut().get_values(&[smt_lhs]).ok()?.get(0)?.1.clone();
 
         debug!("extracted: {}", value);
         if value == "false" {
             return Some(0);
         } else if value == "true" {
             return Some(1);
-        } else  if value.starts_with("#x") {
-            if let Ok(v) = u64::from_str_radix(&value[2..], 16) {
-                return Some(v)
-            }
-        } else if value.starts_with("#b") {
-            if let Ok(v) = u64::from_str_radix(&value[2..], 2) {
-                return Some(v)
-            }
         }
-        None
+        parse_bits(&value)
+    }
+
+    // must call from within assert or value
+    pub fn extract_invocation(&self, _model: &ModelRef, theory: Symbol, args: Vec<TemporalSymbol>) -> Option<u64> {
+        let args = args.iter().map(|arg|self.var(arg)).collect::<Vec<String>>().join(" ");
+        let smt  = format!("({} {})", self.theories.get(&theory)?, args);
+        let value = self.solver.borrow_mut().get_values(&[smt]).ok()?.get(0)?.1.clone();
+
+        if value == "false" {
+            return Some(0);
+        } else if value == "true" {
+            return Some(1);
+        }
+        parse_bits(&value)
     }
 
     // asserts are false if
@@ -558,6 +874,11 @@ impl Solver {
     {
         assert!(lhs.len() > 0);
 
+        if self.trusted {
+            return with(true, None);
+        }
+        self.unknown.set(false);
+
         let branch_smt = self.build_branch_bundle();
 
         let mut asserts_debug = Vec::new();
@@ -574,6 +895,10 @@ impl Solver {
         let rs = self.solve();
 
         let r = match rs {
+            true if self.unknown.get() => {
+                write!(self.solver.borrow_mut(), "; unknown / timeout\n").unwrap();
+                with(false, None)
+            }
             true => {
                 write!(self.solver.borrow_mut(), "; sat / failed\n").unwrap();
                 with(false, Some(ModelRef(())))
@@ -593,35 +918,31 @@ impl Solver {
     {
         let smt_lhs  = self.var(&lhs);
 
+        self.unknown.set(false);
         if !self.solve() {
             warn!("model broke earlier");
             return with(Assertion::Unsolveable, None);
         }
-        let value = self.solver.borrow_mut().get_values(&[smt_lhs.clone()]).unwrap().get(0).unwrap().1.clone();
+        let value = match self.model_value(&smt_lhs) {
+            Some(value) => value,
+            None => return with(Assertion::Unknown, None),
+        };
         write!(self.solver.borrow_mut(), ";  = {:?}\n", value).unwrap();
-        let val = if value.starts_with("#x") {
-            if let Ok(v) = u64::from_str_radix(&value[2..], 16) {
-                v
-            } else {
-                return with(Assertion::Unsolveable, None);
-            }
-        } else if value.starts_with("#b") {
-            if let Ok(v) = u64::from_str_radix(&value[2..], 2) {
-                v
-            } else {
-                return with(Assertion::Unsolveable, None);
-            }
-        } else {
-            return with(Assertion::Unsolveable, None);
+        let val = match parse_bits(&value) {
+            Some(v) => v,
+            None => return with(Assertion::Unsolveable, None),
         };
 
         self.solver.borrow_mut().push(1).unwrap();
         self.solver.borrow_mut().assert(&format!("(not (= {} {}))", smt_lhs, value)).unwrap();
         let rr = match self.solve() {
+            true if self.unknown.get() => {
+                with(Assertion::Unknown, None)
+            }
             false => {
                 self.solver.borrow_mut().pop(1).unwrap();
                 self.solver.borrow_mut().push(1).unwrap();
-                if self.solve() {
+                if self.solve() && !self.unknown.get() {
                     with(Assertion::Constrained(val), Some(ModelRef(())))
                 } else {
                     with(Assertion::Constrained(val), None)
@@ -641,11 +962,15 @@ impl Solver {
     {
         let smt_lhs  = self.var(&lhs);
 
+        self.unknown.set(false);
         if !self.solve() {
             warn!("model broke earlier");
             return with(Assertion::Unsolveable, None);
         }
-        let value = self.solver.borrow_mut().get_values(&[smt_lhs.clone()]).unwrap().get(0).unwrap().1.clone();
+        let value = match self.model_value(&smt_lhs) {
+            Some(value) => value,
+            None => return with(Assertion::Unknown, None),
+        };
 
         write!(self.solver.borrow_mut(), ";  = {:?}\n", value).unwrap();
 
@@ -660,10 +985,13 @@ impl Solver {
         self.solver.borrow_mut().push(1).unwrap();
         self.solver.borrow_mut().assert(&format!("(not (= {} {}))", smt_lhs, value)).unwrap();
         let rr = match self.solve() {
+            true if self.unknown.get() => {
+                with(Assertion::Unknown, None)
+            }
             false => {
                 self.solver.borrow_mut().pop(1).unwrap();
                 self.solver.borrow_mut().push(1).unwrap();
-                if self.solve() {
+                if self.solve() && !self.unknown.get() {
                     with(Assertion::Constrained(val), Some(ModelRef(())))
                 } else {
                     with(Assertion::Constrained(val), None)
@@ -704,6 +1032,18 @@ impl Solver {
     }
 
 
+    /// the function was proven before. asserts pass without asking the solver,
+    /// only values needed to transform the code are still solved.
+    pub fn trust(&mut self) {
+        write!(self.solver.borrow_mut(), "; proof is cached, asserts are not checked\n").unwrap();
+        self.trusted = true;
+    }
+
+    /// the FloatingPoint theory is only enabled when the module uses floats
+    pub fn floats(&self) -> bool {
+        self.floats
+    }
+
     pub fn check_ded(&self, sym: Symbol, here: &crate::ast::Location) {
         if let Some(name) = self.ded_syms.get(&sym) {
             emit_warn(format!("ICE: reuse of dead symbol '{}'", name), &[
@@ -714,7 +1054,33 @@ impl Solver {
 
 
     pub fn solve(&self) -> bool {
-        self.solver.borrow_mut().check_sat().unwrap()
+        match self.solver.borrow_mut().check_sat() {
+            Ok(Some(sat)) => sat,
+            // nothing may be considered proven when the solver gave up
+            Ok(None) | Err(_) => {
+                self.unknown.set(true);
+                true
+            }
+        }
+    }
+
+    // the value of lhs in the current model, if the solver produced one
+    fn model_value(&self, smt_lhs: &str) -> Option<String> {
+        if self.unknown.get() {
+            return None;
+        }
+        let values = self.solver.borrow_mut().get_values(&[smt_lhs.to_string()]).ok()?;
+        Some(values.get(0)?.1.clone())
+    }
+
+    /// the last assert failed because the solver gave up, not because it found a counterexample
+    pub fn unknown(&self) -> bool {
+        self.unknown.get()
+    }
+
+    /// milliseconds the solver may spend on each query
+    pub fn timeout(&self) -> usize {
+        self.timeout
     }
 
     #[cfg(debug_assertions)]
@@ -740,7 +1106,7 @@ impl Solver {
         }
     }
 
-    pub fn new(module_name: String, hints: &HashMap<String, String>) -> Self {
+    pub fn new(module_name: String, hints: &HashMap<String, String>, floats: bool, quantifiers: bool) -> Self {
 
         //Config::set_global_param_value(":model.partial", "true");
         //Config::set_global_param_value(":parallel.enable", "true");
@@ -748,10 +1114,36 @@ impl Solver {
 
 
 
-        let conf = if let (Some("yices2"), Ok(_)) = (hints.get("solver").as_ref().map(|s|s.as_str()), which::which("yices_smt2_mt")) {
-            rsmt2::SmtConf::yices_2()
+        let hint = hints.get("solver").map(|s|s.as_str());
+
+        // @timeout= on the function wins over --smt-timeout and zz.toml
+        let timeout = match hints.get("timeout") {
+            Some(t) => t.parse().unwrap_or_else(|_|{
+                warn!("@timeout={} is not a number of milliseconds", t);
+                DEFAULT_TIMEOUT
+            }),
+            None => match TIMEOUT.load(Ordering::Relaxed) {
+                0 => DEFAULT_TIMEOUT,
+                t => t,
+            },
+        };
+
+        let portfolio = PORTFOLIO.load(Ordering::Relaxed) || hint == Some("portfolio");
+        // yices does not do quantifiers
+        let yices = !portfolio && !quantifiers && hint == Some("yices2") && which::which("yices_smt2_mt").is_ok();
+        let (mut solver, floats) = if portfolio {
+            (Backend::Portfolio(portfolio::Portfolio::new(floats, quantifiers, timeout)), floats)
+        } else if yices {
+            // yices has no floating point theory
+            let mut conf = rsmt2::SmtConf::yices_2();
+            conf.option(portfolio::yices_timeout(timeout));
+            (Backend::Process(rsmt2::Solver::new(conf, Rsmt2Junk).unwrap()), false)
+        } else if cfg!(feature = "native-z3") && hint == Some("native") {
+            (Backend::native(timeout), floats)
         } else if which::which("z3").is_ok() {
-            rsmt2::SmtConf::z3()
+            (Backend::Process(rsmt2::Solver::new(rsmt2::SmtConf::z3(), Rsmt2Junk).unwrap()), floats)
+        } else if cfg!(feature = "native-z3") {
+            (Backend::native(timeout), floats)
         } else {
             panic!("z3 required in PATH")
         };
@@ -759,17 +1151,23 @@ impl Solver {
 
         std::fs::create_dir_all("./target/ssa/").unwrap();
         let outfile = format!("./target/ssa/{}.smt2", module_name);
-
-
-        let mut solver = rsmt2::Solver::new(conf, Rsmt2Junk).unwrap();
         solver.path_tee(outfile).unwrap();
 
         //insanly slow and we don't actually use it.
         //write!(solver,"(set-option :produce-unsat-cores true)\n").unwrap();
 
-        write!(solver,"(set-logic QF_UFBV)\n").unwrap();
+        if floats || quantifiers {
+            write!(solver,"(set-logic ALL)\n").unwrap();
+        } else {
+            write!(solver,"(set-logic QF_UFBV)\n").unwrap();
+        }
         //write!(solver,"(set-option :parallel.enable true)\n").unwrap();
-        //write!(solver,"(set-option :timeout {})\n", TIMEOUT.load(Ordering::Relaxed) as u64).unwrap();
+
+        // yices does not know the option and gets it on the command line. the portfolio sets it for each solver in its own way,
+        // and linked z3 through its solver parameters
+        if !yices && !portfolio {
+            write!(solver,"(set-option :timeout {})\n", timeout).unwrap();
+        }
 
 
         Self {
@@ -781,6 +1179,12 @@ impl Solver {
             symbol_stack:   RefCell::new(vec![Vec::new()]),
             ded_syms:       HashMap::new(),
             assert_counter: 0,
+            floats,
+            trusted:        false,
+            timeout,
+            unknown:        Cell::new(false),
+            quantifiers,
+            captures:       RefCell::new(Vec::new()),
         }
     }
 
@@ -790,6 +1194,739 @@ impl Solver {
 
 
 
+/// where the smt2 text goes.
+/// either a solver process driven by rsmt2, z3 linked into zz, or a race between solver processes
+enum Backend {
+    Process(rsmt2::Solver<Rsmt2Junk>),
+    #[cfg(feature = "native-z3")]
+    Native(native::Native),
+    Portfolio(portfolio::Portfolio),
+}
+
+impl Backend {
+    #[cfg(feature = "native-z3")]
+    fn native(timeout: usize) -> Self {
+        Backend::Native(native::Native::new(timeout))
+    }
+    #[cfg(not(feature = "native-z3"))]
+    fn native(_timeout: usize) -> Self {
+        unreachable!("ICE: zz was built without the native-z3 feature")
+    }
+
+    fn path_tee(&mut self, path: String) -> Result<(), String> {
+        match self {
+            Backend::Process(s) => s.path_tee(path).map_err(|e|e.to_string()),
+            #[cfg(feature = "native-z3")]
+            Backend::Native(s)  => s.path_tee(path).map_err(|e|e.to_string()),
+            Backend::Portfolio(s) => s.path_tee(path).map_err(|e|e.to_string()),
+        }
+    }
+
+    fn declare(&mut self, name: &str, args: &[Type], ret: &Type) -> Result<(), String> {
+        match self {
+            #[cfg(feature = "native-z3")]
+            Backend::Native(s)  => s.declare(name, args, ret),
+            _ => {
+                let args = args.iter().map(|t|t.sort()).collect::<Vec<String>>().join(" ");
+                writeln!(self, "(declare-fun {} ({}) {})", name, args, ret.sort()).map_err(|e|e.to_string())
+            }
+        }
+    }
+
+    fn assert(&mut self, expr: &str) -> Result<(), String> {
+        match self {
+            Backend::Process(s) => s.assert(expr).map_err(|e|e.to_string()),
+            #[cfg(feature = "native-z3")]
+            Backend::Native(s)  => s.assert(expr),
+            Backend::Portfolio(s) => s.command(&format!("(assert {})\n", expr)),
+        }
+    }
+
+    fn push(&mut self, n: u8) -> Result<(), String> {
+        match self {
+            Backend::Process(s) => s.push(n).map_err(|e|e.to_string()),
+            #[cfg(feature = "native-z3")]
+            Backend::Native(s)  => s.push(n),
+            Backend::Portfolio(s) => s.command(&format!("(push {})\n", n)),
+        }
+    }
+
+    fn pop(&mut self, n: u8) -> Result<(), String> {
+        match self {
+            Backend::Process(s) => s.pop(n).map_err(|e|e.to_string()),
+            #[cfg(feature = "native-z3")]
+            Backend::Native(s)  => s.pop(n),
+            Backend::Portfolio(s) => s.command(&format!("(pop {})\n", n)),
+        }
+    }
+
+    // none if the solver gave up
+    fn check_sat(&mut self) -> Result<Option<bool>, String> {
+        match self {
+            Backend::Process(s) => s.check_sat_or_unk().map_err(|e|e.to_string()),
+            #[cfg(feature = "native-z3")]
+            Backend::Native(s)  => s.check_sat(),
+            Backend::Portfolio(s) => s.check_sat(),
+        }
+    }
+
+    fn get_values(&mut self, exprs: &[String]) -> Result<Vec<(String, String)>, String> {
+        match self {
+            Backend::Process(s) => s.get_values(exprs).map_err(|e|e.to_string()),
+            #[cfg(feature = "native-z3")]
+            Backend::Native(s)  => s.get_values(exprs),
+            Backend::Portfolio(s) => parse_values(&s.get_values(exprs)?),
+        }
+    }
+}
+
+impl Write for Backend {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Backend::Process(s) => s.write(buf),
+            #[cfg(feature = "native-z3")]
+            Backend::Native(s)  => s.write(buf),
+            Backend::Portfolio(s) => s.write(buf),
+        }
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Backend::Process(s) => s.flush(),
+            #[cfg(feature = "native-z3")]
+            Backend::Native(s)  => s.flush(),
+            Backend::Portfolio(s) => s.flush(),
+        }
+    }
+}
+
+/// print how often each solver of the portfolio was first
+pub fn portfolio_stats() {
+    let wins = portfolio::WINS.lock().unwrap();
+    if wins.is_empty() {
+        return;
+    }
+    let total : usize = wins.values().sum();
+    let stats = wins.iter()
+        .map(|(name, n)|format!("{} {} ({:.0}%)", name, n, *n as f64 * 100.0 / total as f64))
+        .collect::<Vec<String>>();
+    info!("solver portfolio wins: {}", stats.join(", "));
+}
+
+mod portfolio {
+    use std::collections::BTreeMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::{Arc, Mutex};
+    use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+    use std::time::{Duration, Instant};
+
+    /// first definitive answers per solver, over the whole build
+    pub static WINS: Mutex<BTreeMap<&'static str, usize>> = Mutex::new(BTreeMap::new());
+
+    enum Job {
+        Text(String),
+        Check(usize),
+        Values(String, Sender<Result<String, String>>),
+    }
+
+    /// yices takes the timeout per check-sat on the command line, in whole seconds
+    pub fn yices_timeout(timeout: usize) -> String {
+        format!("--timeout={}", timeout.div_ceil(1000))
+    }
+
+    struct Candidate {
+        name:       &'static str,
+        cmd:        Vec<String>,
+        options:    Vec<String>,
+    }
+
+    struct Member {
+        candidate:  Candidate,
+        jobs:       Sender<Job>,
+        child:      Arc<Mutex<Child>>,
+        // the query this solver has not answered yet
+        busy:       Option<usize>,
+    }
+
+    /// every available solver gets all commands. a check-sat returns with the first sat or unsat.
+    /// a solver that is still busy with an earlier query when the next one comes in is restarted,
+    /// so the slow ones never fall further and further behind.
+    pub struct Portfolio {
+        members:    Vec<Member>,
+        answers_tx: Sender<(usize, usize, Option<bool>)>,
+        answers:    Receiver<(usize, usize, Option<bool>)>,
+        query:      usize,
+        // the solver that answered the last check-sat, and so has the model
+        winner:     Option<usize>,
+        timeout:    Duration,
+        // everything but check-sat and get-value, to bring a restarted solver back to the current state
+        transcript: String,
+        tee:        Option<std::fs::File>,
+    }
+
+    impl Portfolio {
+        pub fn new(floats: bool, quantifiers: bool, timeout: usize) -> Self {
+            let (answers_tx, answers) = channel();
+
+            let mut candidates = vec![
+                Candidate {
+                    name:       "z3",
+                    cmd:        vec!["z3".into(), "-in".into(), "-smt2".into()],
+                    options:    vec![format!("(set-option :timeout {})\n", timeout)],
+                },
+                Candidate {
+                    name:       "cvc5",
+                    cmd:        vec!["cvc5".into(), "--lang".into(), "smt2".into(), "--incremental".into()],
+                    options:    vec![
+                        "(set-option :produce-models true)\n".to_string(),
+                        format!("(set-option :tlimit-per {})\n", timeout),
+                    ],
+                },
+            ];
+            // yices has no floating point theory and no quantifiers
+            if !floats && !quantifiers {
+                candidates.push(Candidate {
+                    name:       "yices2",
+                    cmd:        vec!["yices_smt2_mt".into(), "--incremental".into(), "--smt2-model-format".into(),
+                                     yices_timeout(timeout)],
+                    options:    vec![],
+                });
+            }
+
+            let mut members = Vec::new();
+            for candidate in candidates {
+                if which::which(&candidate.cmd[0]).is_err() {
+                    continue;
+                }
+                match spawn(members.len(), candidate, answers_tx.clone()) {
+                    Ok(member) => members.push(member),
+                    Err(candidate) => warn!("cannot start {} for the solver portfolio", candidate.name),
+                }
+            }
+            if members.is_empty() {
+                panic!("solver portfolio needs at least one of z3, cvc5 or yices_smt2_mt in PATH");
+            }
+
+            Portfolio {
+                members,
+                answers_tx,
+                answers,
+                query:      0,
+                winner:     None,
+                // each solver enforces the timeout itself. this is only for the ones that don't
+                timeout:    Duration::from_millis(timeout as u64 + 1000),
+                transcript: String::new(),
+                tee:        None,
+            }
+        }
+
+        // a solver still working on a query nobody waits for anymore is killed
+        // and replaced by a fresh one that is fed everything it missed
+        fn restart(&mut self, index: usize) {
+            let _ = self.members[index].child.lock().unwrap().kill();
+            let cmd     = self.members[index].candidate.cmd.clone();
+            let options = self.members[index].candidate.options.clone();
+            let name    = self.members[index].candidate.name;
+            let candidate = Candidate{name, cmd, options};
+            match spawn(index, candidate, self.answers_tx.clone()) {
+                Ok(member) => {
+                    let _ = member.jobs.send(Job::Text(self.transcript.clone()));
+                    self.members[index] = member;
+                }
+                Err(_) => {
+                    warn!("cannot restart {} for the solver portfolio", name);
+                    // its thread is gone, so every further job fails to send and it drops out of the race
+                }
+            }
+        }
+
+        // the answers that came in after check_sat returned
+        fn collect_late_answers(&mut self) {
+            while let Ok((member, query, _)) = self.answers.try_recv() {
+                if self.members[member].busy == Some(query) {
+                    self.members[member].busy = None;
+                }
+            }
+        }
+
+        pub fn path_tee(&mut self, path: String) -> std::io::Result<()> {
+            self.tee = Some(std::fs::File::create(path)?);
+            Ok(())
+        }
+
+        pub fn command(&mut self, cmd: &str) -> Result<(), String> {
+            self.write_all(cmd.as_bytes()).map_err(|e|e.to_string())
+        }
+
+        pub fn check_sat(&mut self) -> Result<Option<bool>, String> {
+            if let Some(tee) = &mut self.tee {
+                tee.write_all(b"(check-sat)\n").map_err(|e|e.to_string())?;
+            }
+            self.query += 1;
+            self.winner = None;
+
+            self.collect_late_answers();
+            for i in 0..self.members.len() {
+                if self.members[i].busy.is_some() {
+                    self.restart(i);
+                }
+            }
+
+            let mut racing = 0;
+            for member in &mut self.members {
+                member.busy = None;
+                if member.jobs.send(Job::Check(self.query)).is_ok() {
+                    member.busy = Some(self.query);
+                    racing += 1;
+                }
+            }
+            if racing == 0 {
+                return Err("all solvers of the portfolio died".to_string());
+            }
+
+            let deadline = Instant::now() + self.timeout;
+            while racing > 0 {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                let (member, query, answer) = match self.answers.recv_timeout(wait) {
+                    Ok(v) => v,
+                    // the ones still busy are restarted with the next query
+                    Err(RecvTimeoutError::Timeout) => return Ok(None),
+                    Err(e) => return Err(e.to_string()),
+                };
+                if self.members[member].busy == Some(query) {
+                    self.members[member].busy = None;
+                }
+                // late answer to an earlier query
+                if query != self.query {
+                    continue;
+                }
+                racing -= 1;
+                if let Some(sat) = answer {
+                    self.winner = Some(member);
+                    *WINS.lock().unwrap().entry(self.members[member].candidate.name).or_insert(0) += 1;
+                    return Ok(Some(sat));
+                }
+            }
+            Ok(None)
+        }
+
+        pub fn get_values(&mut self, exprs: &[String]) -> Result<String, String> {
+            let winner = match self.winner {
+                Some(v) => &self.members[v],
+                None => return Err("get-value without a model".to_string()),
+            };
+            let (tx, rx) = channel();
+            winner.jobs.send(Job::Values(exprs.join(" "), tx)).map_err(|e|e.to_string())?;
+            rx.recv().map_err(|e|e.to_string())?
+        }
+    }
+
+    impl Write for Portfolio {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if let Some(tee) = &mut self.tee {
+                tee.write_all(buf)?;
+            }
+            let text = String::from_utf8_lossy(buf).to_string();
+            self.transcript.push_str(&text);
+            for member in &self.members {
+                let _ = member.jobs.send(Job::Text(text.clone()));
+            }
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Drop for Portfolio {
+        // solvers still busy with a query nobody waits for anymore
+        fn drop(&mut self) {
+            for member in &self.members {
+                let _ = member.child.lock().unwrap().kill();
+            }
+        }
+    }
+
+    // each solver is driven by its own thread, so a slow one never blocks the others
+    fn spawn(index: usize, candidate: Candidate, answers: Sender<(usize, usize, Option<bool>)>) -> Result<Member, Candidate> {
+        let name = candidate.name;
+        let mut child = match Command::new(&candidate.cmd[0])
+            .args(&candidate.cmd[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => return Err(candidate),
+        };
+        let (mut stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => return Err(candidate),
+        };
+        let mut stdout = BufReader::new(stdout);
+        let child = Arc::new(Mutex::new(child));
+
+        let (jobs, rx) = channel();
+        let reaper = child.clone();
+        std::thread::spawn(move || {
+            for job in rx {
+                match job {
+                    Job::Text(text) => {
+                        if stdin.write_all(text.as_bytes()).is_err() {
+                            break;
+                        }
+                    }
+                    Job::Check(query) => {
+                        if stdin.write_all(b"(check-sat)\n").and_then(|_|stdin.flush()).is_err() {
+                            let _ = answers.send((index, query, None));
+                            break;
+                        }
+                        let answer = read_check_sat(name, &mut stdout);
+                        let _ = answers.send((index, query, answer.unwrap_or(None)));
+                        if answer.is_none() {
+                            break;
+                        }
+                    }
+                    Job::Values(exprs, reply) => {
+                        let r = stdin.write_all(format!("(get-value ({}))\n", exprs).as_bytes())
+                            .and_then(|_|stdin.flush())
+                            .map_err(|e|e.to_string())
+                            .and_then(|_|read_sexpr(name, &mut stdout));
+                        let _ = reply.send(r);
+                    }
+                }
+            }
+            // the solver may still be running even though it stopped talking to us
+            let mut child = reaper.lock().unwrap();
+            let _ = child.kill();
+            let _ = child.wait();
+        });
+
+        for option in &candidate.options {
+            let _ = jobs.send(Job::Text(option.clone()));
+        }
+        Ok(Member{candidate, jobs, child, busy: None})
+    }
+
+    // none if the solver is gone
+    fn read_check_sat<R: BufRead>(name: &str, stdout: &mut R) -> Option<Option<bool>> {
+        loop {
+            let mut line = String::new();
+            if stdout.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            match line.trim() {
+                "sat"                   => return Some(Some(true)),
+                "unsat"                 => return Some(Some(false)),
+                "unknown" | "timeout"   => return Some(None),
+                ""                      => (),
+                // complaints about commands it doesn't support
+                other                   => debug!("{}: {}", name, other),
+            }
+        }
+    }
+
+    fn read_sexpr<R: BufRead>(name: &str, stdout: &mut R) -> Result<String, String> {
+        let mut sexpr = String::new();
+        let mut depth = 0;
+        loop {
+            let mut line = String::new();
+            if stdout.read_line(&mut line).map_err(|e|e.to_string())? == 0 {
+                return Err(format!("{} exited", name));
+            }
+            if depth == 0 && !line.trim_start().starts_with('(') {
+                continue;
+            }
+            if depth == 0 && line.trim_start().starts_with("(error") {
+                return Err(format!("{}: {}", name, line.trim()));
+            }
+            for c in line.chars() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => (),
+                }
+            }
+            sexpr.push_str(&line);
+            if depth <= 0 {
+                return Ok(sexpr);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "native-z3")]
+mod native {
+    use std::collections::HashMap;
+    use std::io::Write;
+    use crate::z3::{Config, Context, FuncDecl, Model, Params, SatResult, Solver, Sort};
+    use crate::z3::ast::Ast;
+    use super::Type;
+
+    /// z3 in process, driven through its typed api.
+    /// declarations are z3 function declarations, assertions are parsed against them into z3 terms,
+    /// and model values are evaluated in the z3 model, so nothing goes through a pipe.
+    pub struct Native {
+        // the solver and everything made by it borrow the context, so they are declared, and dropped, first
+        decls:      HashMap<String, FuncDecl<'static>>,
+        model:      Option<Model<'static>>,
+        solver:     Solver<'static>,
+        ctx:        Box<Context>,
+        tee:        Option<std::fs::File>,
+    }
+
+    impl Native {
+        pub fn new(timeout: usize) -> Self {
+            let mut cfg = Config::new();
+            cfg.set_model_generation(true);
+            let ctx = Box::new(Context::new(&cfg));
+            // the context is boxed, so it stays where it is for as long as Native exists,
+            // and Native drops everything borrowing from it before the box
+            let ctx_ref : &'static Context = unsafe { &*(ctx.as_ref() as *const Context) };
+            let solver = Solver::new(ctx_ref);
+            let mut params = Params::new(ctx_ref);
+            params.set_u32("timeout", timeout as u32);
+            solver.set_params(&params);
+            Native {
+                decls:      HashMap::new(),
+                model:      None,
+                solver,
+                ctx,
+                tee:        None,
+            }
+        }
+
+        fn ctx(&self) -> &'static Context {
+            unsafe { &*(self.ctx.as_ref() as *const Context) }
+        }
+
+        fn sort(&self, t: &Type) -> Sort<'static> {
+            let ctx = self.ctx();
+            match t {
+                Type::Bool => Sort::bool(ctx),
+                Type::Signed(s) | Type::Unsigned(s) => Sort::bitvector(ctx, *s),
+                Type::Float(e, s) => Sort::float(ctx, *e, *s),
+                Type::Array(t) => Sort::array(ctx, &Sort::bitvector(ctx, 64), &self.sort(t)),
+            }
+        }
+
+        // the declarations a piece of smt2 text refers to
+        fn mentioned(&self, smt: &str) -> Vec<&FuncDecl<'static>> {
+            let mut names : Vec<&str> = smt
+                .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                .filter(|w| !w.is_empty())
+                .collect();
+            names.sort_unstable();
+            names.dedup();
+            names.into_iter().filter_map(|name| self.decls.get(name)).collect()
+        }
+
+        fn tee(&mut self, text: &str) -> Result<(), String> {
+            if let Some(tee) = &mut self.tee {
+                tee.write_all(text.as_bytes()).map_err(|e|e.to_string())?;
+            }
+            Ok(())
+        }
+
+        pub fn path_tee(&mut self, path: String) -> std::io::Result<()> {
+            self.tee = Some(std::fs::File::create(path)?);
+            Ok(())
+        }
+
+        pub fn declare(&mut self, name: &str, args: &[Type], ret: &Type) -> Result<(), String> {
+            let args = args.iter().map(|t|self.sort(t)).collect::<Vec<Sort>>();
+            let ret  = self.sort(ret);
+            let decl = FuncDecl::new(self.ctx(), name, &args.iter().collect::<Vec<&Sort>>(), &ret);
+            self.tee(&format!("(declare-fun {} ({}) {})\n",
+                name,
+                args.iter().map(|s|s.to_string()).collect::<Vec<String>>().join(" "),
+                ret,
+            ))?;
+            self.decls.insert(name.to_string(), decl);
+            Ok(())
+        }
+
+        pub fn assert(&mut self, expr: &str) -> Result<(), String> {
+            let smt = format!("(assert {})\n", expr);
+            self.tee(&smt)?;
+            for a in self.ctx().parse_smtlib2(&smt, &self.mentioned(&smt))? {
+                self.solver.assert(&a);
+            }
+            Ok(())
+        }
+
+        pub fn push(&mut self, n: u8) -> Result<(), String> {
+            self.tee(&format!("(push {})\n", n))?;
+            for _ in 0..n {
+                self.solver.push();
+            }
+            Ok(())
+        }
+
+        pub fn pop(&mut self, n: u8) -> Result<(), String> {
+            self.tee(&format!("(pop {})\n", n))?;
+            self.model = None;
+            self.solver.pop(n as u32);
+            Ok(())
+        }
+
+        pub fn check_sat(&mut self) -> Result<Option<bool>, String> {
+            self.tee("(check-sat)\n")?;
+            self.model = None;
+            match self.solver.check() {
+                SatResult::Sat => {
+                    self.model = Some(self.solver.get_model());
+                    Ok(Some(true))
+                }
+                SatResult::Unsat    => Ok(Some(false)),
+                SatResult::Unknown  => Ok(None),
+            }
+        }
+
+        pub fn get_values(&mut self, exprs: &[String]) -> Result<Vec<(String, String)>, String> {
+            self.tee(&format!("(get-value ({}))\n", exprs.join(" ")))?;
+            let model = match &self.model {
+                Some(model) => model,
+                None => return Err("get-value without a model".to_string()),
+            };
+            let mut values = Vec::new();
+            for expr in exprs {
+                let term  = self.ctx().parse_smtlib2_term(expr, &self.mentioned(expr))?;
+                let value = match model.eval(&term, true) {
+                    Some(value) => value,
+                    None => return Err(format!("cannot evaluate {} in the model", expr)),
+                };
+                values.push((expr.clone(), value.simplify().to_string()));
+            }
+            Ok(values)
+        }
+    }
+
+    // comments, the logic and options only go to the tee.
+    // everything the solver needs to know comes in through the typed methods above
+    impl Write for Native {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if let Some(tee) = &mut self.tee {
+                tee.write_all(buf)?;
+            }
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+}
+
+
+// ((expr value) (expr value))
+fn parse_values(response: &str) -> Result<Vec<(String, String)>, String> {
+    let inner = response.trim();
+    if !inner.starts_with('(') || !inner.ends_with(')') {
+        return Err(format!("unexpected get-value response: {}", response));
+    }
+    let pairs = split_sexprs(&inner[1..inner.len() - 1]);
+    let mut values = Vec::new();
+    for pair in pairs {
+        let pair = pair.trim();
+        if !pair.starts_with('(') || !pair.ends_with(')') {
+            return Err(format!("unexpected get-value response: {}", response));
+        }
+        let parts = split_sexprs(&pair[1..pair.len() - 1]);
+        if parts.len() != 2 {
+            return Err(format!("unexpected get-value response: {}", response));
+        }
+        values.push((parts[0].to_string(), parts[1].to_string()));
+    }
+    Ok(values)
+}
+
+// split a list of s-expressions at the top level
+fn split_sexprs(s: &str) -> Vec<&str> {
+    let mut r = Vec::new();
+    let mut depth = 0;
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => {
+                if depth == 0 && start.is_none() {
+                    start = Some(i);
+                }
+                depth += 1;
+            }
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    r.push(&s[start.take().unwrap()..=i]);
+                }
+            }
+            c if c.is_whitespace() => {
+                if depth == 0 {
+                    if let Some(st) = start.take() {
+                        r.push(&s[st..i]);
+                    }
+                }
+            }
+            _ => {
+                if start.is_none() {
+                    start = Some(i);
+                }
+            }
+        }
+    }
+    if let Some(st) = start {
+        r.push(&s[st..]);
+    }
+    r
+}
+
+// bitvector and floating point model values as raw bits.
+// floats come as (fp sign exponent significand) or one of the special values
+fn parse_bits(value: &str) -> Option<u64> {
+    if value.starts_with("#x") {
+        return u64::from_str_radix(&value[2..], 16).ok();
+    }
+    if value.starts_with("#b") {
+        return u64::from_str_radix(&value[2..], 2).ok();
+    }
+
+    let parts : Vec<&str> = value.trim_matches(|c| c == '(' || c == ')').split_whitespace().collect();
+    match parts.as_slice() {
+        ["fp", sign, exponent, significand] => {
+            let mut bits = String::new();
+            for part in &[sign, exponent, significand] {
+                if part.starts_with("#b") {
+                    bits.push_str(&part[2..]);
+                } else if part.starts_with("#x") {
+                    for c in part[2..].chars() {
+                        bits.push_str(&format!("{:04b}", c.to_digit(16)?));
+                    }
+                } else {
+                    return None;
+                }
+            }
+            u64::from_str_radix(&bits, 2).ok()
+        }
+        ["_", special, e, s] => {
+            let e : u32 = e.parse().ok()?;
+            let s : u32 = s.parse().ok()?;
+            let exponent_max = ((1u64 << e) - 1) << (s - 1);
+            let sign = 1u64 << (e + s - 1);
+            match *special {
+                "+zero" => Some(0),
+                "-zero" => Some(sign),
+                "+oo"   => Some(exponent_max),
+                "-oo"   => Some(sign | exponent_max),
+                "NaN"   => Some(exponent_max | 1 << (s - 2)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+
 use rsmt2::{SmtRes, parse::ValueParser, parse::ExprParser};
 
 
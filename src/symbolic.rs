@@ -1,11 +1,18 @@
 use crate::flatten;
 use crate::ast;
 use crate::name::Name;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 use super::parser::{self, emit_warn, emit_debug};
 use ast::Tags;
 use crate::smt::{Solver, self};
+use crate::make;
+use crate::report;
 use super::Error;
+use fasthash::metro;
 
 pub type Symbol = usize;
 pub type TemporalSymbol = (Symbol, u64);
@@ -38,6 +45,9 @@ enum Value{
         ret:    Option<ast::Typed>,
         callsite_assert: Vec<ast::Expression>,
         callsite_effect: Vec<ast::Expression>,
+        // index of the printf style format string argument
+        format: Option<usize>,
+        modifies: Vec<ast::Expression>,
     },
     SelfCall{
         selfarg: Box<ast::Expression>,
@@ -109,10 +119,62 @@ pub struct Symbolic {
     current_function_ret:   Option<Symbol>,
     current_function_model: Vec<ast::Expression>,
     current_call:           Vec<ast::Location>,
-    in_loop:    bool,
+    current_function_decreases: Option<(ast::Expression, Symbol)>,
+    loops:      Vec<Loop>,
     in_model:   bool,
+    current_function_args:  Vec<(Symbol, ast::NamedArg)>,
+    // inputs of the current function from the first failed proof
+    counterexample: RefCell<Option<Vec<(ast::NamedArg, Input)>>>,
+    obligations:    RefCell<Vec<report::Obligation>>,
+    // all memory of one element type, as smt array indexed by pointer value, then by index
+    contents:       HashMap<smt::Type, Symbol>,
+    // array members read from contents, and from which pointer value at which index
+    elements:       HashMap<Symbol, (Symbol, TemporalSymbol, TemporalSymbol)>,
+    current_function_lemma: bool,
+    current_function_modifies: Vec<ast::Expression>,
+    // depth of expressions that are only proven, never executed
+    proof:          usize,
+    // ghost state read and functions called by the statement being executed
+    ghost_reads:    Vec<ast::Location>,
+    calls:          Vec<ast::Location>,
 }
 
+/// record the solver model of failed proofs as concrete function inputs
+pub static COUNTEREXAMPLES: AtomicBool = AtomicBool::new(false);
+
+/// also prove that unsigned arithmetic does not wrap around. signed overflow is always checked
+pub static UNSIGNED_OVERFLOW: AtomicBool = AtomicBool::new(false);
+
+/// arguments to a function that make one of its proofs fail
+pub struct Counterexample {
+    pub function:   Name,
+    pub message:    String,
+    pub loc:        ast::Location,
+    pub args:       Vec<(ast::NamedArg, Input)>,
+}
+
+/// a function input as extracted from the model. values are C literals
+pub enum Input {
+    Unknown,
+    Value(String),
+    Struct(Vec<(String, Input)>),
+    Null,
+    Buffer{
+        len:    u64,
+        first:  Box<Input>,
+    },
+}
+
+
+#[derive(Clone)]
+struct Loop {
+    invariants: Vec<ast::Expression>,
+    step:       Vec<Box<ast::Statement>>,
+    // the measure and its value at the start of the iteration
+    decreases:  Option<(ast::Expression, Symbol)>,
+    // the invariants held at every break executed so far
+    breaks_keep: bool,
+}
 
 pub enum ScopeReturn {
     NoReturn,
@@ -121,6 +183,41 @@ pub enum ScopeReturn {
 
 impl Symbolic {
     fn execute_module(&mut self, module: &mut flatten::Module, fun: usize) -> Result<(), Error> {
+        self.execute_declarations(module)?;
+
+        let recursion = mutual_recursion(module, fun);
+        let (fun,_,_) = &mut module.d[fun];
+
+        match &mut fun.def {
+            ast::Def::Function{args, body, ret, callassert, calleffect, callattests, decreases, attr, modifies, ..} => {
+                self.current_function_lemma = attr.contains_key("lemma");
+                self.current_function_modifies = modifies.clone();
+                if self.current_function_lemma {
+                    self.check_lemma(args, ret.as_ref(), &fun.loc)?;
+                }
+                if let (Some(measure), Some(call)) = (decreases.as_ref(), recursion) {
+                    return Err(self.trace("mutual recursion is not supported by decreases".to_string(), vec![
+                        (call, "this call leads back to the function through another function".to_string()),
+                        (measure.loc().clone(), "only direct recursive calls are checked against this measure".to_string()),
+                    ]));
+                }
+                self.execute_function(&fun.name, args, ret.as_ref(), body, callassert, calleffect, callattests, decreases.as_ref())?;
+                if !self.ssa.solve() {
+                    return Err(self.trace(format!("function is unprovable"), vec![
+                        (fun.loc.clone(), format!("this function body is impossible to prove"))
+                    ]));
+                }
+            },
+            _ => unreachable!(),
+        }
+
+
+        Ok(())
+    }
+
+    // builtins and everything declared in the module, without executing any function body.
+    // initializers of globals are transformed just like function bodies
+    fn execute_declarations(&mut self, module: &mut flatten::Module) -> Result<(), Error> {
         self.current_module_name = module.name.human_name();
 
         // built in len theory
@@ -271,15 +368,39 @@ impl Symbolic {
 
                     self.ssa.theory(sym, ssa_args, &d.name, Self::smt_type(&ret));
                 },
-                ast::Def::Function{args, vararg, ret, callassert, calleffect, ..} => {
+                ast::Def::Function{args, vararg, ret, callassert, calleffect, hints, attr, modifies, ..} => {
+
+                    let format = match hints.get("format") {
+                        None => None,
+                        Some(fmt) => {
+                            let at = match args.iter().position(|a|&a.name == fmt) {
+                                Some(at) => at,
+                                None => {
+                                    return Err(self.trace("invalid format attribute".to_string(), vec![
+                                        (d.loc.clone(), format!("'{}' is not an argument of {}", fmt, d.name))
+                                    ]));
+                                }
+                            };
+                            if !*vararg || at + 1 != args.len() || args[at].typed.ptr.len() != 1 {
+                                return Err(self.trace("invalid format attribute".to_string(), vec![
+                                    (args[at].loc.clone(), "the format string must be the last char * argument before ...".to_string())
+                                ]));
+                            }
+                            Some(at)
+                        }
+                    };
 
+                    let mut tags = Tags::new();
+                    if let Some(loc) = attr.get("lemma") {
+                        tags.insert("lemma".to_string(), String::new(), loc.clone());
+                    }
                     let sym = self.alloc(Name::from(&d.name), ast::Typed{
                         t:      ast::Type::Other(Name::from(&d.name.clone())),
                         ptr:    Vec::new(),
                         loc:    d.loc.clone(),
                         tail:   ast::Tail::None,
                     },
-                    d.loc.clone(), Tags::new()
+                    d.loc.clone(), tags
                     )?;
 
                     self.memory[sym].value = Value::Function {
@@ -289,6 +410,8 @@ impl Symbolic {
                         ret:    ret.as_ref().map(|r|r.typed.clone()),
                         callsite_assert: callassert.clone(),
                         callsite_effect: calleffect.clone(),
+                        format,
+                        modifies: modifies.clone(),
                     };
                     self.ssa_mark_safe(sym, &d.loc)?;
 
@@ -362,7 +485,7 @@ impl Symbolic {
                         d.loc.clone(), Tags::new()
                     )?;
                 },
-                ast::Def::Struct {..} => {
+                ast::Def::Struct {variants, ..} => {
                     self.alloc(
                         Name::from(&d.name),
                         ast::Typed{
@@ -373,6 +496,25 @@ impl Symbolic {
                         },
                         d.loc.clone(), Tags::new()
                     )?;
+                    for (value, variant) in variants.iter().enumerate() {
+                        let mut localname = Name::from(&d.name);
+                        localname.push(variant.name.clone());
+
+                        let t = ast::Typed {
+                            t:      ast::Type::ULiteral,
+                            loc:    variant.loc.clone(),
+                            ptr:    Vec::new(),
+                            tail:   ast::Tail::None,
+                        };
+                        let sym = self.alloc(
+                            localname,
+                            t,
+                            variant.loc.clone(),
+                            ast::Tags::new(),
+                        )?;
+                        self.memory[sym].value = Value::Integer(value as u64);
+                        self.ssa.literal(sym, value as u64, self.memory[sym].t.clone());
+                    }
                 },
                 ast::Def::Enum{names} => {
                     self.alloc(
@@ -430,22 +572,6 @@ impl Symbolic {
                 ast::Def::Include {..} => {},
             }
         }
-
-        let (fun,_,_) = &mut module.d[fun];
-
-        match &mut fun.def {
-            ast::Def::Function{args, body, ret, callassert, calleffect, callattests, ..} => {
-                self.execute_function(&fun.name, args, ret.as_ref(), body, callassert, calleffect, callattests)?;
-                if !self.ssa.solve() {
-                    return Err(self.trace(format!("function is unprovable"), vec![
-                        (fun.loc.clone(), format!("this function body is impossible to prove"))
-                    ]));
-                }
-            },
-            _ => unreachable!(),
-        }
-
-
         Ok(())
     }
 
@@ -458,6 +584,7 @@ impl Symbolic {
         callassert: &mut Vec<ast::Expression>,
         calleffect: &mut Vec<ast::Expression>,
         callattests: &mut Vec<ast::Expression>,
+        decreases: Option<&ast::Expression>,
     ) -> Result<(), Error> {
 
         self.push(format!("function {}", name));
@@ -469,12 +596,25 @@ impl Symbolic {
         self.ssa.branch();
         self.current_function_name  = name.clone();
         self.current_function_model = calleffect.clone();
+        self.proof = 0;
+        self.ghost_reads.clear();
+        self.calls.clear();
 
         let mut prev : Option<Symbol> =  None;
         for i in 0..args.len() {
             let argname = Name::from(&args[i].name);
-            let sym = self.alloc(argname.clone(), args[i].typed.clone(), args[i].loc.clone(), args[i].tags.clone())?;
+            let mut tags = args[i].tags.clone();
+            if self.current_function_lemma {
+                tags.insert("ghost".to_string(), String::new(), args[i].loc.clone());
+            }
+            let sym = self.alloc(argname.clone(), args[i].typed.clone(), args[i].loc.clone(), tags)?;
             self.memory[sym].value = Value::Unconstrained(format!("passed by value as {}", argname));
+            self.current_function_args.push((sym, args[i].clone()));
+
+            // callsite sources are always generated as string literals
+            if args[i].tags.contains("callsite_source") && args[i].typed.ptr.len() == 1 {
+                self.ssa_mark_nullterm(sym, &args[i].loc)?;
+            }
 
             if args[i].tags.contains("tail") {
                 let prev = match prev {
@@ -560,7 +700,10 @@ impl Symbolic {
         }
 
         for callassert in callassert.iter_mut().chain(callattests.iter_mut()) {
-            let sym = self.execute_expr(callassert)?;
+            self.proof += 1;
+            let sym = self.execute_expr(callassert);
+            self.proof -= 1;
+            let sym = sym?;
             if self.memory[sym].t != smt::Type::Bool {
                 return Err(self.trace(format!("expected boolean, got {}", self.memory[sym].typed), vec![
                     (callassert.loc().clone(), format!("coercion to boolean is not well defined"))
@@ -573,6 +716,43 @@ impl Symbolic {
             }
         }
 
+        // struct invariants hold for everything passed by pointer
+        for (sym, arg) in self.current_function_args.clone() {
+            if arg.tags.contains("unsafe") {
+                continue;
+            }
+            for (inv, loc) in self.struct_invariants(sym)? {
+                if !self.ssa.attest(inv, true) {
+                    return Err(self.trace("struct invariant broke ssa solution".to_string(), vec![
+                        (loc, "there may be conflicting constraints".to_string()),
+                        (arg.loc.clone(), "assumed for this argument".to_string()),
+                    ]));
+                }
+            }
+        }
+
+        // the model may refer to the state at entry with old()
+        let mut model = std::mem::take(&mut self.current_function_model);
+        let mut bound = Vec::new();
+        self.proof += 1;
+        for callsite_effect in &mut model {
+            self.bind_old(callsite_effect, &mut bound)?;
+        }
+        self.proof -= 1;
+        for (name, sym) in bound {
+            self.cur().locals.insert(name, sym);
+        }
+        self.current_function_model = model;
+
+        for place in self.current_function_modifies.clone() {
+            self.frame_place(&place)?;
+        }
+
+        self.current_function_decreases = match decreases {
+            Some(measure) => Some((measure.clone(), self.measure(measure)?)),
+            None => None,
+        };
+
         if let Some(ret) = ret {
             self.current_function_ret = Some(self.alloc(Name::from("return"), ret.typed.clone(), ret.typed.loc.clone(), ast::Tags::new())?);
         } else {
@@ -591,7 +771,41 @@ impl Symbolic {
     }
 
 
+    // a lemma is never executed, so it can only be used for the effect of its model
+    fn check_lemma(&self, args: &[ast::NamedArg], ret: Option<&ast::AnonArg>, loc: &ast::Location) -> Result<(), Error> {
+        if let Some(ret) = ret {
+            return Err(self.trace("lemma cannot return a value".to_string(), vec![
+                (ret.typed.loc.clone(), "use a model clause to describe what the lemma proves".to_string()),
+                (loc.clone(), "in this lemma".to_string()),
+            ]));
+        }
+        for arg in args {
+            if arg.tags.contains("mut") || arg.typed.ptr.iter().any(|ptr|ptr.tags.contains("mut")) {
+                return Err(self.trace("lemma arguments cannot be mutable".to_string(), vec![
+                    (arg.loc.clone(), "a lemma cannot change executable state".to_string()),
+                    (loc.clone(), "in this lemma".to_string()),
+                ]));
+            }
+        }
+        Ok(())
+    }
+
     fn check_function_model(&mut self, end: &ast::Location) -> Result<(), Error> {
+        self.proof += 1;
+        let r = self.prove_function_model(end);
+        self.proof -= 1;
+        r
+    }
+
+    fn prove_function_model(&mut self, end: &ast::Location) -> Result<(), Error> {
+        // callers assume the invariants of everything they passed by mut pointer still hold
+        for (sym, arg) in self.current_function_args.clone() {
+            let mutable = arg.typed.ptr.last().map(|ptr|ptr.tags.contains("mut")) == Some(true);
+            if mutable && !arg.tags.contains("unsafe") {
+                self.prove_struct_invariants(sym, end, "when returning here")?;
+            }
+        }
+
         if self.current_function_model.len() < 1 {
             return Ok(());
         }
@@ -620,13 +834,14 @@ impl Symbolic {
 
 
         // try the fast path
-        let ok = self.ssa.assert(syms.clone(), |val,_|val);
+        let obligations = locs.iter().map(|loc|("model", loc.clone())).collect::<Vec<_>>();
+        let ok = self.prove_all(&obligations, syms.clone());
 
 
         // one assertion broke, try them individually
         if !ok {
             for (sym,loc) in syms.into_iter().zip(locs.into_iter()) {
-                self.ssa.assert(vec![sym], |a,model| match a {
+                self.prove("model", &loc, vec![sym], |a,model| match a {
                     false  => {
                         let mut estack = vec![
                             (loc.clone(), format!("function does not behave like this model")),
@@ -650,129 +865,1223 @@ impl Symbolic {
 
     }
 
+    // the invariants of the struct that ptr points to and of the structs in it, evaluated with self bound to ptr
+    fn struct_invariants(&mut self, ptr: Symbol) -> Result<Vec<(TemporalSymbol, ast::Location)>, Error> {
+        let mut pointee = self.memory[ptr].typed.clone();
+        if pointee.ptr.pop().is_none() || !self.has_invariants(&pointee) {
+            return Ok(Vec::new());
+        }
+        let (mut invariants, fields) = match &pointee.t {
+            ast::Type::Other(name) => match self.defs.get(name) {
+                Some(ast::Def::Struct{invariants, fields, ..}) => (invariants.clone(), fields.clone()),
+                _ => return Ok(Vec::new()),
+            },
+            _ => return Ok(Vec::new()),
+        };
+
+        //dont expose any symbols other than self
+        let global_only = vec![self.stack[0].clone()];
+        let stack_original = std::mem::replace(&mut self.stack, global_only);
+        self.push("struct invariant".to_string());
+        self.cur().locals.insert(Name::from("self"), ptr);
 
-    fn type_coersion(&mut self, a: Symbol, b: Symbol, here: &ast::Location) -> Result<(ast::Typed, Symbol, Symbol), Error>  {
-        if let Value::Theory{..} =  self.memory[a].value {
-            return Err(self.trace(format!("theory '{}' is not a real world object", self.memory[a].name ), vec![
-                (here.clone(), format!("cannot use theory in this instance"))
-            ]));
+        let mut r = Vec::new();
+        self.proof += 1;
+        for invariant in &mut invariants {
+            let sym = self.execute_expr(invariant)?;
+            if self.memory[sym].t != smt::Type::Bool {
+                return Err(self.trace(format!("expected boolean, got {}", self.memory[sym].typed), vec![
+                    (invariant.loc().clone(), "struct invariant must be boolean".to_string())
+                ]));
+            }
+            r.push(((sym, self.memory[sym].temporal), invariant.loc().clone()));
         }
-        if let Value::Theory{..} =  self.memory[b].value {
-            return Err(self.trace(format!("theory '{}' is not a real world object", self.memory[b].name ), vec![
-                (here.clone(), format!("cannot use theory in this instance"))
-            ]));
+        for field in fields {
+            if field.array.is_some() || !self.has_invariants(&field.typed) {
+                continue;
+            }
+            let mut member = ast::Expression::UnaryPre{
+                loc:    field.loc.clone(),
+                op:     ast::PrefixOperator::AddressOf,
+                expr:   Box::new(ast::Expression::MemberAccess{
+                    loc:    field.loc.clone(),
+                    lhs:    Box::new(ast::Expression::Name(ast::Typed{
+                        t:      ast::Type::Other(Name::from("self")),
+                        ptr:    Vec::new(),
+                        loc:    field.loc.clone(),
+                        tail:   ast::Tail::None,
+                    })),
+                    op:     "->".to_string(),
+                    rhs:    field.name.clone(),
+                }),
+            };
+            let member = self.execute_expr(&mut member)?;
+            r.extend(self.struct_invariants(member)?);
         }
+        self.proof -= 1;
 
-        if self.memory[a].typed == self.memory[b].typed {
-            return Ok((self.memory[a].typed.clone(), a,b));
+        self.pop();
+        self.stack = stack_original;
+        Ok(r)
+    }
+
+    fn prove_struct_invariants(&mut self, ptr: Symbol, at: &ast::Location, when: &str) -> Result<(), Error> {
+        self.ssa.push("struct invariant");
+        self.in_model = true;
+        let invariants = self.struct_invariants(ptr);
+        self.in_model = false;
+
+        for (sym, loc) in invariants? {
+            self.prove("invariant", &loc, vec![sym], |a,model| match a {
+                false => {
+                    let mut estack = vec![
+                        (at.clone(), when.to_string()),
+                        (loc.clone(), "struct invariant does not hold".to_string()),
+                    ];
+                    if let Some(model) = &model {
+                        estack.extend(self.demonstrate(model, sym, 0));
+                    }
+                    Err(self.trace(format!("unproven struct invariant of {}", self.memory[ptr].typed), estack))
+                }
+                true => {
+                    Ok(())
+                }
+            })?;
         }
 
+        self.ssa.pop("end of struct invariant");
+        Ok(())
+    }
 
-        // if one is elided, it becomes the other type
-        if self.memory[a].typed.t == ast::Type::Elided {
-            self.memory[a].typed = self.memory[b].typed.clone();
-            self.memory[a].t     = self.memory[b].t.clone();
-            return Ok((self.memory[a].typed.clone(), a,b));
-        }
-        if self.memory[b].typed.t == ast::Type::Elided {
-            self.memory[b].typed = self.memory[a].typed.clone();
-            self.memory[b].t     = self.memory[a].t.clone();
-            return Ok((self.memory[a].typed.clone(), a,b));
+    // the structs a store writes into, innermost first
+    fn store_containers(place: &ast::Expression) -> Vec<ast::Expression> {
+        let mut containers = Vec::new();
+        let mut at = place;
+        loop {
+            at = match at {
+                ast::Expression::MemberAccess{lhs, op, loc, ..} => {
+                    containers.push(if op == "->" {
+                        ast::Expression::UnaryPre{
+                            loc:    loc.clone(),
+                            op:     ast::PrefixOperator::Deref,
+                            expr:   lhs.clone(),
+                        }
+                    } else {
+                        lhs.as_ref().clone()
+                    });
+                    lhs
+                }
+                ast::Expression::ArrayAccess{lhs, ..} | ast::Expression::Cast{expr: lhs, ..} => lhs,
+                _ => return containers,
+            }
         }
+    }
 
+    // whether executing a place again has no effects other than the proofs it already had
+    fn pure_place(expr: &ast::Expression) -> bool {
+        match expr {
+            ast::Expression::Name(_) | ast::Expression::Literal{..} => true,
+            ast::Expression::MemberAccess{lhs, ..}
+                | ast::Expression::Cast{expr: lhs, ..}
+                | ast::Expression::UnaryPre{op: ast::PrefixOperator::Deref, expr: lhs, ..} => Self::pure_place(lhs),
+            ast::Expression::ArrayAccess{lhs, rhs, ..} | ast::Expression::Infix{lhs, rhs, ..} => {
+                Self::pure_place(lhs) && Self::pure_place(rhs)
+            }
+            _ => false,
+        }
+    }
 
-        // if one is an unsigned literal, cast it into the other type
-        if self.memory[a].typed.t == ast::Type::ULiteral {
-            let tmp = self.temporary(
-                format!("implicit coercion of {}", self.memory[a].name),
-                self.memory[b].typed.clone(),
-                self.memory[a].declared.clone(),
-                self.memory[b].tags.clone(),
-            )?;
-
-            self.memory[tmp].value = self.memory[a].value.clone();
-            self.ssa.assign(
-                (tmp,   self.memory[tmp].temporal),
-                (a,     self.memory[a].temporal),
-                Self::smt_type(&self.memory[tmp].typed),
-            );
+    // every store into a struct must keep its invariants, and those of every struct it is in
+    fn check_store(&mut self, place: &ast::Expression, loc: &ast::Location) -> Result<(), Error> {
+        self.check_modifies(place, loc)?;
+        self.check_tag_store(place, loc)?;
+        for mut container in Self::store_containers(place) {
+            if !Self::pure_place(&container) {
+                break;
+            }
+            let sym = self.execute_expr(&mut container)?;
+            if self.has_invariants(&self.memory[sym].typed) {
+                let ptr = self.address_of(sym, loc)?;
+                self.prove_struct_invariants(ptr, loc, "after this store")?;
+            }
+        }
+        Ok(())
+    }
 
-            return Ok((self.memory[b].typed.clone(), tmp, b));
+    fn has_invariants(&self, typed: &ast::Typed) -> bool {
+        if !typed.ptr.is_empty() {
+            return false;
+        }
+        // a struct in a struct keeps its own invariants
+        match &typed.t {
+            ast::Type::Other(name) => match self.defs.get(name) {
+                Some(ast::Def::Struct{invariants, fields, ..}) => {
+                    !invariants.is_empty() || fields.iter().any(|f|f.array.is_none() && self.has_invariants(&f.typed))
+                }
+                _ => false,
+            },
+            _ => false,
         }
+    }
 
-        if self.memory[b].typed.t == ast::Type::ULiteral {
-            let tmp = self.temporary(
-                format!("implicit coercion of {}", self.memory[b].name),
-                self.memory[a].typed.clone(),
-                self.memory[b].declared.clone(),
-                self.memory[a].tags.clone(),
-            )?;
+    // whether anything in the local behind ptr was written. a local that was not
+    // is being constructed by the call it is passed to, and has no invariants yet
+    fn initialized_pointee(&self, ptr: Symbol) -> bool {
+        match &self.memory[ptr].value {
+            Value::Address(to) if self.stack.iter().any(|scope|scope.locals.values().any(|l|l == to)) => {
+                self.initialized(*to)
+            }
+            _ => true,
+        }
+    }
 
-            self.memory[tmp].value = self.memory[b].value.clone();
-            self.ssa.assign(
-                (tmp,   self.memory[tmp].temporal),
-                (b,     self.memory[b].temporal),
-                Self::smt_type(&self.memory[tmp].typed),
-            );
+    fn initialized(&self, sym: Symbol) -> bool {
+        // assigned as a whole, or written by a call it was lent to
+        if self.memory[sym].temporal > 0 {
+            return true;
+        }
+        match &self.memory[sym].value {
+            Value::Uninitialized => false,
+            Value::Struct{members} => members.values().any(|m|self.initialized(*m)),
+            Value::Array{array, ..} => array.values().any(|m|self.initialized(*m)),
+            _ => true,
+        }
+    }
 
-            return Ok((self.memory[a].typed.clone(), a, tmp));
+    // the tag of a tagged union only changes with the whole value, so that the payload always matches it
+    fn check_tag_store(&mut self, place: &ast::Expression, loc: &ast::Location) -> Result<(), Error> {
+        if let ast::Expression::MemberAccess{lhs, op, rhs, ..} = place {
+            if rhs != "tag" {
+                return Ok(());
+            }
+            let mut lhs = lhs.as_ref().clone();
+            if op == "->" {
+                lhs = ast::Expression::UnaryPre{
+                    loc:    lhs.loc().clone(),
+                    op:     ast::PrefixOperator::Deref,
+                    expr:   Box::new(lhs),
+                };
+            }
+            let sym = self.execute_expr(&mut lhs)?;
+            if let Some((name, _)) = self.tagged_union(sym) {
+                let short = name.0.last().unwrap();
+                return Err(self.trace(format!("the tag of {} is read-only", short), vec![
+                    (loc.clone(), format!("assign a whole {}{{tag: ..}} instead", short)),
+                ]));
+            }
         }
+        Ok(())
+    }
 
+    fn check_modifies(&mut self, place: &ast::Expression, loc: &ast::Location) -> Result<(), Error> {
+        match self.loop_place(place) {
+            Some(place) => self.check_frame(place, loc),
+            None => Ok(()),
+        }
+    }
 
-        // TODO if the lhs is a pointer, do an implicit cast
-        if self.memory[a].typed.ptr.len() > 0 {
-            let tmp = self.temporary(
-                format!("implicit cast of {}", self.memory[b].name),
-                self.memory[a].typed.clone(),
-                here.clone(),
-                self.memory[a].tags.clone(),
-            )?;
+    // anything the arguments reach may only be written if the modifies clause lists it.
+    // place is like in loop_place
+    fn check_frame(&mut self, place: (Symbol, bool), loc: &ast::Location) -> Result<(), Error> {
+        if self.current_function_modifies.is_empty() {
+            return Ok(());
+        }
+        let place = self.frame_normalize(place);
 
-            self.memory[tmp].value = self.memory[b].value.clone();
-            self.ssa.assign(
-                (tmp,   self.memory[tmp].temporal),
-                (b,     self.memory[b].temporal),
-                Self::smt_type(&self.memory[tmp].typed),
-            );
-            return Ok((self.memory[a].typed.clone(), a, tmp));
+        let reachable = self.current_function_args.iter()
+            .filter(|(_, arg)|!arg.typed.ptr.is_empty())
+            .any(|(sym, _)|self.frame_covers(self.frame_normalize((*sym, false)), place, true));
+        if !reachable {
+            return Ok(());
         }
 
-        if let (ast::Type::Other(at), ast::Type::Other(bt)) = (&self.memory[a].typed.t, &self.memory[b].typed.t) {
-            if let (Some(ast::Def::Fntype{..}), Some(ast::Def::Function{..})) = (self.defs.get(at), self.defs.get(bt)) {
-                return Ok((self.memory[b].typed.clone(), a, b));
+        for expr in self.current_function_modifies.clone() {
+            let listed = self.frame_place(&expr)?;
+            if self.frame_covers(self.frame_normalize(listed), place, false) {
+                return Ok(());
             }
         }
+        Err(self.trace("write outside of modifies clause".to_string(), vec![
+            (loc.clone(), "this location is not listed in the modifies clause of the function".to_string()),
+        ]))
+    }
 
-        // object copy with rhs having no tail
-        // TODO in theory the C compiler should init the tail with zeroes, but copy traits would be better
-        if self.memory[a].typed.t == self.memory[b].typed.t && self.memory[b].typed.tail == ast::Tail::None {
-            return Ok((self.memory[a].typed.clone(), a, b));
+    // the location a modifies clause names, a field or everything a pointer points to.
+    // false means only whatever the symbol contains or points to, like in loop_place
+    fn frame_place(&mut self, expr: &ast::Expression) -> Result<(Symbol, bool), Error> {
+        let (mut expr, whole) = match expr {
+            ast::Expression::UnaryPre{op: ast::PrefixOperator::Deref, expr, ..} => (expr.as_ref().clone(), false),
+            ast::Expression::MemberAccess{..} => (expr.clone(), true),
+            _ => {
+                return Err(self.trace("invalid modifies clause".to_string(), vec![
+                    (expr.loc().clone(), "must be a field or what a pointer points to, like self->len or *self".to_string()),
+                ]));
+            }
+        };
+        self.proof += 1;
+        let sym = self.execute_expr(&mut expr);
+        let container = match &mut expr {
+            ast::Expression::MemberAccess{lhs, op, ..} => Some((self.execute_expr(lhs), op == "->")),
+            _ => None,
+        };
+        self.proof -= 1;
+        let sym = sym?;
+
+        // an array member only names what is in the array
+        if let Some((container, deref)) = container {
+            let mut container = container?;
+            if let (true, Value::Address(to)) = (deref, &self.memory[container].value) {
+                container = *to;
+            }
+            if self.array_members(container).contains(&sym) {
+                return Ok((sym, false));
+            }
         }
-
-        return Err(self.trace(format!("incompatible types {} and {}", self.memory[a].typed, self.memory[b].typed), vec![
-            (here.clone(), format!("this expression is unprovable over incompatible types")),
-            (self.memory[a].declared.clone(), format!("{} := {} {:?}", self.memory[a].name, self.memory[a].typed, self.memory[a].value)),
-            (self.memory[b].declared.clone(), format!("{} := {} {:?}", self.memory[b].name, self.memory[b].typed, self.memory[a].value))
-        ]));
+        Ok((sym, whole))
     }
 
+    // a pointer to a single object stands for that object
+    fn frame_normalize(&self, (sym, whole): (Symbol, bool)) -> (Symbol, bool) {
+        match &self.memory[sym].value {
+            Value::Address(to) if !whole => (*to, true),
+            _ => (sym, whole),
+        }
+    }
 
-    fn execute_scope(&mut self, body: &mut Vec<Box<ast::Statement>>) -> Result<ScopeReturn, Error> {
-        for i in 0..body.len() {
-            let (body, _rest) = body.split_at_mut(i + 1);
+    // if region includes place. pointers within region are only followed when deep is set
+    fn frame_covers(&self, (region, all): (Symbol, bool), (place, whole): (Symbol, bool), deep: bool) -> bool {
+        if region == place {
+            return all || !whole;
+        }
+        let mut inside = Vec::new();
+        self.frame_members(region, deep, &mut inside);
+        inside.contains(&place)
+    }
 
-            match body[i].as_mut() {
-                ast::Statement::Var{loc, typed: ref mut typed_o, tags, name, array, assign} => {
+    fn frame_members(&self, sym: Symbol, deep: bool, into: &mut Vec<Symbol>) {
+        let members = match &self.memory[sym].value {
+            Value::Struct{members} => members.values().cloned().collect::<Vec<_>>(),
+            Value::Array{array, ..} => array.values().cloned().collect::<Vec<_>>(),
+            Value::Address(to) if deep => vec![*to],
+            _ => return,
+        };
+        for member in members {
+            if !into.contains(&member) {
+                into.push(member);
+                self.frame_members(member, deep, into);
+            }
+        }
+    }
 
-                    let mut typed = typed_o.clone();
-                    if array.is_some() {
-                        typed.ptr.push(ast::Pointer{
-                            loc: loc.clone(),
+    // old(expr) in a model refers to the state before the function ran.
+    // it is evaluated now and replaced by a name bound to a copy of its value
+    fn bind_old(&mut self, expr: &mut ast::Expression, bound: &mut Vec<(Name, Symbol)>) -> Result<(), Error> {
+        match expr {
+            ast::Expression::Call{name, args, loc, ..} => {
+                let is_old = match name.as_ref() {
+                    ast::Expression::Name(ast::Typed{t: ast::Type::Other(n), ..}) => format!("{}", n) == "old",
+                    _ => false,
+                };
+                if !is_old {
+                    self.bind_old(name, bound)?;
+                    for arg in args {
+                        self.bind_old(arg, bound)?;
+                    }
+                    return Ok(());
+                }
+                if args.len() != 1 {
+                    return Err(self.trace("call argument count mismatch".to_string(), vec![
+                        (name.loc().clone(), format!("builtin needs 1 argument, but you passed {}", args.len()))
+                    ]));
+                }
+                let sym = self.execute_expr(&mut args[0])?;
+                let tmp = self.temporary(
+                    format!("old({})", self.memory[sym].name),
+                    self.memory[sym].typed.clone(),
+                    loc.clone(),
+                    Tags::new(),
+                )?;
+                self.copy(tmp, sym, loc)?;
+
+                let name = Name::from(&format!("old {}", tmp));
+                bound.push((name.clone(), tmp));
+                *expr = ast::Expression::Name(ast::Typed{
+                    t:      ast::Type::Other(name),
+                    loc:    loc.clone(),
+                    ptr:    Vec::new(),
+                    tail:   ast::Tail::None,
+                });
+                Ok(())
+            }
+            ast::Expression::MemberAccess{lhs, ..} => self.bind_old(lhs, bound),
+            ast::Expression::ArrayAccess{lhs, rhs, ..} | ast::Expression::Infix{lhs, rhs, ..} => {
+                self.bind_old(lhs, bound)?;
+                self.bind_old(rhs, bound)
+            }
+            ast::Expression::Cast{expr, ..} | ast::Expression::UnaryPost{expr, ..} | ast::Expression::UnaryPre{expr, ..} => {
+                self.bind_old(expr, bound)
+            }
+            ast::Expression::StructInit{fields, ..} => {
+                for (_, field) in fields {
+                    self.bind_old(field, bound)?;
+                }
+                Ok(())
+            }
+            ast::Expression::ArrayInit{fields, ..} => {
+                for field in fields {
+                    self.bind_old(field, bound)?;
+                }
+                Ok(())
+            }
+            ast::Expression::Ternary{cond, then, otherwise, ..} => {
+                self.bind_old(cond, bound)?;
+                self.bind_old(then, bound)?;
+                self.bind_old(otherwise, bound)
+            }
+            ast::Expression::Quantifier{range, body, ..} => {
+                self.bind_old(range, bound)?;
+                self.bind_old(body, bound)
+            }
+            ast::Expression::Name(_)
+            | ast::Expression::LiteralString{..}
+            | ast::Expression::LiteralChar{..}
+            | ast::Expression::Literal{..}
+            | ast::Expression::Closure{..} => Ok(()),
+        }
+    }
+
+
+    // a loop is executed as one arbitrary iteration.
+    // everything the loop may write to is replaced with an unconstrained value,
+    // so the iteration can only rely on the invariants and the loop condition.
+    // returns the branch conditions that hold after the loop
+    fn execute_loop(
+        &mut self,
+        cond:       Option<&mut ast::Expression>,
+        step:       &mut Vec<Box<ast::Statement>>,
+        invariants: &[ast::Expression],
+        decreases:  Option<&ast::Expression>,
+        body:       &mut ast::Block,
+    ) -> Result<Vec<(TemporalSymbol, ast::Location, bool)>, Error> {
+
+        let cond_after = cond.as_ref().map(|cond|(**cond).clone());
+
+        self.check_loop_invariants(invariants, "loop invariant does not hold on entry")?;
+
+        let mut writes = Vec::new();
+        self.loop_writes_scope(&body.statements, &mut writes);
+        self.loop_writes_scope(step, &mut writes);
+        let structs = self.loop_struct_invariants(&body.end)?;
+        let before = self.havoc(&writes, &body.end);
+        self.assume_struct_invariants(&structs, &body.end)?;
+
+        self.push("loop iteration".to_string());
+        self.ssa.branch();
+
+        for invariant in invariants.iter() {
+            let sym = self.loop_invariant(invariant)?;
+            self.ssa.constrain_branch(sym, true);
+            self.cur().trace.push((sym, invariant.loc().clone(), false));
+        }
+        if let Some(cond) = cond {
+            let sym = self.loop_condition(cond)?;
+            self.ssa.constrain_branch(sym, true);
+            self.cur().trace.push((sym, cond.loc().clone(), false));
+        }
+
+        let decreases = match decreases {
+            Some(measure) => Some((measure.clone(), self.measure(measure)?)),
+            None => None,
+        };
+
+        self.loops.push(Loop{
+            invariants: invariants.to_vec(),
+            step:       step.clone(),
+            decreases:  decreases.clone(),
+            breaks_keep: true,
+        });
+        if let ScopeReturn::NoReturn = self.execute_scope(&mut body.statements)? {
+            self.execute_scope(step)?;
+            self.check_loop_invariants(invariants, "loop invariant is not maintained by the loop body")?;
+            if let Some((measure, start)) = &decreases {
+                self.check_decreases(measure, *start, &body.end, "when starting the next iteration from here")?;
+            }
+        }
+        let frame = self.loops.pop().unwrap();
+
+        self.ssa.unbranch(false);
+        self.pop();
+
+        // anything written by the loop has some value of an arbitrary iteration,
+        // unless the loop was never reached
+        let mut writes = Vec::new();
+        self.loop_writes_scope(&body.statements, &mut writes);
+        self.loop_writes_scope(step, &mut writes);
+        for (sym, temporal) in self.havoc(&writes, &body.end) {
+            let prev = before.iter().find(|(s,_)|*s == sym).map(|(_,t)|*t).unwrap_or(temporal);
+            self.ssa.assign_branch(
+                (sym, self.memory[sym].temporal),
+                (sym, self.memory[sym].temporal),
+                (sym, prev),
+                self.memory[sym].t.clone(),
+            );
+        }
+        self.assume_struct_invariants(&structs, &body.end)?;
+
+        // a break leaves the loop while the condition may still hold,
+        // and in any state unless the invariants held at every break
+        let mut after = Vec::new();
+        let breaks = loop_breaks(&body.statements);
+        if !breaks || frame.breaks_keep {
+            for invariant in invariants.iter() {
+                let sym = self.loop_invariant(invariant)?;
+                self.ssa.constrain_branch(sym, true);
+                after.push((sym, invariant.loc().clone(), false));
+            }
+        }
+        if !breaks {
+            if let Some(mut cond) = cond_after {
+                let sym = self.loop_condition(&mut cond)?;
+                self.ssa.constrain_branch(sym, false);
+                after.push((sym, cond.loc().clone(), false));
+            }
+        }
+        Ok(after)
+    }
+
+    // continue jumps to the step expression of a for loop, after which the invariants must hold.
+    // the step is only executed for the proof, the statements following the continue do not see it
+    fn loop_continue(&mut self, mut frame: Loop, loc: &ast::Location) -> Result<(), Error> {
+        if frame.invariants.is_empty() && frame.decreases.is_none() {
+            return Ok(());
+        }
+
+        let saved : Vec<(u64, Value)> = self.memory.iter().map(|m|(m.temporal, m.value.clone())).collect();
+
+        self.push("continue".to_string());
+        self.ssa.push("continue");
+        self.execute_scope(&mut frame.step)?;
+        let mut r = self.check_loop_invariants(&frame.invariants, "loop invariant does not hold at continue");
+        if let (Ok(()), Some((measure, start))) = (&r, &frame.decreases) {
+            r = self.check_decreases(measure, *start, loc, "when starting the next iteration from here");
+        }
+        self.ssa.pop("end of continue");
+        self.pop();
+
+        for (i, (temporal, value)) in saved.into_iter().enumerate() {
+            self.memory[i].temporal = temporal;
+            self.memory[i].value    = value;
+        }
+
+        r.map_err(|mut e|{
+            e.details.insert(0, (loc.clone(), "when continuing here".to_string()));
+            e
+        })
+    }
+
+    // whether the invariants still hold when breaking out of the innermost loop here.
+    // not an error if they don't, the loop just says nothing about its state after a break
+    fn loop_break(&mut self) -> Result<(), Error> {
+        let breaks_loop = self.stack.iter().rev()
+            .find(|scope|scope.name == "case" || scope.name == "loop iteration")
+            .map(|scope|scope.name == "loop iteration") == Some(true);
+        let frame = match self.loops.last() {
+            Some(frame) if breaks_loop && frame.breaks_keep => frame.clone(),
+            _ => return Ok(()),
+        };
+        if frame.invariants.is_empty() {
+            return Ok(());
+        }
+        let mut syms = Vec::new();
+        for invariant in frame.invariants.iter() {
+            syms.push(self.loop_invariant(invariant)?);
+        }
+        if !self.ssa.assert(syms, |a,_|a) {
+            self.loops.last_mut().unwrap().breaks_keep = false;
+        }
+        Ok(())
+    }
+
+    fn loop_condition(&mut self, expr: &mut ast::Expression) -> Result<TemporalSymbol, Error> {
+        let sym = self.execute_expr(expr)?;
+        if self.memory[sym].t != smt::Type::Bool {
+            return Err(self.trace(format!("expected boolean, got {}", self.memory[sym].typed), vec![
+                (expr.loc().clone(), "must be boolean".to_string())
+            ]));
+        }
+        Ok((sym, self.memory[sym].temporal))
+    }
+
+    fn loop_invariant(&mut self, invariant: &ast::Expression) -> Result<TemporalSymbol, Error> {
+        self.proof += 1;
+        let r = self.loop_condition(&mut invariant.clone());
+        self.proof -= 1;
+        r
+    }
+
+    fn check_loop_invariants(&mut self, invariants: &[ast::Expression], message: &str) -> Result<(), Error> {
+        for invariant in invariants.iter() {
+            let sym = self.loop_invariant(invariant)?;
+            self.prove("invariant", invariant.loc(), vec![sym], |a,model| match a {
+                false => {
+                    let mut estack = vec![
+                        (invariant.loc().clone(), message.to_string()),
+                    ];
+                    if let Some(model) = &model {
+                        estack.extend(self.demonstrate(model, sym, 0));
+                    }
+                    Err(self.trace("unproven loop invariant".to_string(), estack))
+                }
+                true => {
+                    Ok(())
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    // evaluate a termination measure and freeze its current value,
+    // so it can be compared against the value at the next iteration or recursive call
+    fn measure(&mut self, measure: &ast::Expression) -> Result<Symbol, Error> {
+        self.proof += 1;
+        let sym = self.execute_expr(&mut measure.clone());
+        self.proof -= 1;
+        let sym = sym?;
+        if self.memory[sym].t == smt::Type::Bool || self.is_float(sym) || !self.memory[sym].typed.ptr.is_empty() {
+            return Err(self.trace(format!("expected integer, got {}", self.memory[sym].typed), vec![
+                (measure.loc().clone(), "termination measure must be an integer".to_string())
+            ]));
+        }
+        self.check_measure_bounded(measure, sym, measure.loc())?;
+
+        let start = self.temporary(
+            format!("measure at {}", measure.loc()),
+            self.memory[sym].typed.clone(),
+            measure.loc().clone(),
+            Tags::new(),
+        )?;
+        self.copy(start, sym, measure.loc())?;
+        Ok(start)
+    }
+
+    // an unsigned measure is always bounded by zero, a signed one must be proven to be
+    fn check_measure_bounded(&mut self, measure: &ast::Expression, sym: Symbol, here: &ast::Location) -> Result<(), Error> {
+        if !self.memory[sym].typed.t.signed() {
+            return Ok(());
+        }
+        let zero = self.literal(here, Value::Integer(0), ast::Typed{
+            t:      ast::Type::ULiteral,
+            ptr:    Vec::new(),
+            loc:    here.clone(),
+            tail:   ast::Tail::None,
+        })?;
+        let bounded = self.compare(sym, zero, ast::InfixOperator::Moreeq, here)?;
+        self.prove("decreases", measure.loc(), vec![bounded], |a,model| match a {
+            false => {
+                let mut estack = vec![
+                    (measure.loc().clone(), "measure may be negative".to_string()),
+                ];
+                if here != measure.loc() {
+                    estack.push((here.clone(), "at this point".to_string()));
+                }
+                if let Some(model) = &model {
+                    estack.extend(self.demonstrate(model, bounded, 0));
+                }
+                Err(self.trace("unproven termination".to_string(), estack))
+            }
+            true => {
+                Ok(())
+            }
+        })
+    }
+
+    // the measure must be strictly smaller than it was at start.
+    // here is where the next iteration or recursive call begins
+    fn check_decreases(&mut self, measure: &ast::Expression, start: Symbol, here: &ast::Location, what: &str) -> Result<(), Error> {
+        self.proof += 1;
+        let now = self.execute_expr(&mut measure.clone());
+        self.proof -= 1;
+        let now = now?;
+        self.check_measure_bounded(measure, now, here)?;
+        let decreased = self.compare(now, start, ast::InfixOperator::Lessthan, here)?;
+        self.prove("decreases", here, vec![decreased], |a,model| match a {
+            false => {
+                let mut estack = vec![
+                    (measure.loc().clone(), "measure does not decrease".to_string()),
+                    (here.clone(), what.to_string()),
+                ];
+                if let Some(model) = &model {
+                    estack.extend(self.demonstrate(model, decreased, 0));
+                }
+                Err(self.trace("unproven termination".to_string(), estack))
+            }
+            true => {
+                Ok(())
+            }
+        })
+    }
+
+    fn compare(&mut self, lhs: Symbol, rhs: Symbol, op: ast::InfixOperator, here: &ast::Location) -> Result<TemporalSymbol, Error> {
+        let (newtype, lhs, rhs) = self.type_coersion(lhs, rhs, here)?;
+        let tmp = self.temporary(
+            format!("{} {:?} {}", self.memory[lhs].name, op, self.memory[rhs].name),
+            ast::Typed{
+                t:      ast::Type::Bool,
+                ptr:    Vec::new(),
+                loc:    here.clone(),
+                tail:   ast::Tail::None,
+            },
+            here.clone(),
+            Tags::new(),
+        )?;
+        self.memory[tmp].value = Value::InfixOp {
+            lhs:    (lhs, self.memory[lhs].temporal),
+            rhs:    (rhs, self.memory[rhs].temporal),
+            op:     op.clone(),
+        };
+        self.ssa.infix_op(
+            tmp,
+            (lhs, self.memory[lhs].temporal),
+            (rhs, self.memory[rhs].temporal),
+            op,
+            smt::Type::Bool,
+            newtype.t.signed(),
+        );
+        Ok((tmp, self.memory[tmp].temporal))
+    }
+
+    // replace everything in writes with unconstrained values.
+    // true means the symbol itself is written, false means only whatever it contains or points to.
+    // returns the temporals before they were replaced
+    fn havoc(&mut self, writes: &[(Symbol, bool)], here: &ast::Location) -> Vec<TemporalSymbol> {
+        let mut havoced = Vec::new();
+        for (sym, whole) in writes {
+            if *whole {
+                self.havoc_value(*sym, here, &mut havoced);
+            } else {
+                self.havoc_contents(*sym, here, &mut havoced);
+            }
+        }
+        havoced
+    }
+
+    fn havoc_value(&mut self, sym: Symbol, here: &ast::Location, havoced: &mut Vec<TemporalSymbol>) {
+        match self.memory[sym].value.clone() {
+            Value::Struct{..} | Value::Array{..} => {
+                self.havoc_contents(sym, here, havoced);
+            }
+            Value::Theory{..} | Value::Function{..} | Value::SelfCall{..} | Value::Void => {
+            }
+            _ => {
+                if havoced.iter().any(|(s,_)|*s == sym) {
+                    return;
+                }
+                havoced.push((sym, self.memory[sym].temporal));
+                self.ssa.debug(&format!("{} to temporal +1 because of loop", sym));
+                self.memory[sym].temporal += 1;
+                let tt = self.memory[sym].temporal;
+                self.memory[sym].assignments.insert(tt, here.clone());
+                self.memory[sym].value = Value::Unconstrained("modified in loop".to_string());
+            }
+        }
+    }
+
+    fn havoc_contents(&mut self, sym: Symbol, here: &ast::Location, havoced: &mut Vec<TemporalSymbol>) {
+        // the pointer itself may change in the loop, so all memory of its element type may have been written
+        if let Some(contents) = self.pointee_type(sym).and_then(|t|self.contents.get(&t).cloned()) {
+            self.havoc_value(contents, here, havoced);
+        }
+        match self.memory[sym].value.clone() {
+            Value::Struct{members} => {
+                let arrays = self.array_members(sym);
+                for (_, member) in members {
+                    if arrays.contains(&member) {
+                        self.havoc_contents(member, here, havoced);
+                    } else {
+                        self.havoc_value(member, here, havoced);
+                    }
+                }
+            }
+            Value::Array{array, ..} => {
+                for (_, member) in array {
+                    self.havoc_value(member, here, havoced);
+                }
+            }
+            Value::Address(to) => {
+                self.havoc_value(to, here, havoced);
+            }
+            _ => (),
+        }
+    }
+
+    // every store in the loop and every call it makes keeps the struct invariants,
+    // so they still hold for every struct the loop can reach, if they held when it was reached
+    fn loop_struct_invariants(&mut self, here: &ast::Location) -> Result<Vec<Symbol>, Error> {
+        let unsafe_args : Vec<Symbol> = self.current_function_args.iter()
+            .filter(|(_, arg)|arg.tags.contains("unsafe"))
+            .map(|(sym, _)|*sym)
+            .collect();
+        let locals : Vec<Symbol> = self.stack[1..].iter().flat_map(|scope|scope.locals.values().cloned()).collect();
+
+        let mut ptrs = Vec::new();
+        for sym in locals {
+            if self.memory[sym].typed.ptr.len() == 1 {
+                let mut pointee = self.memory[sym].typed.clone();
+                pointee.ptr.pop();
+                if self.has_invariants(&pointee) && self.initialized_pointee(sym) && !unsafe_args.contains(&sym) {
+                    ptrs.push(sym);
+                }
+            } else if self.has_invariants(&self.memory[sym].typed) && self.initialized(sym) {
+                ptrs.push(self.address_of(sym, here)?);
+            }
+        }
+        Ok(ptrs)
+    }
+
+    fn assume_struct_invariants(&mut self, ptrs: &[Symbol], here: &ast::Location) -> Result<(), Error> {
+        for ptr in ptrs {
+            for (inv, invloc) in self.struct_invariants(*ptr)? {
+                if !self.ssa.attest(inv, true) {
+                    return Err(self.trace("struct invariant broke ssa solution".to_string(), vec![
+                        (invloc, "there may be conflicting constraints".to_string()),
+                        (here.clone(), "assumed in this loop".to_string()),
+                    ]));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, name: &Name) -> Option<Symbol> {
+        for scope in self.stack.iter().rev() {
+            if let Some(v) = scope.locals.get(name) {
+                return Some(*v);
+            }
+        }
+        None
+    }
+
+    // the storage an expression refers to, without executing it.
+    // true if it is exactly that storage, false if it is somewhere inside of it
+    fn loop_place(&self, expr: &ast::Expression) -> Option<(Symbol, bool)> {
+        match expr {
+            ast::Expression::Name(name) => match &name.t {
+                ast::Type::Other(name) => self.lookup(name).map(|sym|(sym, true)),
+                _ => None,
+            },
+            ast::Expression::MemberAccess{lhs, op, rhs, ..} => {
+                let (mut sym, whole) = self.loop_place(lhs)?;
+                if !whole {
+                    return Some((sym, false));
+                }
+                if op == "->" {
+                    match &self.memory[sym].value {
+                        Value::Address(to) => sym = *to,
+                        _ => return Some((sym, false)),
+                    }
+                }
+                match &self.memory[sym].value {
+                    Value::Struct{members} => match members.get(rhs) {
+                        Some(member) => Some((*member, !self.array_members(sym).contains(member))),
+                        None => Some((sym, false)),
+                    },
+                    _ => Some((sym, false)),
+                }
+            }
+            ast::Expression::ArrayAccess{lhs, ..} => {
+                self.loop_place(lhs).map(|(sym,_)|(sym, false))
+            }
+            ast::Expression::UnaryPre{op: ast::PrefixOperator::Deref, expr, ..} => {
+                self.loop_place(expr).map(|(sym,_)|(sym, false))
+            }
+            ast::Expression::Cast{expr, ..} => {
+                self.loop_place(expr)
+            }
+            _ => None,
+        }
+    }
+
+    fn loop_write(&self, expr: &ast::Expression, into: &mut Vec<(Symbol, bool)>) {
+        if let Some(place) = self.loop_place(expr) {
+            into.push(place);
+        }
+    }
+
+    // passing a pointer somewhere may write to whatever it points to
+    fn loop_borrow(&self, expr: &ast::Expression, into: &mut Vec<(Symbol, bool)>) {
+        if let Some((sym, _)) = self.loop_place(expr) {
+            if !self.memory[sym].typed.ptr.is_empty() || matches!(self.memory[sym].value, Value::Array{..}) {
+                into.push((sym, false));
+            }
+        }
+    }
+
+    fn loop_writes_scope(&self, body: &Vec<Box<ast::Statement>>, into: &mut Vec<(Symbol, bool)>) {
+        for stm in body {
+            self.loop_writes_statement(stm, into);
+        }
+    }
+
+    fn loop_writes_statement(&self, stm: &ast::Statement, into: &mut Vec<(Symbol, bool)>) {
+        match stm {
+            ast::Statement::Assign{lhs, rhs, ..} => {
+                self.loop_write(lhs, into);
+                self.loop_borrow(rhs, into);
+                self.loop_writes_expr(lhs, into);
+                self.loop_writes_expr(rhs, into);
+            }
+            ast::Statement::Var{assign, array, ..} => {
+                if let Some(assign) = assign {
+                    self.loop_borrow(assign, into);
+                    self.loop_writes_expr(assign, into);
+                }
+                if let Some(Some(array)) = array {
+                    self.loop_writes_expr(array, into);
+                }
+            }
+            ast::Statement::Expr{expr, ..} => {
+                self.loop_writes_expr(expr, into);
+            }
+            ast::Statement::Return{expr, ..} => {
+                if let Some(expr) = expr {
+                    self.loop_writes_expr(expr, into);
+                }
+            }
+            ast::Statement::Defer{..} => unreachable!("ICE: defer is removed by expand"),
+            ast::Statement::Ghost{stm, ..} => {
+                self.loop_writes_statement(stm, into);
+            }
+            ast::Statement::Switch{expr, cases, default, ..} => {
+                self.loop_writes_expr(expr, into);
+                for (conds, body) in cases {
+                    for expr in conds {
+                        self.loop_writes_expr(expr, into);
+                    }
+                    self.loop_writes_scope(&body.statements, into);
+                }
+                if let Some(default) = default {
+                    self.loop_writes_scope(&default.statements, into);
+                }
+            }
+            ast::Statement::For{e1, e2, e3, body, ..} => {
+                self.loop_writes_scope(e1, into);
+                if let Some(expr) = e2 {
+                    self.loop_writes_expr(expr, into);
+                }
+                self.loop_writes_scope(e3, into);
+                self.loop_writes_scope(&body.statements, into);
+            }
+            ast::Statement::While{expr, body, ..} | ast::Statement::ForIn{expr, body, ..} => {
+                self.loop_writes_expr(expr, into);
+                self.loop_writes_scope(&body.statements, into);
+            }
+            ast::Statement::If{branches} => {
+                for (_, expr, body) in branches {
+                    if let Some(expr) = expr {
+                        self.loop_writes_expr(expr, into);
+                    }
+                    self.loop_writes_scope(&body.statements, into);
+                }
+            }
+            ast::Statement::Block(body) | ast::Statement::Unsafe(body) => {
+                self.loop_writes_scope(&body.statements, into);
+            }
+            ast::Statement::Mark{..}
+            | ast::Statement::Label{..}
+            | ast::Statement::Continue{..}
+            | ast::Statement::Break{..}
+            | ast::Statement::CBlock{..} => {
+            }
+        }
+    }
+
+    fn loop_writes_expr(&self, expr: &ast::Expression, into: &mut Vec<(Symbol, bool)>) {
+        match expr {
+            ast::Expression::UnaryPost{expr, ..} => {
+                self.loop_write(expr, into);
+                self.loop_writes_expr(expr, into);
+            }
+            ast::Expression::UnaryPre{op, expr, ..} => {
+                match op {
+                    ast::PrefixOperator::Increment
+                    | ast::PrefixOperator::Decrement
+                    | ast::PrefixOperator::AddressOf => {
+                        self.loop_write(expr, into);
+                    }
+                    _ => (),
+                }
+                self.loop_writes_expr(expr, into);
+            }
+            ast::Expression::Call{name, args, ..} => {
+                // builtins and theories do not write anything
+                let builtin = match name.as_ref() {
+                    ast::Expression::Name(typed) => match &typed.t {
+                        ast::Type::Other(n) => match self.lookup(n) {
+                            None => true,
+                            Some(sym) => matches!(self.memory[sym].value, Value::Theory{..}),
+                        },
+                        _ => false,
+                    },
+                    _ => false,
+                };
+                if !builtin {
+                    if let ast::Expression::MemberAccess{lhs, op, ..} = name.as_ref() {
+                        if op == "." {
+                            self.loop_write(lhs, into);
+                        } else {
+                            self.loop_borrow(lhs, into);
+                        }
+                    }
+                    for arg in args {
+                        self.loop_borrow(arg, into);
+                    }
+                }
+                self.loop_writes_expr(name, into);
+                for arg in args {
+                    self.loop_writes_expr(arg, into);
+                }
+            }
+            ast::Expression::MemberAccess{lhs, ..} => {
+                self.loop_writes_expr(lhs, into);
+            }
+            ast::Expression::ArrayAccess{lhs, rhs, ..} | ast::Expression::Infix{lhs, rhs, ..} => {
+                self.loop_writes_expr(lhs, into);
+                self.loop_writes_expr(rhs, into);
+            }
+            ast::Expression::Ternary{cond, then, otherwise, ..} => {
+                self.loop_writes_expr(cond, into);
+                self.loop_writes_expr(then, into);
+                self.loop_writes_expr(otherwise, into);
+            }
+            ast::Expression::Quantifier{range, body, ..} => {
+                self.loop_writes_expr(range, into);
+                self.loop_writes_expr(body, into);
+            }
+            ast::Expression::Closure{..} => unreachable!("ICE: closure is lowered by closure::lower"),
+            ast::Expression::Cast{expr, ..} => {
+                self.loop_writes_expr(expr, into);
+            }
+            ast::Expression::StructInit{fields, ..} => {
+                for (_, expr) in fields {
+                    self.loop_writes_expr(expr, into);
+                }
+            }
+            ast::Expression::ArrayInit{fields, ..} => {
+                for expr in fields {
+                    self.loop_writes_expr(expr, into);
+                }
+            }
+            ast::Expression::Name(_)
+            | ast::Expression::Literal{..}
+            | ast::Expression::LiteralString{..}
+            | ast::Expression::LiteralChar{..} => {
+            }
+        }
+    }
+
+    // an integer literal passed as argument has the type of the parameter,
+    // so that the callsite assert compares it with the signedness of the parameter
+    fn coerce_literal(&mut self, sym: Symbol, typed: &ast::Typed) -> Result<Symbol, Error> {
+        if self.memory[sym].typed.t != ast::Type::ULiteral || !typed.ptr.is_empty() {
+            return Ok(sym);
+        }
+        match typed.t {
+            ast::Type::Other(_) | ast::Type::Instance(..) | ast::Type::Elided | ast::Type::Bool
+                | ast::Type::ULiteral | ast::Type::ILiteral | ast::Type::FLiteral
+                | ast::Type::F32 | ast::Type::F64 => return Ok(sym),
+            _ => (),
+        }
+        let tmp = self.temporary(
+            format!("implicit coercion of {}", self.memory[sym].name),
+            typed.clone(),
+            self.memory[sym].declared.clone(),
+            self.memory[sym].tags.clone(),
+        )?;
+        self.memory[tmp].value = self.memory[sym].value.clone();
+        self.ssa.assign(
+            (tmp,   self.memory[tmp].temporal),
+            (sym,   self.memory[sym].temporal),
+            Self::smt_type(typed),
+        );
+        Ok(tmp)
+    }
+
+    fn type_coersion(&mut self, a: Symbol, b: Symbol, here: &ast::Location) -> Result<(ast::Typed, Symbol, Symbol), Error>  {
+        if let Value::Theory{..} =  self.memory[a].value {
+            return Err(self.trace(format!("theory '{}' is not a real world object", self.memory[a].name ), vec![
+                (here.clone(), format!("cannot use theory in this instance"))
+            ]));
+        }
+        if let Value::Theory{..} =  self.memory[b].value {
+            return Err(self.trace(format!("theory '{}' is not a real world object", self.memory[b].name ), vec![
+                (here.clone(), format!("cannot use theory in this instance"))
+            ]));
+        }
+
+        if self.memory[a].typed == self.memory[b].typed {
+            return Ok((self.memory[a].typed.clone(), a,b));
+        }
+
+
+        // if one is elided, it becomes the other type
+        if self.memory[a].typed.t == ast::Type::Elided {
+            self.memory[a].typed = self.memory[b].typed.clone();
+            self.memory[a].t     = self.memory[b].t.clone();
+            return Ok((self.memory[a].typed.clone(), a,b));
+        }
+        if self.memory[b].typed.t == ast::Type::Elided {
+            self.memory[b].typed = self.memory[a].typed.clone();
+            self.memory[b].t     = self.memory[a].t.clone();
+            return Ok((self.memory[a].typed.clone(), a,b));
+        }
+
+
+        // if one is an unsigned literal, cast it into the other type
+        if self.memory[a].typed.t == ast::Type::ULiteral {
+            let tmp = self.temporary(
+                format!("implicit coercion of {}", self.memory[a].name),
+                self.memory[b].typed.clone(),
+                self.memory[a].declared.clone(),
+                self.memory[b].tags.clone(),
+            )?;
+
+            self.memory[tmp].value = self.memory[a].value.clone();
+            self.ssa.assign(
+                (tmp,   self.memory[tmp].temporal),
+                (a,     self.memory[a].temporal),
+                Self::smt_type(&self.memory[tmp].typed),
+            );
+
+            return Ok((self.memory[b].typed.clone(), tmp, b));
+        }
+
+        if self.memory[b].typed.t == ast::Type::ULiteral {
+            let tmp = self.temporary(
+                format!("implicit coercion of {}", self.memory[b].name),
+                self.memory[a].typed.clone(),
+                self.memory[b].declared.clone(),
+                self.memory[a].tags.clone(),
+            )?;
+
+            self.memory[tmp].value = self.memory[b].value.clone();
+            self.ssa.assign(
+                (tmp,   self.memory[tmp].temporal),
+                (b,     self.memory[b].temporal),
+                Self::smt_type(&self.memory[tmp].typed),
+            );
+
+            return Ok((self.memory[a].typed.clone(), a, tmp));
+        }
+
+        // a float literal rounds into any float type, but not into integers
+        if self.memory[a].typed.t == ast::Type::FLiteral && self.is_float(b) {
+            let tmp = self.temporary(
+                format!("implicit coercion of {}", self.memory[a].name),
+                self.memory[b].typed.clone(),
+                self.memory[a].declared.clone(),
+                self.memory[b].tags.clone(),
+            )?;
+
+            self.memory[tmp].value = self.memory[a].value.clone();
+            self.ssa.assign(
+                (tmp,   self.memory[tmp].temporal),
+                (a,     self.memory[a].temporal),
+                Self::smt_type(&self.memory[tmp].typed),
+            );
+
+            return Ok((self.memory[b].typed.clone(), tmp, b));
+        }
+
+        if self.memory[b].typed.t == ast::Type::FLiteral && self.is_float(a) {
+            let tmp = self.temporary(
+                format!("implicit coercion of {}", self.memory[b].name),
+                self.memory[a].typed.clone(),
+                self.memory[b].declared.clone(),
+                self.memory[a].tags.clone(),
+            )?;
+
+            self.memory[tmp].value = self.memory[b].value.clone();
+            self.ssa.assign(
+                (tmp,   self.memory[tmp].temporal),
+                (b,     self.memory[b].temporal),
+                Self::smt_type(&self.memory[tmp].typed),
+            );
+
+            return Ok((self.memory[a].typed.clone(), a, tmp));
+        }
+
+
+        // TODO if the lhs is a pointer, do an implicit cast
+        if self.memory[a].typed.ptr.len() > 0 {
+            let tmp = self.temporary(
+                format!("implicit cast of {}", self.memory[b].name),
+                self.memory[a].typed.clone(),
+                here.clone(),
+                self.memory[a].tags.clone(),
+            )?;
+
+            self.memory[tmp].value = self.memory[b].value.clone();
+            self.ssa.assign(
+                (tmp,   self.memory[tmp].temporal),
+                (b,     self.memory[b].temporal),
+                Self::smt_type(&self.memory[tmp].typed),
+            );
+            return Ok((self.memory[a].typed.clone(), a, tmp));
+        }
+
+        if let (ast::Type::Other(at), ast::Type::Other(bt)) = (&self.memory[a].typed.t, &self.memory[b].typed.t) {
+            if let (Some(ast::Def::Fntype{..}), Some(ast::Def::Function{..})) = (self.defs.get(at), self.defs.get(bt)) {
+                return Ok((self.memory[b].typed.clone(), a, b));
+            }
+        }
+
+        // object copy with rhs having no tail
+        // TODO in theory the C compiler should init the tail with zeroes, but copy traits would be better
+        if self.memory[a].typed.t == self.memory[b].typed.t && self.memory[b].typed.tail == ast::Tail::None {
+            return Ok((self.memory[a].typed.clone(), a, b));
+        }
+
+        return Err(self.trace(format!("incompatible types {} and {}", self.memory[a].typed, self.memory[b].typed), vec![
+            (here.clone(), format!("this expression is unprovable over incompatible types")),
+            (self.memory[a].declared.clone(), format!("{} := {} {:?}", self.memory[a].name, self.memory[a].typed, self.memory[a].value)),
+            (self.memory[b].declared.clone(), format!("{} := {} {:?}", self.memory[b].name, self.memory[b].typed, self.memory[a].value))
+        ]));
+    }
+
+
+    fn execute_scope(&mut self, body: &mut Vec<Box<ast::Statement>>) -> Result<ScopeReturn, Error> {
+        for i in 0..body.len() {
+            let (body, _rest) = body.split_at_mut(i + 1);
+
+            let marks = (self.ghost_reads.len(), self.calls.len());
+            let mut ghost = self.current_function_lemma;
+
+            match body[i].as_mut() {
+                ast::Statement::Var{loc, typed: ref mut typed_o, tags, name, array, assign} => {
+
+                    let mut typed = typed_o.clone();
+                    if array.is_some() {
+                        typed.ptr.push(ast::Pointer{
+                            loc: loc.clone(),
                             tags: Tags::new(),
                         });
                     }
 
-                    let sym = self.alloc(Name::from(name.as_str()), typed, loc.clone(), tags.clone())?;
+                    // an elided type is the type of the assigned value, which must be known before the local exists
+                    let mut assigned = None;
+                    if let (ast::Type::Elided, Some(assign)) = (&typed.t, assign.as_mut()) {
+                        let sym2 = self.execute_expr(assign)?;
+                        typed = self.memory[sym2].typed.clone();
+                        typed.loc = loc.clone();
+                        assigned = Some(sym2);
+                    }
+
+                    let mut tags = tags.clone();
+                    if self.current_function_lemma {
+                        tags.insert("ghost".to_string(), String::new(), loc.clone());
+                    }
+                    ghost = tags.contains("ghost");
+                    let sym = self.alloc(Name::from(name.as_str()), typed, loc.clone(), tags)?;
 
                     if let Some(array) = array {
                         self.ssa_mark_safe(sym, loc)?;
@@ -805,7 +2114,10 @@ impl Symbolic {
                     self.tail_into_ssa(sym, loc)?;
 
                     if let Some(assign) = assign {
-                        let sym2 = self.execute_expr(assign)?;
+                        let sym2 = match assigned {
+                            Some(sym2) => sym2,
+                            None => self.execute_expr(assign)?,
+                        };
                         self.copy(sym, sym2, loc)?;
 
                     };
@@ -910,6 +2222,15 @@ impl Symbolic {
                     // continue execution as if no condition was met
                 }
                 ast::Statement::Expr{expr, ..} => {
+                    let place = match expr {
+                        ast::Expression::UnaryPost{expr, ..} => Some(expr),
+                        ast::Expression::UnaryPre{expr, op: ast::PrefixOperator::Increment, ..}
+                            | ast::Expression::UnaryPre{expr, op: ast::PrefixOperator::Decrement, ..} => Some(expr),
+                        _ => None,
+                    };
+                    if let Some((sym, true)) = place.and_then(|place|self.loop_place(place)) {
+                        ghost = ghost || self.memory[sym].tags.contains("ghost");
+                    }
                     self.execute_expr(expr)?;
                 }
                 ast::Statement::Return{loc, expr} => {
@@ -919,6 +2240,7 @@ impl Symbolic {
                             self.copy(retsym, e, expr.loc())?;
                         }
                     }
+                    self.check_ghost(ghost, marks)?;
                     self.check_function_model(loc)?;
                     // stop. do not execute anything behind return
                     return Ok(ScopeReturn::Return(loc.clone()));
@@ -932,6 +2254,13 @@ impl Symbolic {
 
                     let switchsym = self.execute_expr(expr)?;
 
+                    // expand switches over the tag of any tagged union it knows the type of
+                    if let Some((name, _)) = self.tagged_union(switchsym) {
+                        return Err(self.trace(format!("cannot switch over this {}", name.0.last().unwrap()), vec![
+                            (expr.loc().clone(), "assign the expression to a local first".to_string()),
+                        ]));
+                    }
+
                     for (conds, body) in cases {
                         for expr2 in conds {
 
@@ -996,18 +2325,23 @@ impl Symbolic {
                     }
                 }
                 ast::Statement::Assign{loc, lhs, op, rhs} => {
+                    let place = lhs.clone();
                     let lhs = self.execute_expr(lhs)?;
+                    let element = lhs;
+                    if self.memory[lhs].tags.contains("ghost") {
+                        ghost = true;
+                    } else if self.current_function_lemma {
+                        return Err(self.trace("a lemma cannot write executable state".to_string(), vec![
+                            (loc.clone(), "only ghost state can be assigned in a lemma".to_string()),
+                        ]));
+                    }
                     let rhs = self.execute_expr(rhs)?;
 
                     let (newtype, lhs, rhs) = self.type_coersion(lhs, rhs, loc)?;
 
 
                     if let ast::AssignOperator::Eq = op {
-                        if self.in_loop {
-                            self.memory[lhs].temporal += 1;
-                        } else {
-                            self.copy(lhs, rhs, loc)?;
-                        }
+                        self.copy(lhs, rhs, loc)?;
                     } else {
                         let tmp = self.temporary("assign inter".to_string(),
                             newtype.clone(),
@@ -1025,12 +2359,19 @@ impl Symbolic {
                         let infix = match op {
                             ast::AssignOperator::Bitor  => ast::InfixOperator::Bitor,
                             ast::AssignOperator::Bitand => ast::InfixOperator::Bitand,
+                            ast::AssignOperator::Bitxor => ast::InfixOperator::Bitxor,
                             ast::AssignOperator::Add    => ast::InfixOperator::Add,
                             ast::AssignOperator::Sub    => ast::InfixOperator::Subtract,
+                            ast::AssignOperator::Mul    => ast::InfixOperator::Multiply,
+                            ast::AssignOperator::Div    => ast::InfixOperator::Divide,
+                            ast::AssignOperator::Mod    => ast::InfixOperator::Modulo,
+                            ast::AssignOperator::Shiftleft  => ast::InfixOperator::Shiftleft,
+                            ast::AssignOperator::Shiftright => ast::InfixOperator::Shiftright,
                             ast::AssignOperator::Eq     => {
                                 unreachable!();
                             }
                         };
+                        self.check_defined(lhs, rhs, &infix, loc)?;
                         let value = Value::InfixOp {
                             lhs:    (lhs, self.memory[lhs].temporal),
                             rhs:    (rhs, self.memory[rhs].temporal),
@@ -1042,22 +2383,31 @@ impl Symbolic {
                             tmp,
                             (lhs, self.memory[lhs].temporal),
                             (rhs, self.memory[rhs].temporal),
-                            infix,
+                            infix.clone(),
                             self.memory[tmp].t.clone(),
                             newtype.t.signed(),
                          );
+                        self.check_overflow(
+                            (lhs, self.memory[lhs].temporal),
+                            (rhs, self.memory[rhs].temporal),
+                            infix,
+                            self.memory[tmp].t.clone(),
+                            loc,
+                        )?;
 
-                        if self.in_loop {
-                            self.memory[lhs].temporal += 1;
-                        } else {
-                            self.copy(lhs, tmp, loc)?;
-                        }
+                        self.copy(lhs, tmp, loc)?;
                     }
+                    self.store_element(element);
+                    self.check_store(&place, loc)?;
                 }
                 ast::Statement::Continue{loc} => {
+                    if let Some(frame) = self.loops.last().cloned() {
+                        self.loop_continue(frame, loc)?;
+                    }
                     return Ok(ScopeReturn::Return(loc.clone()));
                 }
                 ast::Statement::Break{loc} => {
+                    self.loop_break()?;
                     return Ok(ScopeReturn::Return(loc.clone()));
                 }
                 ast::Statement::Block(block) => {
@@ -1065,76 +2415,58 @@ impl Symbolic {
                     self.execute_scope(&mut block.statements)?;
                     self.pop();
                 }
-                ast::Statement::For{e1,e2,e3,body} => {
+                ast::Statement::For{e1,e2,e3,invariants,decreases,body} => {
                     self.push("for loop".to_string());
-                    //self.ssa.push("for loop");
-
-                    let prev_loop = self.in_loop;
-                    self.in_loop = false;
                     self.execute_scope(e1)?;
-                    self.in_loop = true;
-
-                    self.execute_scope(e3)?;
-
-                    if let Some(expr) = e2 {
-                        let sym = self.execute_expr(expr)?;
-                        if self.memory[sym].t != smt::Type::Bool {
-                            return Err(self.trace(format!("expected boolean, got {}", self.memory[sym].typed), vec![
-                                                  (expr.loc().clone(), format!("must be boolean"))
-                            ]));
-                        }
-                        let sym = (sym, self.memory[sym].temporal);
-                        self.cur().trace.push((sym.clone(), expr.loc().clone(),false));
-                        if !self.ssa.attest(sym, true) {
-                            return Err(self.trace(format!("condition breaks ssa"), vec![
-                                (expr.loc().clone(), format!("there may be conflicting constraints"))
-                            ]));
-                        }
-                    }
-
-
-                    self.execute_scope(&mut body.statements)?;
-                    self.in_loop = prev_loop;
-                    //self.ssa.pop("end of for loop");
+                    let after = self.execute_loop(e2.as_mut(), e3, invariants, decreases.as_ref(), body)?;
                     self.pop();
+                    self.cur().trace.extend(after);
                 }
-                ast::Statement::While{expr, body} => {
+                ast::Statement::While{expr, invariants, decreases, body} => {
                     self.push("while loop".to_string());
-                    //self.ssa.push("while loop");
-
-                    let sym = self.execute_expr(expr)?;
-                    if self.memory[sym].t != smt::Type::Bool {
-                        return Err(self.trace(format!("expected boolean, got {}", self.memory[sym].typed), vec![
-                            (expr.loc().clone(), format!("must be boolean"))
-                        ]));
-                    }
-                    let sym = (sym, self.memory[sym].temporal);
-                    self.cur().trace.push((sym.clone(), expr.loc().clone(),false));
-
-                    if !self.ssa.attest(sym, true) {
-                        return Err(self.trace(format!("condition breaks ssa"), vec![
-                            (expr.loc().clone(), format!("there may be conflicting constraints"))
-                        ]));
-                    }
-
-                    let prev_loop = self.in_loop;
-                    self.in_loop = true;
-
-                    self.execute_scope(&mut body.statements)?;
-
-                    self.in_loop = prev_loop;
-                    //self.ssa.pop("end of while loop");
+                    let after = self.execute_loop(Some(expr), &mut Vec::new(), invariants, decreases.as_ref(), body)?;
                     self.pop();
+                    self.cur().trace.extend(after);
                 }
-                ast::Statement::Unsafe{..} => {
+                ast::Statement::Unsafe(block) => {
+                    // unsafe blocks are not executed, so nothing in them can be lowered
+                    if let Some(loc) = block.statements.iter().find_map(|stm|for_in_loc(stm)) {
+                        return Err(self.trace(format!("ranged for is not supported in unsafe blocks"), vec![
+                            (loc.clone(), format!("use an index loop instead")),
+                        ]));
+                    }
                 }
                 ast::Statement::CBlock{..} => {
                 }
+                ast::Statement::Defer{..} => unreachable!("ICE: defer is removed by expand"),
+                ast::Statement::ForIn{..} => unreachable!("ICE: ranged for is lowered by expand"),
+                ast::Statement::Ghost{..} => unreachable!("ICE: ghost statements are marked by ghost::erase after execution"),
             }
+
+            self.check_ghost(ghost, marks)?;
         }
         Ok(ScopeReturn::NoReturn)
     }
 
+    // ghost statements are not emitted, so they cannot call anything that is,
+    // and nothing that is emitted can depend on ghost state
+    fn check_ghost(&mut self, ghost: bool, marks: (usize, usize)) -> Result<(), Error> {
+        let reads = self.ghost_reads.split_off(marks.0);
+        let calls = self.calls.split_off(marks.1);
+        if ghost {
+            if let Some(loc) = calls.first() {
+                return Err(self.trace("ghost code cannot call functions that are executed".to_string(), vec![
+                    (loc.clone(), "this call would be removed along with the ghost statement".to_string()),
+                ]));
+            }
+        } else if let Some(loc) = reads.first() {
+            return Err(self.trace("ghost state used in executable code".to_string(), vec![
+                (loc.clone(), "ghost state only exists in proofs and is not emitted".to_string()),
+            ]));
+        }
+        Ok(())
+    }
+
     fn expand_callargs(
         &mut self,
         defined:        &Vec<ast::NamedArg>,
@@ -1450,11 +2782,15 @@ impl Symbolic {
             _ => {},
         }
 
+        let mut tags = field.1.tags.clone();
+        if self.memory[lhs_sym].tags.contains("ghost") {
+            tags.insert("ghost".to_string(), String::new(), loc.clone());
+        }
         let tmp = self.temporary(
             format!("{}.{}", self.memory[lhs_sym].name, rhs),
             fieldtyped,
             loc.clone(),
-            field.1.tags.clone(),
+            tags,
         )?;
 
 
@@ -1483,13 +2819,24 @@ impl Symbolic {
         Ok(tmp)
     }
 
+    // ghost state may only be read by proofs and ghost statements
+    fn read_ghost(&mut self, sym: Symbol, loc: &ast::Location) {
+        if self.proof == 0 && self.memory[sym].tags.contains("ghost") {
+            self.ghost_reads.push(loc.clone());
+        }
+    }
+
     fn execute_expr(&mut self, expr: &mut ast::Expression) -> Result<Symbol, Error> {
         let exprloc = expr.loc().clone();
         self.ssa.debug_loc(expr.loc());
         match expr {
             ast::Expression::Name(name) => {
                 match &name.t {
-                    ast::Type::Other(n) => self.name(&n, &name.loc),
+                    ast::Type::Other(n) => {
+                        let sym = self.name(&n, &name.loc)?;
+                        self.read_ghost(sym, &name.loc);
+                        Ok(sym)
+                    }
                     _ => {
                         return Err(self.trace(format!("builtin type '{}' is  not an object", name), vec![
                             (name.loc.clone(), format!("cannot use builtin here"))
@@ -1504,7 +2851,11 @@ impl Symbolic {
                 }
 
                 match self.member_access(lhs_sym, rhs, loc) {
-                    Ok(v) => Ok(v),
+                    Ok(v) => {
+                        self.check_variant(lhs_sym, rhs, loc)?;
+                        self.read_ghost(v, loc);
+                        Ok(v)
+                    }
                     Err(e) => {
                         if let ast::Type::Other(name) = &self.memory[lhs_sym].typed.t {
                             let mut name = name.clone();
@@ -1576,7 +2927,8 @@ impl Symbolic {
                     None
                 };
                 match &self.memory[lhs_sym].value {
-                    Value::Array{array, ..} => {
+                    // members must be read from the contents when quantifiers can see them
+                    Value::Array{array, ..} if !self.ssa.quantifiers() => {
                         if let Some(i) = &static_index {
                             if let Some(sym) = array.get(i) {
                                 return Ok(*sym);
@@ -1632,7 +2984,7 @@ impl Symbolic {
                     );
 
                 self.ssa.debug("assert that length less than index is true");
-                self.ssa.assert(vec![(tmp2, self.memory[tmp2].temporal)], |a,model| match a {
+                self.prove("bounds", loc, vec![(tmp2, self.memory[tmp2].temporal)], |a,model| match a {
                     false => {
                         let mut estack = Vec::new();
                         estack.extend(self.demonstrate(model.as_ref().unwrap(), (tmp2, self.memory[tmp2].temporal), 0));
@@ -1656,6 +3008,14 @@ impl Symbolic {
                 )?;
                 self.memory[tmp].value = Value::Unconstrained("array content".to_string());
 
+                if self.ssa.quantifiers() {
+                    let contents = self.contents(lhs_sym, loc)?;
+                    let ptr   = (lhs_sym, self.memory[lhs_sym].temporal);
+                    let index = (rhs_sym, self.memory[rhs_sym].temporal);
+                    self.ssa.select(tmp, (contents, self.memory[contents].temporal), ptr, index);
+                    self.elements.insert(tmp, (contents, ptr, index));
+                }
+
                 match &mut self.memory[lhs_sym].value {
                     Value::Array{array, ..} => {
                         if let Some(i) = &static_index {
@@ -1732,6 +3092,26 @@ impl Symbolic {
                         tail:   ast::Tail::None,
                     };
                     self.literal(loc, Value::Integer(v), t)
+                } else if let Some(v) = v.strip_prefix('-').and_then(parser::parse_u64) {
+                    // two's complement, which truncates correctly into any signed type it coerces into
+                    let t = ast::Typed {
+                        t:      ast::Type::ULiteral,
+                        loc:    loc.clone(),
+                        ptr:    Vec::new(),
+                        tail:   ast::Tail::None,
+                    };
+                    self.literal(loc, Value::Integer(v.wrapping_neg()), t)
+                } else if let (true, Ok(f)) = (self.ssa.floats(), v.parse::<f64>()) {
+                    let t = ast::Typed {
+                        t:      ast::Type::FLiteral,
+                        loc:    loc.clone(),
+                        ptr:    Vec::new(),
+                        tail:   ast::Tail::None,
+                    };
+                    let sym = self.temporary(format!("literal {}", v), t, loc.clone(), Tags::new())?;
+                    self.memory[sym].value = Value::Unconstrained(format!("literal {}", v));
+                    self.ssa.literal_float(sym, f, self.memory[sym].t.clone());
+                    Ok(sym)
                 } else {
                     let t = ast::Typed {
                         t:      ast::Type::ULiteral,
@@ -1767,21 +3147,49 @@ impl Symbolic {
                         }
                         let sym = self.execute_expr(&mut args[0])?;
 
-
-
-                        let r = self.literal(loc, Value::Integer(1), ast::Typed {
-                            t:      ast::Type::ULiteral,
-                            loc:    loc.clone(),
-                            ptr:    Vec::new(),
-                            tail:   ast::Tail::None,
-                        });
-
-                        *expr = ast::Expression::Literal{
+                        *expr = ast::Expression::LiteralString{
                             loc: loc.clone(),
-                            v:  format!("\"{}\"", self.memory[sym].typed),
+                            v:  format!("{}", self.memory[sym].typed).into_bytes(),
                         };
                         self.current_call.pop();
-                        return r;
+                        return self.execute_expr(expr);
+                    },
+                    Some(n @ "isnan") | Some(n @ "isinf") | Some(n @ "::ext::<math.h>::isnan") | Some(n @ "::ext::<math.h>::isinf") => {
+                        if args.len() != 1 {
+                            return Err(self.trace("call argument count mismatch".to_string(), vec![
+                                (name.loc().clone(), format!("builtin needs 1 argument, but you passed {}", args.len()))
+                            ]));
+                        }
+                        let sym = self.execute_expr(&mut args[0])?;
+                        if !self.is_float(sym) {
+                            return Err(self.trace(format!("expected floating point, got {}", self.memory[sym].typed), vec![
+                                (args[0].loc().clone(), format!("{} is only defined on floating point", n))
+                            ]));
+                        }
+
+                        // math.h implements these as macros
+                        if !n.starts_with("::ext::") {
+                            *emit = ast::EmitBehaviour::Error {
+                                loc:        loc.clone(),
+                                message:    format!("builtin {} outside static(). import it from <math.h>", n),
+                            };
+                        }
+
+                        let tmp = self.temporary(
+                            format!("{}({})", n, self.memory[sym].name),
+                            ast::Typed{
+                                t:      ast::Type::Bool,
+                                ptr:    Vec::new(),
+                                loc:    loc.clone(),
+                                tail:   ast::Tail::None,
+                            },
+                            loc.clone(),
+                            Tags::new(),
+                        )?;
+                        let predicate = if n.ends_with("isnan") { "fp.isNaN" } else { "fp.isInfinite" };
+                        self.ssa.float_class(tmp, (sym, self.memory[sym].temporal), predicate);
+                        self.current_call.pop();
+                        return Ok(tmp);
                     },
                     Some("len") => {
                         if args.len() != 1 {
@@ -1805,6 +3213,11 @@ impl Symbolic {
                         }
                         // no shortcut found, continue
                     }
+                    Some("old") => {
+                        return Err(self.trace("old() outside of model".to_string(), vec![
+                            (loc.clone(), "old() refers to the state at function entry and can only be used in model".to_string())
+                        ]));
+                    }
                     Some("static_attest") => {
                         *emit = ast::EmitBehaviour::Skip;
                         self.ssa.debug_loc(loc);
@@ -1814,7 +3227,10 @@ impl Symbolic {
                                 (name.loc().clone(), format!("builtin needs 1 argument, but you passed {}", args.len()))
                             ]));
                         }
-                        let sym = self.execute_expr(&mut args[0])?;
+                        self.proof += 1;
+                        let sym = self.execute_expr(&mut args[0]);
+                        self.proof -= 1;
+                        let sym = sym?;
                         if self.memory[sym].t != smt::Type::Bool {
                             return Err(self.trace(format!("expected boolean, got {}", self.memory[sym].typed), vec![
                                 (args[0].loc().clone(), format!("argument must be boolean"))
@@ -1825,6 +3241,8 @@ impl Symbolic {
                                 (expr.loc().clone(), format!("static_attest leads to conflicting constraints"))
                             ]));
                         }
+                        self.obligations.borrow_mut().push(report::Obligation::new(
+                            "static_attest", args[0].loc(), report::Outcome::Assumed, Duration::default()));
 
                         let r = self.literal(loc, Value::Integer(1), ast::Typed {
                             t:      ast::Type::ULiteral,
@@ -1844,13 +3262,16 @@ impl Symbolic {
                                 (name.loc().clone(), format!("builtin needs 1 argument, but you passed {}", args.len()))
                             ]));
                         }
-                        let sym = self.execute_expr(&mut args[0])?;
+                        self.proof += 1;
+                        let sym = self.execute_expr(&mut args[0]);
+                        self.proof -= 1;
+                        let sym = sym?;
                         if self.memory[sym].t != smt::Type::Bool {
                             return Err(self.trace(format!("expected boolean, got {}", self.memory[sym].typed), vec![
                                 (args[0].loc().clone(), format!("coercion to boolean is difficult to prove"))
                             ]));
                         }
-                        self.ssa.assert(vec![(sym, self.memory[sym].temporal)], |a,model|match a{
+                        self.prove("static_assert", loc, vec![(sym, self.memory[sym].temporal)], |a,model|match a{
                             false => {
                                 let mut estack = vec![(loc.clone(),
                                     format!("you may need an if condition or callsite_assert to increase confidence"))];
@@ -1890,6 +3311,11 @@ impl Symbolic {
                                     (loc.clone(), format!("there may be conflicting constraints"))
                                 ]))
                             }
+                            smt::Assertion::Unknown => {
+                                Err(self.trace(format!("unknown: solver timeout after {}ms", self.ssa.timeout()), vec![
+                                    (loc.clone(), format!("this static could not be evaluated. try a larger @timeout= on the function"))
+                                ]))
+                            }
                             smt::Assertion::Unconstrained(_) => {
                                 let mut estack = vec![(loc.clone(),
                                 format!("you may need an if condition or callsite_assert to increase confidence"))];
@@ -1935,6 +3361,8 @@ impl Symbolic {
                                     ret:    ret.as_ref().map(|r|r.typed.clone()),
                                     callsite_assert: Vec::new(),
                                     callsite_effect: Vec::new(),
+                                    format: None,
+                                    modifies: Vec::new(),
                                 };
 
                             }
@@ -2008,16 +3436,26 @@ impl Symbolic {
                         self.current_call.pop();
                         return r;
                     }
-                    Value::Function{args: fargs, ret, vararg, callsite_assert, callsite_effect, loc: functionlloc} => {
+                    Value::Function{args: fargs, ret, vararg, callsite_assert, callsite_effect, format, loc: functionlloc, modifies} => {
 
                         // borrochecker stupidity
                         let mut callsite_effect = callsite_effect.clone();
                         let vararg = *vararg;
+                        let format = *format;
                         let functionlloc = functionlloc.clone();
 
                         let ret = ret.clone();
                         let fargs = fargs.clone();
                         let mut callsite_assert = callsite_assert.clone();
+                        let modifies = modifies.clone();
+
+                        // a lemma is only proven, so nothing it calls is emitted
+                        let proof = self.proof;
+                        if self.memory[name_sym].tags.contains("lemma") {
+                            self.proof += 1;
+                        } else if self.proof == 0 {
+                            self.calls.push(loc.clone());
+                        }
 
                         if !*expanded {
                             *expanded = true;
@@ -2030,9 +3468,13 @@ impl Symbolic {
                                     &self.memory[name_sym].name, fargs.len(), args.len()))
                             ]));
                         }
+
                         let mut syms = Vec::new();
                         for (i, arg) in args.iter_mut().enumerate() {
-                            let s = self.execute_expr(arg)?;
+                            let mut s = self.execute_expr(arg)?;
+                            if let Some(farg) = fargs.get(i) {
+                                s = self.coerce_literal(s, &farg.typed)?;
+                            }
                             /*
                             // TODO doesnt respect casting yet
 
@@ -2048,7 +3490,28 @@ impl Symbolic {
                             syms.push((s,self.memory[s].temporal));
                         }
 
+                        // the callee assumes the invariants of what it gets by pointer,
+                        // unless the pointee is not initialized yet and the callee constructs it
+                        if self.proof == 0 {
+                            for (i, (s, _)) in syms.iter().enumerate() {
+                                match fargs.get(i) {
+                                    Some(farg) if !farg.tags.contains("unsafe") => (),
+                                    _ => continue,
+                                }
+                                let mut pointee = self.memory[*s].typed.clone();
+                                if pointee.ptr.pop().is_some() && pointee.ptr.is_empty()
+                                    && self.has_invariants(&pointee) && self.initialized_pointee(*s)
+                                {
+                                    self.prove_struct_invariants(*s, args[i].loc(), "when passed to this call")?;
+                                }
+                            }
+                        }
+                        self.proof += 1;
 
+                        if let Some(at) = format {
+                            let syms = syms.iter().map(|(s,_)|*s).collect::<Vec<_>>();
+                            self.check_format(at, args, &syms, loc)?;
+                        }
 
                         //dont expose any symbols during callsite assert
                         let global_only = vec![self.stack[0].clone()];
@@ -2057,6 +3520,7 @@ impl Symbolic {
                         if callsite_assert.len() > 0 {
                             let mut ca_syms = Vec::new();
                             let mut ca_locs = Vec::new();
+                            let mut ca_kinds = Vec::new();
 
 
                             self.push("callsite_assert".to_string());
@@ -2073,16 +3537,18 @@ impl Symbolic {
                                 let casym = self.execute_expr(callsite_assert)?;
                                 ca_syms.push((casym, self.memory[casym].temporal));
                                 ca_locs.push(callsite_assert.loc().clone());
+                                ca_kinds.push(where_kind(callsite_assert));
                             }
 
                             // try the fast path
-                            let ok = self.ssa.assert(ca_syms.clone(), |val,_|val);
+                            let obligations = ca_kinds.iter().cloned().zip(ca_locs.iter().cloned()).collect::<Vec<_>>();
+                            let ok = self.prove_all(&obligations, ca_syms.clone());
 
 
                             // one assertion broke, try them individually
                             if !ok {
-                                for (sym,loc2) in ca_syms.into_iter().zip(ca_locs.into_iter()) {
-                                    self.ssa.assert(vec![sym], |a,model| match a {
+                                for ((sym,loc2),kind) in ca_syms.into_iter().zip(ca_locs.into_iter()).zip(ca_kinds.into_iter()) {
+                                    self.prove(kind, &loc2, vec![sym], |a,model| match a {
                                         false => {
                                             let mut estack = vec![
                                                 (loc.clone(),format!("in this callsite")),
@@ -2105,6 +3571,47 @@ impl Symbolic {
                             self.pop();
                         }
 
+                        // direct recursion must decrease the measure of the caller
+                        if self.memory[name_sym].name == Name::from(&self.current_function_name) {
+                            if let Some((measure, start)) = self.current_function_decreases.clone() {
+                                self.push("recursive call".to_string());
+                                self.ssa.push("recursive call");
+                                for (i, farg) in fargs.iter().enumerate() {
+                                    self.cur().locals.insert(Name::from(&farg.name), syms[i].0);
+                                }
+                                let r = self.check_decreases(&measure, start, loc, "in this recursive call");
+                                self.ssa.pop("end of recursive call");
+                                self.pop();
+                                r?;
+                            }
+                        }
+
+                        // old() in callsite effects is the state before the call
+                        let mut old_bound = Vec::new();
+                        if !callsite_effect.is_empty() {
+                            self.push("old".to_string());
+                            for (i, farg) in fargs.iter().enumerate() {
+                                self.cur().locals.insert(Name::from(&farg.name), syms[i].0);
+                            }
+                            for callsite_effect in &mut callsite_effect {
+                                self.bind_old(callsite_effect, &mut old_bound)?;
+                            }
+                            self.pop();
+                        }
+
+                        // with a modifies clause only the listed locations change
+                        let mut frame = Vec::new();
+                        if !modifies.is_empty() {
+                            self.push("modifies".to_string());
+                            for (i, farg) in fargs.iter().enumerate() {
+                                self.cur().locals.insert(Name::from(&farg.name), syms[i].0);
+                            }
+                            for place in &modifies {
+                                frame.push(self.frame_place(place)?);
+                            }
+                            self.pop();
+                        }
+
                         self.stack = stack_original;
 
                         // TODO for now mark all pointer call args as untrackable in the callsite
@@ -2112,6 +3619,7 @@ impl Symbolic {
                         self.ssa.debug("borrows after call");
                         for (i,(s,_)) in syms.iter().enumerate() {
                             let mut borrow = false;
+                            let mut uninitialized = false;
                             if let Some(farg) = fargs.get(i) {
                                 if farg.tags.contains("mut") || farg.tags.contains("alias"){
                                     borrow = true;
@@ -2120,16 +3628,54 @@ impl Symbolic {
                                     if ptr.tags.contains("mut") || ptr.tags.contains("alias"){
                                         borrow = true;
                                     }
+                                    if ptr.tags.contains("uninitialized") {
+                                        uninitialized = true;
+                                    }
                                 }
                             } else {
                                 borrow = true;
                             }
-                            if borrow {
-                                self.borrow_away(*s);
+                            if borrow && modifies.is_empty() {
+                                if proof == 0 {
+                                    self.check_frame((*s, false), loc)?;
+                                }
+                                // a model relating the members to old() values only means something
+                                // if the members may have changed, and nothing that was in uninitialized
+                                // memory is left after the callee initialized it
+                                self.borrow_away(*s, !old_bound.is_empty() || uninitialized);
+                            }
+                            if let Some(farg) = fargs.get(i) {
+                                self.borrow_closure(*s, &farg.typed);
+                            }
+                        }
+                        for place in frame {
+                            if proof == 0 {
+                                self.check_frame(place, loc)?;
                             }
+                            self.borrow_place(place);
                         }
                         self.ssa.debug("end of borrows after call");
 
+                        // the function proved the invariants of what it got by mut pointer when returning
+                        for (i,(s,_)) in syms.iter().enumerate() {
+                            let farg = match fargs.get(i) {
+                                Some(farg) => farg,
+                                None => continue,
+                            };
+                            let mutable = farg.typed.ptr.last().map(|ptr|ptr.tags.contains("mut")) == Some(true);
+                            if !mutable || farg.tags.contains("unsafe") {
+                                continue;
+                            }
+                            for (inv, invloc) in self.struct_invariants(*s)? {
+                                if !self.ssa.attest(inv, true) {
+                                    return Err(self.trace("struct invariant broke ssa solution".to_string(), vec![
+                                        (invloc, "there may be conflicting constraints".to_string()),
+                                        (loc.clone(), "assumed after this call".to_string()),
+                                    ]));
+                                }
+                            }
+                        }
+
                         let return_sym = self.temporary(
                             format!("return value of {}", self.memory[name_sym].name),
                             ret.clone().unwrap_or(ast::Typed{
@@ -2173,6 +3719,9 @@ impl Symbolic {
                             for (i, farg) in fargs.iter().enumerate() {
                                 self.cur().locals.insert(Name::from(&farg.name), syms[i].0);
                             }
+                            for (name, sym) in &old_bound {
+                                self.cur().locals.insert(name.clone(), *sym);
+                            }
 
                             let casym = self.execute_expr(callsite_effect)?;
 
@@ -2190,6 +3739,7 @@ impl Symbolic {
                         self.ssa.debug("end of callsite effects");
 
                         self.stack = stack_original;
+                        self.proof = proof;
 
 
                         self.current_call.pop();
@@ -2199,8 +3749,15 @@ impl Symbolic {
                         emit_debug(format!("call expression on {} is unprovable", s), &[
                             (loc.clone(), format!("consider using an unsafe block"))
                         ]);
-                        for arg in args {
-                            self.execute_expr(arg)?;
+                        if self.proof == 0 {
+                            self.calls.push(loc.clone());
+                        }
+                        let mut syms = Vec::new();
+                        for arg in args.iter_mut() {
+                            syms.push(self.execute_expr(arg)?);
+                        }
+                        if let Some(at) = static_name.as_ref().and_then(|n|c_format_arg(n)) {
+                            self.check_format(at, args, &syms, loc)?;
                         }
                         let tmp = self.temporary(
                             format!("return value of {}", self.memory[name_sym].name),
@@ -2208,16 +3765,118 @@ impl Symbolic {
                             loc.clone(),
                             Tags::new(),
                             )?;
+                        if static_name.as_deref() == Some("::ext::<stddef.h>::sizeof") {
+                            // every complete type has a size, so it can be divided by
+                            let zero = self.literal(loc, Value::Integer(0), self.memory[tmp].typed.clone())?;
+                            let nonzero = self.compare(tmp, zero, ast::InfixOperator::Nequals, loc)?;
+                            self.ssa.attest(nonzero, true);
+                        }
                         self.current_call.pop();
                         Ok(tmp)
 
-                    }
-                    o => {
-                        return Err(self.trace(format!("call expression on {} not safe", o), vec![
-                            (loc.clone(), format!("call requires a function"))
+                    }
+                    o => {
+                        return Err(self.trace(format!("call expression on {} not safe", o), vec![
+                            (loc.clone(), format!("call requires a function"))
+                        ]));
+                    }
+                }
+            }
+            ast::Expression::Closure{..} => unreachable!("ICE: closure is lowered by closure::lower"),
+            ast::Expression::Quantifier{loc, forall, var, range, body} => {
+                let tmp = self.temporary(
+                    format!("{} ({} {})", if *forall { "forall" } else { "exists" }, var.typed, var.name),
+                    ast::Typed{
+                        t:      ast::Type::Bool,
+                        ptr:    Vec::new(),
+                        loc:    loc.clone(),
+                        tail:   ast::Tail::None,
+                    },
+                    loc.clone(),
+                    Tags::new(),
+                )?;
+
+                self.push("quantifier".to_string());
+                let bound = self.alloc(Name::from(&var.name), var.typed.clone(), var.loc.clone(), var.tags.clone())?;
+                self.memory[bound].value = Value::Unconstrained("bound by quantifier".to_string());
+                self.ssa.quantifier_begin(bound);
+
+                let mut syms = Vec::new();
+                for expr in [range, body] {
+                    let sym = self.execute_expr(expr)?;
+                    if self.memory[sym].typed.t != ast::Type::Bool {
+                        return Err(self.trace(format!("expected boolean, got {}", self.memory[sym].typed), vec![
+                            (expr.loc().clone(), "quantifier must be over a boolean expression".to_string())
                         ]));
                     }
+                    let sym = (sym, self.memory[sym].temporal);
+                    // the body only needs to hold within range
+                    if syms.is_empty() {
+                        self.ssa.attest(sym, true);
+                    }
+                    syms.push(sym);
                 }
+
+                self.ssa.quantifier_end(tmp, *forall, syms[0], syms[1]);
+                self.pop();
+                Ok(tmp)
+            }
+            ast::Expression::Ternary { loc, cond, then, otherwise} => {
+                let cond_sym = self.execute_expr(cond)?;
+                if self.memory[cond_sym].typed.t != ast::Type::Bool {
+                    return Err(self.trace(format!("expected boolean, got {}", self.memory[cond_sym].typed), vec![
+                        (cond.loc().clone(), format!("coercion to boolean is difficult to prove"))
+                    ]));
+                }
+                let cond_sym = (cond_sym, self.memory[cond_sym].temporal);
+
+                // each arm is only evaluated when the condition selects it
+                self.ssa.branch();
+                self.ssa.constrain_branch(cond_sym, true);
+                self.cur().trace.push((cond_sym, cond.loc().clone(), false));
+                let then_sym = self.execute_expr(then);
+                self.cur().trace.pop();
+                self.ssa.unbranch(false);
+                let then_sym = then_sym?;
+
+                self.ssa.branch();
+                self.ssa.constrain_branch(cond_sym, false);
+                self.cur().trace.push((cond_sym, cond.loc().clone(), false));
+                let otherwise_sym = self.execute_expr(otherwise);
+                self.cur().trace.pop();
+                self.ssa.unbranch(false);
+                let otherwise_sym = otherwise_sym?;
+
+                let (newtype, then_sym, otherwise_sym) = self.type_coersion(then_sym, otherwise_sym, loc)?;
+
+                let scalar = |v: &Value| !matches!(v, Value::Struct{..} | Value::Array{..});
+                if !newtype.ptr.is_empty()
+                    || !scalar(&self.memory[then_sym].value)
+                    || !scalar(&self.memory[otherwise_sym].value)
+                {
+                    return Err(self.trace(format!("conditional expression must have a scalar type"), vec![
+                        (loc.clone(), format!("arms are {}", newtype))
+                    ]));
+                }
+
+                let tmp = self.temporary(format!("conditional expression"),
+                    newtype.clone(),
+                    loc.clone(),
+                    Tags::new(),
+                )?;
+                self.memory[tmp].value = Value::Unconstrained("conditional expression".to_string());
+
+                self.ssa.branch();
+                self.ssa.constrain_branch(cond_sym, true);
+                self.ssa.assign_branch(
+                    (tmp,           self.memory[tmp].temporal),
+                    (then_sym,      self.memory[then_sym].temporal),
+                    (otherwise_sym, self.memory[otherwise_sym].temporal),
+                    self.memory[tmp].t.clone(),
+                );
+                self.ssa.unbranch(false);
+
+                Ok(tmp)
             }
             ast::Expression::Infix { lhs, rhs, loc, op} => {
                 let lhs_sym = self.execute_expr(lhs)?;
@@ -2247,6 +3906,12 @@ impl Symbolic {
                     }
                 }
                 */
+                if self.is_float(lhs_sym) && newtype.ptr.is_empty() && !op.takes_float() {
+                    return Err(self.trace(format!("invalid types for integer operator"), vec![
+                        (loc.clone(), format!("not defined for type {}", newtype))
+                    ]))
+                }
+
                 let signed  = newtype.t.signed();
                 if op.returns_boolean() {
                     newtype = ast::Typed{
@@ -2313,7 +3978,7 @@ impl Symbolic {
                         false,
                     );
                     self.ssa.debug("assert that length less than index is true");
-                    self.ssa.assert(vec![(len_assert, self.memory[len_assert].temporal)], |a, model| match a {
+                    self.prove("bounds", loc, vec![(len_assert, self.memory[len_assert].temporal)], |a, model| match a {
                         false => {
                             let mut estack = Vec::new();
                             estack.extend(self.demonstrate(model.as_ref().unwrap(), (len_assert, self.memory[len_assert].temporal), 0));
@@ -2380,6 +4045,8 @@ impl Symbolic {
 
 
 
+                self.check_defined(lhs_sym, rhs_sym, op, loc)?;
+
                 let value = Value::InfixOp {
                     lhs:    (lhs_sym, self.memory[lhs_sym].temporal),
                     rhs:    (rhs_sym, self.memory[rhs_sym].temporal),
@@ -2396,6 +4063,13 @@ impl Symbolic {
                     self.memory[tmp].t.clone(),
                     signed,
                 );
+                self.check_overflow(
+                    (lhs_sym, self.memory[lhs_sym].temporal),
+                    (rhs_sym, self.memory[rhs_sym].temporal),
+                    op.clone(),
+                    self.memory[tmp].t.clone(),
+                    loc,
+                )?;
                 Ok(tmp)
             }
             ast::Expression::Cast { expr, into, loc } => {
@@ -2423,11 +4097,8 @@ impl Symbolic {
                 self.memory[lhs_sym].tags.clone(),
                 )?;
 
-                if self.in_loop {
-                    self.memory[tmp].temporal += 1;
-                } else {
-                    self.copy(tmp, lhs_sym, loc)?;
-                }
+                self.copy(tmp, lhs_sym, loc)?;
+                self.check_step_overflow(tmp, op == &ast::PostfixOperator::Increment, loc)?;
 
                 let value = Value::PostfixOp {
                     lhs:    (tmp, self.memory[tmp].temporal),
@@ -2447,7 +4118,8 @@ impl Symbolic {
                     op.clone(),
                     self.memory[lhs_sym].t.clone(),
                 );
-
+                self.store_element(lhs_sym);
+                self.check_store(expr, loc)?;
 
                 Ok(tmp)
             }
@@ -2459,30 +4131,9 @@ impl Symbolic {
                     }
                     ast::PrefixOperator::AddressOf => {
                         let lhs_sym = self.execute_expr(expr)?;
-                        let mut typed =  self.memory[lhs_sym].typed.clone();
-                        typed.ptr.push(ast::Pointer{
-                            tags:   self.memory[lhs_sym].tags.clone(),
-                            loc:    loc.clone(),
-                        });
-                        let tmp = self.temporary(
-                            format!("addressof({})", self.memory[lhs_sym].name),
-                            typed,
-                            loc.clone(),
-                            ast::Tags::new(),
-                        )?;
-                        self.memory[tmp].value = Value::Address(lhs_sym);
-                        self.len_into_ssa(tmp, loc, 1)?;
-
-                        // TODO this is semi wrong
-                        // it must be coming from a virtual stack
-                        // because if we want to prove pointer arithmetic
-                        // this value is meaningless
-                        self.ssa.literal(tmp, lhs_sym as u64, smt::Type::Unsigned(64));
-
-                        self.ssa_mark_safe(tmp, loc)?;
-                        Ok(tmp)
+                        self.address_of(lhs_sym, loc)
                     }
-                    crate::ast::PrefixOperator::Boolnot | crate::ast::PrefixOperator::Bitnot => {
+                    crate::ast::PrefixOperator::Boolnot | crate::ast::PrefixOperator::Bitnot | crate::ast::PrefixOperator::Negate => {
                         let rhs_sym = self.execute_expr(expr)?;
 
 
@@ -2492,7 +4143,28 @@ impl Symbolic {
                                     (expr.loc().clone(), format!("coercion to boolean is difficult to prove"))
                                 ]));
                             }
+                        } else if *op == crate::ast::PrefixOperator::Negate {
+                            if self.memory[rhs_sym].t == smt::Type::Bool {
+                                return Err(self.trace(format!("expected number, got {}", self.memory[rhs_sym].typed), vec![
+                                    (expr.loc().clone(), format!("invalid operand on boolean"))
+                                ]));
+                            }
+                            if self.checks_overflow(rhs_sym) {
+                                let zero = self.literal(loc, Value::Integer(0), self.memory[rhs_sym].typed.clone())?;
+                                self.check_overflow(
+                                    (zero, self.memory[zero].temporal),
+                                    (rhs_sym, self.memory[rhs_sym].temporal),
+                                    ast::InfixOperator::Subtract,
+                                    self.memory[rhs_sym].t.clone(),
+                                    loc,
+                                )?;
+                            }
                         } else if *op == crate::ast::PrefixOperator::Bitnot {
+                            if self.is_float(rhs_sym) {
+                                return Err(self.trace(format!("expected integer , got {}", self.memory[rhs_sym].typed), vec![
+                                    (expr.loc().clone(), format!("invalid operand on floating point"))
+                                ]));
+                            }
                             if self.memory[rhs_sym].t == smt::Type::Bool {
                                 return Err(self.trace(format!("expected integer , got {}", self.memory[rhs_sym].typed), vec![
                                     (expr.loc().clone(), format!("invalid operand on boolean"))
@@ -2534,6 +4206,8 @@ impl Symbolic {
                             Tags::new(),
                         )?;
                         self.copy(tmp, rhs_sym, loc)?;
+                        self.check_step_overflow(tmp, op == &ast::PrefixOperator::Increment, loc)?;
+                        self.check_modifies(expr, loc)?;
 
 
                         let value = Value::PrefixOp {
@@ -2569,6 +4243,11 @@ impl Symbolic {
                     members,
                 };
 
+                if self.proof == 0 && self.has_invariants(typed) {
+                    let ptr = self.address_of(aptr, loc)?;
+                    self.prove_struct_invariants(ptr, loc, "for this struct literal")?;
+                }
+
                 Ok(aptr)
             }
             ast::Expression::ArrayInit {fields, loc} => {
@@ -2609,12 +4288,108 @@ impl Symbolic {
 
 
 
-    // a pointer value has been borrowed, so everything it points to might have been tampered with
-    fn borrow_away(&mut self, sym: Symbol) {
+    fn address_of(&mut self, lhs_sym: Symbol, loc: &ast::Location) -> Result<Symbol, Error> {
+        let mut typed =  self.memory[lhs_sym].typed.clone();
+        typed.ptr.push(ast::Pointer{
+            tags:   self.memory[lhs_sym].tags.clone(),
+            loc:    loc.clone(),
+        });
+        let tmp = self.temporary(
+            format!("addressof({})", self.memory[lhs_sym].name),
+            typed,
+            loc.clone(),
+            ast::Tags::new(),
+        )?;
+        self.memory[tmp].value = Value::Address(lhs_sym);
+        self.len_into_ssa(tmp, loc, 1)?;
+
+        // TODO this is semi wrong
+        // it must be coming from a virtual stack
+        // because if we want to prove pointer arithmetic
+        // this value is meaningless
+        self.ssa.literal(tmp, lhs_sym as u64, smt::Type::Unsigned(64));
+
+        self.ssa_mark_safe(tmp, loc)?;
+        Ok(tmp)
+    }
+
+    // the smt type of what a pointer or array points to
+    fn pointee_type(&self, sym: Symbol) -> Option<smt::Type> {
+        let mut typed = self.memory[sym].typed.clone();
+        typed.ptr.pop()?;
+        Some(Self::smt_type(&typed))
+    }
+
+    // everything pointers to one element type point to, as one smt array indexed by the pointer value,
+    // just like len and safe are a function of the pointer value and not of the local holding it.
+    // only used when quantifiers need to talk about all of it at once
+    fn contents(&mut self, sym: Symbol, loc: &ast::Location) -> Result<Symbol, Error> {
+        let element = self.pointee_type(sym).expect("ICE: contents of non pointer");
+        if let Some(contents) = self.contents.get(&element) {
+            return Ok(*contents);
+        }
+        let t = smt::Type::Array(Box::new(smt::Type::Array(Box::new(element.clone()))));
+
+        let contents = self.temporary(
+            format!("contents of {} memory", self.memory[sym].typed),
+            self.memory[sym].typed.clone(),
+            loc.clone(),
+            Tags::new(),
+        )?;
+        self.memory[contents].t = t.clone();
+        self.memory[contents].value = Value::Unconstrained("contents".to_string());
+        self.ssa.declare(contents, &format!("contents of {} memory", self.memory[sym].typed), t);
+        self.contents.insert(element, contents);
+        Ok(contents)
+    }
+
+    // a write to an array member is a write to the contents of the array
+    fn store_element(&mut self, element: Symbol) {
+        let (contents, ptr, index) = match self.elements.get(&element) {
+            Some(v) => *v,
+            None => return,
+        };
+        let t = match &self.memory[contents].t {
+            smt::Type::Array(t) => match &**t {
+                smt::Type::Array(t) => (**t).clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let prev = (contents, self.memory[contents].temporal);
+        self.memory[contents].temporal += 1;
+        self.ssa.store(
+            (contents, self.memory[contents].temporal),
+            prev,
+            ptr,
+            index,
+            (element, self.memory[element].temporal),
+            t,
+        );
+    }
+
+    // the contents of borrowed memory may be anything after the call
+    fn borrow_array(&mut self, sym: Symbol) {
+        if self.memory[sym].t != smt::Type::Unsigned(64) {
+            return;
+        }
+        if let Some(contents) = self.pointee_type(sym).and_then(|t|self.contents.get(&t).cloned()) {
+            let prev = (contents, self.memory[contents].temporal);
+            self.memory[contents].temporal += 1;
+            self.ssa.forget(
+                (contents, self.memory[contents].temporal),
+                prev,
+                (sym, self.memory[sym].temporal),
+            );
+        }
+    }
+
+    fn borrow_away(&mut self, sym: Symbol, deep: bool) {
+        self.borrow_array(sym);
         match &self.memory[sym].value.clone() {
             Value::Array{array,..} => {
                 for (_,s2) in array.clone() {
-                    self.borrow_away(s2);
+                    self.borrow_away(s2, deep);
                 }
             }
             Value::Address(to) => {
@@ -2629,12 +4404,179 @@ impl Symbolic {
                     (*to, self.memory[*to].temporal -1),
                     self.memory[*to].t.clone(),
                 );
-
+                if deep {
+                    self.borrow_contents(*to);
+                }
             }
             _ => ()
         }
     }
 
+    // a location listed in the modifies clause of a called function
+    fn borrow_place(&mut self, (sym, whole): (Symbol, bool)) {
+        if !whole {
+            self.borrow_away(sym, true);
+            return;
+        }
+        self.memory[sym].temporal += 1;
+        self.ssa.assign_branch(
+            (sym, self.memory[sym].temporal),
+            (sym, self.memory[sym].temporal),
+            (sym, self.memory[sym].temporal -1),
+            self.memory[sym].t.clone(),
+        );
+        self.borrow_contents(sym);
+    }
+
+    // members of borrowed memory are just as untrackable as the memory itself
+    fn borrow_contents(&mut self, sym: Symbol) {
+        self.borrow_array(sym);
+        let arrays = self.array_members(sym);
+        let mut contents = match &self.memory[sym].value {
+            Value::Struct{members} => members.values().cloned().collect::<Vec<_>>(),
+            Value::Array{array,..} => array.values().cloned().collect::<Vec<_>>(),
+            _ => return,
+        };
+        contents.sort();
+        for member in contents {
+            if arrays.contains(&member) {
+                self.borrow_contents(member);
+                continue;
+            }
+            self.memory[member].temporal += 1;
+            self.ssa.assign_branch(
+                (member, self.memory[member].temporal),
+                (member, self.memory[member].temporal),
+                (member, self.memory[member].temporal -1),
+                self.memory[member].t.clone(),
+            );
+            self.borrow_contents(member);
+        }
+    }
+
+
+    // a closure may write to every mutable local captured in its environment
+    fn borrow_closure(&mut self, sym: Symbol, typed: &ast::Typed) {
+        let closure = match &typed.t {
+            ast::Type::Other(name) if typed.ptr.is_empty() => matches!(self.defs.get(name), Some(ast::Def::Struct{closure: true, ..})),
+            _ => false,
+        };
+        if !closure {
+            return;
+        }
+        let env = match &self.memory[sym].value {
+            Value::Struct{members} => match members.get("env").map(|env|&self.memory[*env].value) {
+                Some(Value::Address(env)) => *env,
+                _ => return,
+            },
+            _ => return,
+        };
+        if let Value::Struct{members} = self.memory[env].value.clone() {
+            for (_, member) in members {
+                if self.memory[member].typed.ptr.last().map(|ptr|ptr.tags.contains("mut")) == Some(true) {
+                    self.borrow_away(member, false);
+                }
+            }
+        }
+    }
+
+    // arguments following a literal printf style format string must match its conversions
+    fn check_format(
+        &mut self,
+        at:     usize,
+        args:   &[Box<ast::Expression>],
+        syms:   &[Symbol],
+        loc:    &ast::Location,
+    ) -> Result<(), Error> {
+        let (fmt, fmtloc) = match args.get(at).map(|a|a.as_ref()) {
+            Some(ast::Expression::LiteralString{v, loc}) => (v.clone(), loc.clone()),
+            // only literals can be checked
+            _ => return Ok(()),
+        };
+
+        let conversions = match format_conversions(&fmt) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(self.trace("invalid format string".to_string(), vec![
+                    (fmtloc, e)
+                ]));
+            }
+        };
+
+        let passed = args.len() - at - 1;
+        if conversions.len() != passed {
+            return Err(self.trace("format argument count mismatch".to_string(), vec![
+                (loc.clone(), format!("format string expects {} arguments, but you passed {}", conversions.len(), passed)),
+                (fmtloc, "format string is here".to_string()),
+            ]));
+        }
+
+        for (i, (conversion, expected)) in conversions.into_iter().enumerate() {
+            let arg = &args[at + 1 + i];
+            let sym = syms[at + 1 + i];
+            let typed = self.memory[sym].typed.clone();
+
+            let is_struct = match &typed.t {
+                ast::Type::Other(n) => matches!(self.defs.get(n), Some(ast::Def::Struct{..})),
+                _ => false,
+            };
+            // varargs are only promoted to int, so the width must match the length modifier.
+            // c types passed through as Other are not known here
+            let ok = match expected {
+                FormatArg::Int     => typed.ptr.is_empty() && !is_struct && matches!(typed.t,
+                    ast::Type::U8 | ast::Type::U16 | ast::Type::U32 | ast::Type::I8 | ast::Type::I16 | ast::Type::I32 |
+                    ast::Type::Int | ast::Type::UInt | ast::Type::Bool | ast::Type::ULiteral | ast::Type::ILiteral |
+                    ast::Type::Other(_)),
+                FormatArg::Long    => typed.ptr.is_empty() && !is_struct && matches!(typed.t,
+                    ast::Type::U64 | ast::Type::I64 | ast::Type::USize | ast::Type::ISize | ast::Type::Other(_)),
+                FormatArg::Float   => typed.ptr.is_empty() && !is_struct &&
+                    matches!(typed.t, ast::Type::F32 | ast::Type::F64 | ast::Type::FLiteral | ast::Type::Other(_)),
+                FormatArg::String{..} => typed.ptr.len() == 1 && !is_struct &&
+                    matches!(typed.t, ast::Type::U8 | ast::Type::I8 | ast::Type::Other(_)),
+                FormatArg::Pointer => !typed.ptr.is_empty(),
+            };
+            if !ok {
+                return Err(self.trace("format argument type mismatch".to_string(), vec![
+                    (arg.loc().clone(), format!("{} expects {}, got {}", conversion, expected, typed)),
+                    (fmtloc, "format string is here".to_string()),
+                ]));
+            }
+
+            // a precision limits how much of the string is read
+            if expected != (FormatArg::String{precision: false}) {
+                continue;
+            }
+
+            let tmp = self.temporary(
+                format!("nullterm({})", self.memory[sym].name),
+                ast::Typed{
+                    t:      ast::Type::Bool,
+                    ptr:    Vec::new(),
+                    loc:    arg.loc().clone(),
+                    tail:   ast::Tail::None,
+                },
+                arg.loc().clone(),
+                Tags::new(),
+            )?;
+            let theosym = self.builtin.get("nullterm").expect("ICE: nullterm theory not built in");
+            self.ssa.invocation(*theosym, vec![(sym, self.memory[sym].temporal)], (tmp, 0));
+
+            self.prove("nullterm", arg.loc(), vec![(tmp, self.memory[tmp].temporal)], |a,model| match a {
+                false  => {
+                    let mut estack = vec![(arg.loc().clone(),
+                    format!("{} requires a null terminated string. you may need an if condition or callsite_assert to prove it is nullterm", conversion))];
+                    if let Some(model) = &model {
+                        estack.extend(self.demonstrate(model, (tmp, self.memory[tmp].temporal), 0));
+                    }
+                    Err(self.trace("unproven nullterm for format argument".to_string(), estack))
+                }
+                true => {
+                    Ok(())
+                }
+            })?;
+        }
+        Ok(())
+    }
 
     fn deref(&mut self, lhs_sym: Symbol, loc: &ast::Location) -> Result<Symbol, Error> {
 
@@ -2687,7 +4629,7 @@ impl Symbolic {
         let theosym = self.builtin.get("safe").expect("ICE: safe theory not built in");
         self.ssa.invocation(*theosym, vec![(lhs_sym, self.memory[lhs_sym].temporal)], (tmp1, 0));
 
-        self.ssa.assert(vec![(tmp1, self.memory[tmp1].temporal)], |a,model| match a {
+        self.prove("safe", loc, vec![(tmp1, self.memory[tmp1].temporal)], |a,model| match a {
             false  => {
                 let mut estack = vec![(loc.clone(),
                 format!("you may need an if condition or callsite_assert to prove it is safe"))];
@@ -2725,6 +4667,7 @@ impl Symbolic {
         match t.t {
             ast::Type::Bool     => crate::smt::Type::Bool,
             ast::Type::Other(_) => crate::smt::Type::Unsigned(64),
+            ast::Type::Instance(_, _) => crate::smt::Type::Unsigned(64),
             ast::Type::U8       => crate::smt::Type::Unsigned(8),
             ast::Type::U16      => crate::smt::Type::Unsigned(16),
             ast::Type::U32      => crate::smt::Type::Unsigned(32),
@@ -2742,14 +4685,15 @@ impl Symbolic {
             ast::Type::USize    => crate::smt::Type::Unsigned(64),
             ast::Type::ISize    => crate::smt::Type::Signed(64),
 
-            ast::Type::F64      => crate::smt::Type::Unsigned(64),
-            ast::Type::F32      => crate::smt::Type::Unsigned(64),
+            ast::Type::F64      => crate::smt::Type::Float(11, 53),
+            ast::Type::F32      => crate::smt::Type::Float(8, 24),
 
 
             // these are actually just pollution in smt. they're casted before use
             ast::Type::Elided   => crate::smt::Type::Unsigned(64),
             ast::Type::ULiteral => crate::smt::Type::Unsigned(64),
             ast::Type::ILiteral => crate::smt::Type::Signed(64),
+            ast::Type::FLiteral => crate::smt::Type::Float(11, 53),
         }
     }
 
@@ -2765,6 +4709,7 @@ impl Symbolic {
         }
 
         let t = Self::smt_type(&typed);
+        self.check_float_support(&t, &loc)?;
         let symbol = self.memory.len();
         self.memory.push(Storage{
             typed:      typed.clone(),
@@ -2817,6 +4762,11 @@ impl Symbolic {
                             (loc.clone(), format!("there may be conflicting constraints"))
                         ]))
                     }
+                    smt::Assertion::Unknown => {
+                        Err(self.trace(format!("unknown: solver timeout after {}ms", self.ssa.timeout()), vec![
+                            (loc.clone(), format!("this tail size could not be evaluated. try a larger @timeout= on the function"))
+                        ]))
+                    }
                     smt::Assertion::Unconstrained(_) => {
                         let mut estack = vec![(loc.clone(),
                         format!("you may need an if condition or callsite_assert to increase confidence"))];
@@ -2867,6 +4817,7 @@ impl Symbolic {
     fn temporary(&mut self, name: String, typed: ast::Typed, loc: ast::Location, tags: ast::Tags) -> Result<Symbol, Error> {
         self.ssa.debug_loc(&loc);
         let t = Self::smt_type(&typed);
+        self.check_float_support(&t, &loc)?;
         let symbol = self.memory.len();
         self.memory.push(Storage{
             t:          t.clone(),
@@ -2982,9 +4933,27 @@ impl Symbolic {
                    }
                    _ => {
                        self.len_into_ssa(lhs, used_here, len)?;
+                       // a pointer to the array has the address of the array
+                       if self.memory[lhs].t == smt::Type::Unsigned(64) {
+                           self.ssa.assign_branch(
+                               (lhs, self.memory[lhs].temporal),
+                               (rhs, self.memory[rhs].temporal),
+                               (lhs, self.memory[lhs].temporal-1),
+                               smt::Type::Unsigned(64),
+                           );
+                       }
                    }
                }
+               let zeroes = array.values().all(|m|matches!(self.memory[*m].value, Value::Integer(0)));
+               let whole = matches!(value, Value::Array{..});
                self.memory[lhs].value = value;
+               //initialization is defined to be complete in C, so {0} zeroes all of a struct
+               if zeroes && self.struct_fields(lhs).is_some() {
+                   self.zero(lhs, used_here)?;
+               }
+               if whole {
+                   self.borrow_array(lhs);
+               }
                return Ok(());
             },
             _ => {
@@ -3129,7 +5098,7 @@ impl Symbolic {
         ]));
     }
 
-    fn new(module_name: &Name, hints: &HashMap<String, String>) -> Self {
+    fn new(module_name: &Name, hints: &HashMap<String, String>, floats: bool, quantifiers: bool) -> Self {
         Symbolic {
             stack:  vec![
                 Scope {
@@ -3139,7 +5108,7 @@ impl Symbolic {
                 }
             ],
             memory:  Default::default(),
-            ssa:     Solver::new(module_name.to_string(), hints),
+            ssa:     Solver::new(module_name.to_string(), hints, floats, quantifiers),
             builtin: Default::default(),
             defs:    HashMap::new(),
             current_module_name:    module_name.human_name(),
@@ -3147,8 +5116,19 @@ impl Symbolic {
             current_function_ret:   None,
             current_function_model: Vec::new(),
             current_call:           Vec::new(),
-            in_loop: false,
+            current_function_decreases: None,
+            loops:   Vec::new(),
             in_model:false,
+            current_function_args:  Vec::new(),
+            counterexample: RefCell::new(None),
+            obligations:    RefCell::new(Vec::new()),
+            contents:       HashMap::new(),
+            elements:       HashMap::new(),
+            current_function_lemma: false,
+            current_function_modifies: Vec::new(),
+            proof:          0,
+            ghost_reads:    Vec::new(),
+            calls:          Vec::new(),
         }
     }
 
@@ -3161,14 +5141,277 @@ impl Symbolic {
         });
     }
 
-    fn pop(&mut self) {
-        self.stack.pop();
+    fn pop(&mut self) {
+        self.stack.pop();
+    }
+
+    fn check_float_support(&self, t: &smt::Type, loc: &ast::Location) -> Result<(), Error> {
+        if let smt::Type::Float(..) = t {
+            if !self.ssa.floats() {
+                return Err(self.trace("floating point is not supported by this solver".to_string(), vec![
+                    (loc.clone(), "proving floating point requires z3".to_string())
+                ]));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_float(&self, sym: Symbol) -> bool {
+        matches!(self.memory[sym].t, smt::Type::Float(..))
+    }
+
+    fn cur(&mut self) -> &mut Scope {
+        self.stack.last_mut().unwrap()
+    }
+
+
+    // signed overflow is undefined behaviour in C.
+    // unsigned wraparound is defined, and only an error with unsigned_overflow = true in zz.toml
+    fn checks_overflow(&self, sym: Symbol) -> bool {
+        if !self.memory[sym].typed.ptr.is_empty() {
+            return false;
+        }
+        match self.memory[sym].t {
+            smt::Type::Signed(_)    => true,
+            smt::Type::Unsigned(_)  => UNSIGNED_OVERFLOW.load(Ordering::Relaxed),
+            _                       => false,
+        }
+    }
+
+    fn check_overflow(&mut self, lhs: TemporalSymbol, rhs: TemporalSymbol, op: ast::InfixOperator, t: smt::Type, loc: &ast::Location)
+        -> Result<(), Error>
+    {
+        let signed = match t {
+            smt::Type::Signed(_) => true,
+            smt::Type::Unsigned(_) if UNSIGNED_OVERFLOW.load(Ordering::Relaxed) => false,
+            _ => return Ok(()),
+        };
+        let opname = match op {
+            ast::InfixOperator::Add      => "+",
+            ast::InfixOperator::Subtract => "-",
+            ast::InfixOperator::Multiply => "*",
+            _ => return Ok(()),
+        };
+        if !self.memory[lhs.0].typed.ptr.is_empty() || !self.memory[rhs.0].typed.ptr.is_empty() {
+            return Ok(());
+        }
+
+        self.ssa.debug("begin overflow");
+        let tmp = self.temporary(format!("{} {} {}", self.memory[lhs.0].name, opname, self.memory[rhs.0].name),
+            ast::Typed{
+                t:      ast::Type::Bool,
+                ptr:    Vec::new(),
+                loc:    loc.clone(),
+                tail:   ast::Tail::None,
+            },
+            loc.clone(),
+            Tags::new(),
+        )?;
+        self.memory[tmp].value = Value::InfixOp {
+            lhs,
+            rhs,
+            op: op.clone(),
+        };
+        self.ssa.no_overflow(tmp, lhs, rhs, op, t);
+
+        self.ssa.debug("assert that the operation does not overflow");
+        self.prove("overflow", loc, vec![(tmp, self.memory[tmp].temporal)], |a,model| match a {
+            false => {
+                let mut estack = Vec::new();
+                estack.extend(self.demonstrate(model.as_ref().unwrap(), (tmp, self.memory[tmp].temporal), 0));
+                Err(self.trace(format!("possible {} overflow", if signed { "signed integer" } else { "unsigned integer" }), estack))
+            }
+            true => {
+                Ok(())
+            }
+        })
+    }
+
+    // the name and variants of the tagged union in sym, if it is one
+    fn tagged_union(&self, sym: Symbol) -> Option<(Name, Vec<ast::Variant>)> {
+        if !self.memory[sym].typed.ptr.is_empty() {
+            return None;
+        }
+        if let ast::Type::Other(n) = &self.memory[sym].typed.t {
+            if let Some(variants) = self.defs.get(n).and_then(ast::Variant::of) {
+                return Some((n.clone(), variants.clone()));
+            }
+        }
+        None
+    }
+
+    // payload fields of a tagged union may only be accessed while the tag is their variant
+    fn check_variant(&mut self, lhs_sym: Symbol, field: &str, loc: &ast::Location) -> Result<(), Error> {
+        let (name, variants) = match self.tagged_union(lhs_sym) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let (tag, variant) = match variants.iter().enumerate().find(|(_, v)|v.fields.iter().any(|f|f == field)) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        self.ssa.debug("begin variant");
+        let tagsym   = self.member_access(lhs_sym, "tag", loc)?;
+        let expected = self.literal(loc, Value::Integer(tag as u64), self.memory[tagsym].typed.clone())?;
+        let matches  = self.compare(tagsym, expected, ast::InfixOperator::Equals, loc)?;
+
+        self.ssa.debug("assert that the tag matches the variant");
+        self.prove("variant", loc, vec![matches], |a,model| match a {
+            false => {
+                let mut estack = vec![
+                    (loc.clone(), format!("{} is only valid while the tag is {}::{}", field, name.0.last().unwrap(), variant.name)),
+                ];
+                estack.extend(self.demonstrate(model.as_ref().unwrap(), matches, 0));
+                Err(self.trace(format!("possible access to a field of another variant"), estack))
+            }
+            true => {
+                Ok(())
+            }
+        })
+    }
+
+    // division by zero, INT_MIN / -1 and shifting by the bit width or more are undefined behaviour in C
+    fn check_defined(&mut self, lhs: Symbol, rhs: Symbol, op: &ast::InfixOperator, loc: &ast::Location) -> Result<(), Error> {
+        if !self.memory[lhs].typed.ptr.is_empty() || !self.memory[rhs].typed.ptr.is_empty() {
+            return Ok(());
+        }
+        let (size, signed) = match self.memory[rhs].t {
+            smt::Type::Signed(size)     => (size, true),
+            smt::Type::Unsigned(size)   => (size, false),
+            _ => return Ok(()),
+        };
+        let typed = self.memory[rhs].typed.clone();
+
+        match op {
+            ast::InfixOperator::Divide | ast::InfixOperator::Modulo => {
+                self.ssa.debug("begin division");
+                let zero = self.literal(loc, Value::Integer(0), typed.clone())?;
+                let nonzero = self.compare(rhs, zero, ast::InfixOperator::Nequals, loc)?;
+                self.ssa.debug("assert that the divisor is not zero");
+                self.prove("division", loc, vec![nonzero], |a,model| match a {
+                    false => {
+                        let mut estack = Vec::new();
+                        estack.extend(self.demonstrate(model.as_ref().unwrap(), nonzero, 0));
+                        Err(self.trace(format!("possible division by zero"), estack))
+                    }
+                    true => {
+                        Ok(())
+                    }
+                })?;
+
+                if signed {
+                    let min       = self.literal(loc, Value::Integer(1 << (size - 1)), typed.clone())?;
+                    let minus_one = self.literal(loc, Value::Integer(u64::max_value() >> (64 - size)), typed)?;
+                    let not_min       = self.compare(lhs, min, ast::InfixOperator::Nequals, loc)?;
+                    let not_minus_one = self.compare(rhs, minus_one, ast::InfixOperator::Nequals, loc)?;
+                    let ok = self.compare(not_min.0, not_minus_one.0, ast::InfixOperator::Boolor, loc)?;
+                    self.ssa.debug("assert that the division does not overflow");
+                    self.prove("division", loc, vec![ok], |a,model| match a {
+                        false => {
+                            let mut estack = Vec::new();
+                            estack.extend(self.demonstrate(model.as_ref().unwrap(), ok, 0));
+                            Err(self.trace(format!("possible signed integer overflow in division"), vec![
+                                (loc.clone(), format!("the smallest {} divided by -1 is not representable", self.memory[lhs].typed))
+                            ].into_iter().chain(estack).collect()))
+                        }
+                        true => {
+                            Ok(())
+                        }
+                    })?;
+                }
+            }
+            ast::InfixOperator::Shiftleft | ast::InfixOperator::Shiftright => {
+                self.ssa.debug("begin shift");
+                let width = self.literal(loc, Value::Integer(size as u64), typed.clone())?;
+                let mut in_range = self.compare(rhs, width, ast::InfixOperator::Lessthan, loc)?;
+                if signed {
+                    let zero = self.literal(loc, Value::Integer(0), typed)?;
+                    let positive = self.compare(rhs, zero, ast::InfixOperator::Moreeq, loc)?;
+                    in_range = self.compare(positive.0, in_range.0, ast::InfixOperator::Booland, loc)?;
+                }
+                self.ssa.debug("assert that the shift is smaller than the bit width");
+                self.prove("shift", loc, vec![in_range], |a,model| match a {
+                    false => {
+                        let mut estack = Vec::new();
+                        estack.extend(self.demonstrate(model.as_ref().unwrap(), in_range, 0));
+                        Err(self.trace(format!("possible shift by a negative amount or by {} bits or more", size), estack))
+                    }
+                    true => {
+                        Ok(())
+                    }
+                })?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // ++ and -- of the value in sym
+    fn check_step_overflow(&mut self, sym: Symbol, increment: bool, loc: &ast::Location) -> Result<(), Error> {
+        if !self.checks_overflow(sym) {
+            return Ok(());
+        }
+        let one = self.literal(loc, Value::Integer(1), self.memory[sym].typed.clone())?;
+        self.check_overflow(
+            (sym, self.memory[sym].temporal),
+            (one, self.memory[one].temporal),
+            if increment { ast::InfixOperator::Add } else { ast::InfixOperator::Subtract },
+            self.memory[sym].t.clone(),
+            loc,
+        )
+    }
+
+    // every scalar in the storage of sym becomes zero
+    fn zero(&mut self, sym: Symbol, loc: &ast::Location) -> Result<(), Error> {
+        if let Some(fields) = self.struct_fields(sym) {
+            for field in fields {
+                let member = self.member_access(sym, &field.name, loc)?;
+                self.zero(member, loc)?;
+            }
+            return Ok(());
+        }
+        match self.memory[sym].value.clone() {
+            Value::Array{array, ..} => {
+                for (_, member) in array {
+                    self.zero(member, loc)?;
+                }
+            }
+            _ => match self.memory[sym].t {
+                smt::Type::Unsigned(_) | smt::Type::Signed(_) | smt::Type::Bool => {
+                    let zero = self.literal(loc, Value::Integer(0), self.memory[sym].typed.clone())?;
+                    self.copy(sym, zero, loc)?;
+                }
+                _ => (),
+            }
+        }
+        Ok(())
     }
 
-    fn cur(&mut self) -> &mut Scope {
-        self.stack.last_mut().unwrap()
+    // an array member is always at the same place in the struct, only what is in it changes
+    fn array_members(&self, sym: Symbol) -> Vec<Symbol> {
+        let members = match &self.memory[sym].value {
+            Value::Struct{members} => members,
+            _ => return Vec::new(),
+        };
+        self.struct_fields(sym).unwrap_or_default().iter()
+            .filter(|field|field.array.is_some())
+            .filter_map(|field|members.get(&field.name).cloned())
+            .collect()
     }
 
+    fn struct_fields(&self, sym: Symbol) -> Option<Vec<ast::Field>> {
+        if !self.memory[sym].typed.ptr.is_empty() {
+            return None;
+        }
+        match &self.memory[sym].typed.t {
+            ast::Type::Other(name) => match self.defs.get(name) {
+                Some(ast::Def::Struct{fields, ..}) => Some(fields.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 
     fn literal(&mut self, loc: &ast::Location, value: Value, t: ast::Typed) -> Result<Symbol, Error> {
         self.ssa.debug_loc(loc);
@@ -3199,6 +5442,127 @@ impl Symbolic {
         }
     }
 
+    fn format_value(t: &smt::Type, v: u64) -> String {
+        match t {
+            smt::Type::Bool         => if v > 0 { "true".to_string() } else { "false".to_string() },
+            smt::Type::Float(8, 24) => format!("{:?}", f32::from_bits(v as u32)),
+            smt::Type::Float(..)    => format!("{:?}", f64::from_bits(v)),
+            _                       => format!("0x{:x}", v),
+        }
+    }
+
+    // assert and record the obligation for the report.
+    // if the solver gives up, that is reported as a timeout instead of calling with
+    fn prove<F>(&self, kind: &str, loc: &ast::Location, lhs: Vec<TemporalSymbol>, with: F) -> Result<(), Error>
+        where F : Fn(bool, Option<smt::ModelRef>) -> Result<(), Error>,
+    {
+        let start   = Instant::now();
+        let outcome = Cell::new((report::Outcome::Proven, Duration::default()));
+        let r = self.ssa.assert(lhs, |a, model| {
+            if !a && self.ssa.unknown() {
+                outcome.set((report::Outcome::Timeout, start.elapsed()));
+                return Err(self.trace(format!("unknown: solver timeout after {}ms", self.ssa.timeout()), vec![
+                    (loc.clone(), format!("this {} could neither be proven nor disproven. try a larger @timeout= on the function",
+                        kind)),
+                ]));
+            }
+            outcome.set((if a { report::Outcome::Proven } else { report::Outcome::Failed }, start.elapsed()));
+            with(a, model)
+        });
+        let (outcome, time) = outcome.get();
+        self.obligations.borrow_mut().push(report::Obligation::new(kind, loc, outcome, time));
+        r
+    }
+
+    // several obligations in one query. only recorded if all of them hold,
+    // otherwise the caller proves them one by one
+    fn prove_all(&self, obligations: &[(&str, ast::Location)], lhs: Vec<TemporalSymbol>) -> bool {
+        let start = Instant::now();
+        let ok = self.ssa.assert(lhs, |a,_|a);
+        if ok {
+            let time = start.elapsed() / obligations.len().max(1) as u32;
+            for (kind, loc) in obligations {
+                self.obligations.borrow_mut().push(report::Obligation::new(kind, loc, report::Outcome::Proven, time));
+            }
+        }
+        ok
+    }
+
+    // the value of a function argument when it was passed in
+    fn counterexample_input(&self, model: &smt::ModelRef, sym: Symbol) -> Input {
+        if self.memory[sym].typed.ptr.len() > 0 {
+            let safe = self.builtin.get("safe").expect("ICE: safe theory not built in");
+            if let Some(0) = self.ssa.extract_invocation(model, *safe, vec![(sym, 0)]) {
+                return Input::Null;
+            }
+            let len = self.builtin.get("len").expect("ICE: len theory not built in");
+            let len = match self.ssa.extract_invocation(model, *len, vec![(sym, 0)]) {
+                Some(len) if len > 0 && len <= 4096 => len,
+                _ => 1,
+            };
+            let first = match &self.memory[sym].value {
+                Value::Address(to) => self.counterexample_input(model, *to),
+                _ => Input::Unknown,
+            };
+            return Input::Buffer{len, first: Box::new(first)};
+        }
+
+        match &self.memory[sym].value {
+            Value::Struct{members} => {
+                let mut members : Vec<(String, Input)> = members.iter()
+                    .map(|(name, member)|(name.clone(), self.counterexample_input(model, *member)))
+                    .collect();
+                members.sort_by(|a,b|a.0.cmp(&b.0));
+                Input::Struct(members)
+            }
+            Value::Array{..} => Input::Unknown,
+            _ => match self.ssa.extract(model, (sym, 0)) {
+                Some(v) => Input::Value(Self::c_literal(&self.memory[sym].t, v)),
+                None    => Input::Unknown,
+            }
+        }
+    }
+
+    fn c_literal(t: &smt::Type, v: u64) -> String {
+        match t {
+            smt::Type::Bool => if v > 0 { "true".to_string() } else { "false".to_string() },
+            smt::Type::Float(8, 24) => {
+                let f = f32::from_bits(v as u32);
+                if f.is_nan() {
+                    "__builtin_nanf(\"\")".to_string()
+                } else if f.is_infinite() {
+                    format!("{}__builtin_inff()", if f < 0.0 { "-" } else { "" })
+                } else {
+                    format!("{:e}f", f)
+                }
+            }
+            smt::Type::Float(..) => {
+                let f = f64::from_bits(v);
+                if f.is_nan() {
+                    "__builtin_nan(\"\")".to_string()
+                } else if f.is_infinite() {
+                    format!("{}__builtin_inf()", if f < 0.0 { "-" } else { "" })
+                } else {
+                    format!("{:e}", f)
+                }
+            }
+            smt::Type::Signed(bits) => {
+                let v = if *bits < 64 {
+                    ((v << (64 - bits)) as i64) >> (64 - bits)
+                } else {
+                    v as i64
+                };
+                if v == i64::min_value() {
+                    "(-9223372036854775807 - 1)".to_string()
+                } else {
+                    format!("{}", v)
+                }
+            }
+            smt::Type::Unsigned(_) => format!("0x{:x}", v),
+            smt::Type::Array(_) => unreachable!(),
+        }
+    }
+
     fn demonstrate(&self, model: &smt::ModelRef, sym: TemporalSymbol, depth: usize) -> Vec<(ast::Location, String)> {
         let mut estack  = Vec::new();
 
@@ -3216,18 +5580,18 @@ impl Symbolic {
         match &self.memory[sym.0].value {
             Value::InfixOp{lhs, rhs, op} => {
                 let overflow = self.ssa.infix_op_will_wrap(
+                    model,
                     *lhs,
                     *rhs,
                     op.clone(),
-                    self.memory[sym.0].t.clone(),
+                    self.memory[lhs.0].t.clone(),
                 );
 
-                if let Some(v) = self.ssa.extract(model, sym) {
-                    let v = if self.memory[sym.0].t == smt::Type::Bool {
-                        if v > 0 { "true".to_string() } else { "false".to_string() }
-                    } else {
-                        format!("0x{:x}", v)
-                    };
+                if overflow && self.memory[sym.0].t == smt::Type::Bool {
+                    // an overflow obligation. its value only says that it does not hold
+                    estack.push((valloc, format!("for OVERFLOW of {}", self.memory[sym.0].name)));
+                } else if let Some(v) = self.ssa.extract(model, sym) {
+                    let v = Self::format_value(&self.memory[sym.0].t, v);
 
                     if overflow {
                         estack.push((valloc, format!("for OVERFLOW of {} |{}| = {}", self.memory[sym.0].name, sym.1, v)));
@@ -3246,11 +5610,7 @@ impl Symbolic {
             //Value::Integer(_) => return estack,
             _ => {
                 if let Some(v) = self.ssa.extract(model, sym) {
-                    let v = if self.memory[sym.0].t == smt::Type::Bool {
-                        if v > 0 { "true".into() } else { "false".into() }
-                    } else {
-                        format!("0x{:x}", v)
-                    };
+                    let v = Self::format_value(&self.memory[sym.0].t, v);
                     estack.push((valloc, format!("for {} |{}| = {}", self.memory[sym.0].name, sym.1, v)));
                 } else {
                     estack.push((valloc, format!("for {} |{}| = ??", self.memory[sym.0].temporal, sym.1)));
@@ -3259,6 +5619,14 @@ impl Symbolic {
         };
 
 
+        // lemmas are not emitted, so there is nothing to run a counterexample against
+        if depth == 0 && COUNTEREXAMPLES.load(Ordering::Relaxed) && self.counterexample.borrow().is_none() && !self.current_function_lemma {
+            let inputs = self.current_function_args.iter()
+                .map(|(sym, arg)|(arg.clone(), self.counterexample_input(model, *sym)))
+                .collect();
+            *self.counterexample.borrow_mut() = Some(inputs);
+        }
+
         if depth == 0 {
             for stack in self.stack.iter().rev() {
                 for (sym, loc, onlyiftrue) in &stack.trace {
@@ -3305,9 +5673,562 @@ impl Symbolic {
 }
 
 
-pub fn execute(module: &mut flatten::Module) -> bool {
+// true if the loop body contains a break that leaves this loop
+fn loop_breaks(body: &Vec<Box<ast::Statement>>) -> bool {
+    for stm in body {
+        match stm.as_ref() {
+            ast::Statement::Break{..} => return true,
+            ast::Statement::Block(body) | ast::Statement::Unsafe(body) if loop_breaks(&body.statements) => {
+                return true;
+            }
+            ast::Statement::If{branches} => {
+                for (_, _, body) in branches {
+                    if loop_breaks(&body.statements) {
+                        return true;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    false
+}
+
+enum Visit<'a> {
+    Expr(&'a ast::Expression),
+    Typed(&'a ast::Typed),
+}
+
+// every expression and every written type in a block, including loop clauses and closure bodies
+fn visit_block<'a>(block: &'a ast::Block, f: &mut dyn FnMut(Visit<'a>)) {
+    for stm in &block.statements {
+        visit_statement(stm, f);
+    }
+}
+
+fn visit_statement<'a>(stm: &'a ast::Statement, f: &mut dyn FnMut(Visit<'a>)) {
+    match stm {
+        ast::Statement::Mark{lhs, ..} => visit_expr(lhs, f),
+        ast::Statement::Label{..}
+            | ast::Statement::Continue{..}
+            | ast::Statement::Break{..}
+            | ast::Statement::CBlock{..} => (),
+        ast::Statement::Assign{lhs, rhs, ..} => {
+            visit_expr(lhs, f);
+            visit_expr(rhs, f);
+        }
+        ast::Statement::Expr{expr, ..} => visit_expr(expr, f),
+        ast::Statement::Switch{expr, cases, default, ..} => {
+            visit_expr(expr, f);
+            for (conds, block) in cases {
+                for cond in conds {
+                    visit_expr(cond, f);
+                }
+                visit_block(block, f);
+            }
+            if let Some(block) = default {
+                visit_block(block, f);
+            }
+        }
+        ast::Statement::Return{expr, ..} => {
+            if let Some(expr) = expr {
+                visit_expr(expr, f);
+            }
+        }
+        ast::Statement::Ghost{stm, ..} | ast::Statement::Defer{stm, ..} => visit_statement(stm, f),
+        ast::Statement::Var{typed, array, assign, ..} => {
+            f(Visit::Typed(typed));
+            if let Some(Some(expr)) = array {
+                visit_expr(expr, f);
+            }
+            if let Some(expr) = assign {
+                visit_expr(expr, f);
+            }
+        }
+        ast::Statement::While{expr, invariants, decreases, body} => {
+            visit_expr(expr, f);
+            for expr in invariants.iter().chain(decreases.iter()) {
+                visit_expr(expr, f);
+            }
+            visit_block(body, f);
+        }
+        ast::Statement::For{e1, e2, e3, invariants, decreases, body} => {
+            for stm in e1.iter().chain(e3.iter()) {
+                visit_statement(stm, f);
+            }
+            for expr in e2.iter().chain(invariants.iter()).chain(decreases.iter()) {
+                visit_expr(expr, f);
+            }
+            visit_block(body, f);
+        }
+        ast::Statement::If{branches} => {
+            for (_, expr, block) in branches {
+                if let Some(expr) = expr {
+                    visit_expr(expr, f);
+                }
+                visit_block(block, f);
+            }
+        }
+        ast::Statement::ForIn{typed, expr, invariants, body, ..} => {
+            f(Visit::Typed(typed));
+            visit_expr(expr, f);
+            for expr in invariants {
+                visit_expr(expr, f);
+            }
+            visit_block(body, f);
+        }
+        ast::Statement::Block(block) | ast::Statement::Unsafe(block) => visit_block(block, f),
+    }
+}
+
+fn visit_expr<'a>(expr: &'a ast::Expression, f: &mut dyn FnMut(Visit<'a>)) {
+    f(Visit::Expr(expr));
+    match expr {
+        ast::Expression::Name(_)
+            | ast::Expression::LiteralString{..}
+            | ast::Expression::LiteralChar{..}
+            | ast::Expression::Literal{..} => (),
+        ast::Expression::MemberAccess{lhs, ..} => visit_expr(lhs, f),
+        ast::Expression::ArrayAccess{lhs, rhs, ..} | ast::Expression::Infix{lhs, rhs, ..} => {
+            visit_expr(lhs, f);
+            visit_expr(rhs, f);
+        }
+        ast::Expression::Call{name, args, ..} => {
+            visit_expr(name, f);
+            for arg in args {
+                visit_expr(arg, f);
+            }
+        }
+        ast::Expression::Cast{into, expr, ..} => {
+            f(Visit::Typed(into));
+            visit_expr(expr, f);
+        }
+        ast::Expression::UnaryPost{expr, ..} | ast::Expression::UnaryPre{expr, ..} => visit_expr(expr, f),
+        ast::Expression::StructInit{typed, fields, ..} => {
+            f(Visit::Typed(typed));
+            for (_, expr) in fields {
+                visit_expr(expr, f);
+            }
+        }
+        ast::Expression::ArrayInit{fields, ..} => {
+            for expr in fields {
+                visit_expr(expr, f);
+            }
+        }
+        ast::Expression::Ternary{cond, then, otherwise, ..} => {
+            visit_expr(cond, f);
+            visit_expr(then, f);
+            visit_expr(otherwise, f);
+        }
+        ast::Expression::Closure{args, ret, body, ..} => {
+            for arg in args {
+                f(Visit::Typed(&arg.typed));
+            }
+            if let Some(ret) = ret {
+                f(Visit::Typed(&ret.typed));
+            }
+            visit_block(body, f);
+        }
+        ast::Expression::Quantifier{var, range, body, ..} => {
+            f(Visit::Typed(&var.typed));
+            visit_expr(range, f);
+            visit_expr(body, f);
+        }
+    }
+}
+
+// functions called by name from a function body. calls through function pointers are not known
+fn direct_calls(d: &ast::Local) -> Vec<(Name, ast::Location)> {
+    let mut calls = Vec::new();
+    if let ast::Def::Function{body, ..} = &d.def {
+        visit_block(body, &mut |v| {
+            if let Visit::Expr(ast::Expression::Call{name, loc, ..}) = v {
+                if let ast::Expression::Name(typed) = name.as_ref() {
+                    if let ast::Type::Other(name) = &typed.t {
+                        calls.push((name.clone(), loc.clone()));
+                    }
+                }
+            }
+        });
+    }
+    calls
+}
+
+// a call from the function to another function that eventually calls the function again.
+// the measure of one function says nothing about the other, so this can't be proven to terminate
+fn mutual_recursion(module: &flatten::Module, at: usize) -> Option<ast::Location> {
+    let graph : HashMap<Name, Vec<(Name, ast::Location)>> = module.d.iter()
+        .map(|(d,_,_)|(Name::from(&d.name), direct_calls(d)))
+        .collect();
+    let name = Name::from(&module.d[at].0.name);
+
+    for (callee, loc) in &graph[&name] {
+        if callee == &name {
+            continue;
+        }
+        let mut seen  = HashSet::new();
+        let mut stack = vec![callee.clone()];
+        while let Some(next) = stack.pop() {
+            if next == name {
+                return Some(loc.clone());
+            }
+            if !seen.insert(next.clone()) {
+                continue;
+            }
+            if let Some(calls) = graph.get(&next) {
+                stack.extend(calls.iter().map(|(callee,_)|callee.clone()));
+            }
+        }
+    }
+    None
+}
+
+// the floating point theory is expensive, so it's only enabled if any declaration in the module has a float type
+fn uses_floats(module: &flatten::Module) -> bool {
+    let mut floats = false;
+    let mut visit = |v: Visit| match v {
+        Visit::Typed(typed) => {
+            if let ast::Type::F32 | ast::Type::F64 | ast::Type::FLiteral = typed.t {
+                floats = true;
+            }
+        }
+        Visit::Expr(ast::Expression::Literal{v, ..}) => {
+            // integer literals like -1 also parse as f64, only a fraction or exponent makes a float literal
+            if v.contains(|c| c == '.' || c == 'e' || c == 'E') && parser::parse_u64(v).is_none() && v.parse::<f64>().is_ok() {
+                floats = true;
+            }
+        }
+        Visit::Expr(_) => (),
+    };
+    for (d,_,_) in &module.d {
+        match &d.def {
+            ast::Def::Function{args, ret, body, ..} => {
+                for arg in args {
+                    visit(Visit::Typed(&arg.typed));
+                }
+                if let Some(ret) = ret {
+                    visit(Visit::Typed(&ret.typed));
+                }
+                visit_block(body, &mut visit);
+            }
+            ast::Def::Fntype{args, ret, ..} | ast::Def::Theory{args, ret, ..} => {
+                for arg in args {
+                    visit(Visit::Typed(&arg.typed));
+                }
+                if let Some(ret) = ret {
+                    visit(Visit::Typed(&ret.typed));
+                }
+            }
+            ast::Def::Struct{fields, ..} => {
+                for field in fields {
+                    visit(Visit::Typed(&field.typed));
+                }
+            }
+            ast::Def::Static{typed, expr, ..} | ast::Def::Const{typed, expr} => {
+                visit(Visit::Typed(typed));
+                visit_expr(expr, &mut visit);
+            }
+            ast::Def::Macro{..} | ast::Def::Enum{..} | ast::Def::Testcase{..} | ast::Def::Include{..} => (),
+        }
+    }
+    floats
+}
+
+// same for quantifiers, which also need memory contents modelled as arrays
+fn uses_quantifiers(module: &flatten::Module) -> bool {
+    let mut quantifiers = false;
+    let mut visit = |v: Visit| {
+        if let Visit::Expr(ast::Expression::Quantifier{..}) = v {
+            quantifiers = true;
+        }
+    };
+    for (d,_,_) in &module.d {
+        match &d.def {
+            ast::Def::Function{body, callassert, calleffect, callattests, decreases, modifies, ..} => {
+                for expr in callassert.iter().chain(calleffect).chain(callattests).chain(decreases).chain(modifies) {
+                    visit_expr(expr, &mut visit);
+                }
+                visit_block(body, &mut visit);
+            }
+            ast::Def::Struct{invariants, ..} => {
+                for expr in invariants {
+                    visit_expr(expr, &mut visit);
+                }
+            }
+            ast::Def::Static{expr, ..} | ast::Def::Const{expr, ..} => {
+                visit_expr(expr, &mut visit);
+            }
+            ast::Def::Macro{body, ..} => {
+                visit_block(body, &mut visit);
+            }
+            ast::Def::Fntype{..} | ast::Def::Theory{..} | ast::Def::Enum{..} | ast::Def::Testcase{..} | ast::Def::Include{..} => (),
+        }
+    }
+    quantifiers
+}
+
+// callsite conditions on the builtin theories are reported as their own kind
+fn where_kind(expr: &ast::Expression) -> &'static str {
+    if let ast::Expression::Call{name, ..} = expr {
+        if let ast::Expression::Name(ast::Typed{t: ast::Type::Other(n), ..}) = name.as_ref() {
+            match format!("{}", n).as_str() {
+                "safe"      => return "safe",
+                "nullterm"  => return "nullterm",
+                _ => (),
+            }
+        }
+    }
+    "where"
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FormatArg {
+    // anything promoted to int
+    Int,
+    // l, ll, j, z and t
+    Long,
+    Float,
+    String{precision: bool},
+    Pointer,
+}
+
+impl std::fmt::Display for FormatArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FormatArg::Int      => write!(f, "an integer of at most 32 bits"),
+            FormatArg::Long     => write!(f, "a 64 bit integer"),
+            FormatArg::Float    => write!(f, "a floating point"),
+            FormatArg::String{..} => write!(f, "a char pointer"),
+            FormatArg::Pointer  => write!(f, "a pointer"),
+        }
+    }
+}
+
+// the c library functions taking a printf style format string, and which argument it is
+fn c_format_arg(name: &str) -> Option<usize> {
+    match name {
+        "::ext::<stdio.h>::printf"   => Some(0),
+        "::ext::<stdio.h>::fprintf"  => Some(1),
+        "::ext::<stdio.h>::dprintf"  => Some(1),
+        "::ext::<stdio.h>::sprintf"  => Some(1),
+        "::ext::<stdio.h>::snprintf" => Some(2),
+        _ => None,
+    }
+}
+
+// the arguments consumed by each conversion of a printf style format string
+fn format_conversions(fmt: &[u8]) -> Result<Vec<(String, FormatArg)>, String> {
+    let mut r = Vec::new();
+    let mut i = 0;
+    while i < fmt.len() {
+        if fmt[i] != b'%' {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        while i < fmt.len() && b"-+ #0'".contains(&fmt[i]) {
+            i += 1;
+        }
+        // width and precision may be passed as extra int arguments
+        let mut has_precision = false;
+        for precision in &[false, true] {
+            if *precision {
+                if i >= fmt.len() || fmt[i] != b'.' {
+                    break;
+                }
+                has_precision = true;
+                i += 1;
+            }
+            if i < fmt.len() && fmt[i] == b'*' {
+                r.push(("*".to_string(), FormatArg::Int));
+                i += 1;
+            } else {
+                while i < fmt.len() && fmt[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+        }
+        let modifier = i;
+        while i < fmt.len() && b"hlLqjzt".contains(&fmt[i]) {
+            i += 1;
+        }
+        let modifier = &fmt[modifier..i];
+        let conversion = match fmt.get(i) {
+            Some(c) => *c,
+            None => return Err("incomplete conversion at the end of the format string".to_string()),
+        };
+        i += 1;
+        let spec = String::from_utf8_lossy(&fmt[start..i]).to_string();
+        let expected = match conversion {
+            b'%' if i - start == 2 => continue,
+            b'c' => FormatArg::Int,
+            b'd' | b'i' | b'o' | b'u' | b'x' | b'X' => match modifier {
+                b"" | b"h" | b"hh" => FormatArg::Int,
+                b"l" | b"ll" | b"q" | b"j" | b"z" | b"t" => FormatArg::Long,
+                _ => return Err(format!("{} is not a valid integer length", spec)),
+            },
+            b'e' | b'E' | b'f' | b'F' | b'g' | b'G' | b'a' | b'A' => FormatArg::Float,
+            b's' => FormatArg::String{precision: has_precision},
+            b'p' => FormatArg::Pointer,
+            b'n' => return Err(format!("{} writes through its argument and is not supported", spec)),
+            _ => return Err(format!("{} is not a valid conversion", spec)),
+        };
+        r.push((spec, expected));
+    }
+    Ok(r)
+}
+
+/// proofs of functions that did not change are not repeated.
+/// a function is identified by its own source, the declarations of everything else in the module
+/// and the settings it was built with. the bodies of other functions do not matter for its proof.
+///
+/// a cached function is not executed at all. symbolic execution transforms the body for C
+/// (static(), casts of tail pointers, self calls, ...), so the C the emitter produced from the
+/// transformed body is kept with the proof, and emitted again instead of the body.
+pub struct ProofCache {
+    dir:        String,
+    salt:       String,
+    // proven functions waiting for their emitted body, by function name
+    proven:     Mutex<HashMap<String, String>>,
+}
+
+impl ProofCache {
+    pub fn new(stage: &make::Stage, variant: &str, features: &HashMap<String, bool>) -> Self {
+        let dir = format!("./target/{}/proofs/", stage);
+        std::fs::create_dir_all(&dir).expect("create target dir");
+
+        let mut features : Vec<String> = features.iter().map(|(n,e)|format!("{}={}", n, e)).collect();
+        features.sort();
+
+        // a different zz may prove differently
+        let zz = std::env::current_exe().ok()
+            .and_then(|exe|exe.metadata().ok())
+            .and_then(|meta|meta.modified().ok());
+
+        ProofCache {
+            dir,
+            salt: format!("{} {:?} {} {} {}\n", env!("CARGO_PKG_VERSION"), zz, variant, features.join(","),
+                UNSIGNED_OVERFLOW.load(Ordering::Relaxed)),
+            proven: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn path(&self, module: &flatten::Module, at: usize) -> String {
+        let mut others : Vec<String> = module.d.iter().enumerate()
+            .filter(|(i,_)| *i != at)
+            .map(|(_,(d,_,_))| declaration_source(d))
+            .collect();
+        others.sort();
+
+        let own = &module.d[at].0;
+        let mut hints : Vec<String> = match &own.def {
+            ast::Def::Function{hints, ..} => hints.iter().map(|(k,v)|format!("{}={}", k, v)).collect(),
+            _ => Vec::new(),
+        };
+        hints.sort();
+
+        let mut content = self.salt.clone();
+        content.push_str(&declaration_source(own));
+        content.push_str(own.loc.span.as_str());
+        // the emitted body refers to lines in the source
+        content.push_str(&format!("{}:{}", own.loc.file, own.loc.line()));
+        content.push_str(&hints.join(","));
+        for other in others {
+            content.push_str(&other);
+        }
+
+        format!("{}{:032x}", self.dir, metro::hash128(content.as_bytes()))
+    }
+
+    fn lookup(&self, proof: &str) -> Option<Cached> {
+        let content = std::fs::read_to_string(proof).ok()?;
+        match content.split_once('\n') {
+            Some((_, body)) if !body.is_empty() => Some(Cached::Emitted(body.to_string())),
+            _ => Some(Cached::Proven),
+        }
+    }
+
+    fn record(&self, proof: &str, name: &str) {
+        let _ = std::fs::write(proof, name);
+        self.proven.lock().unwrap().insert(name.to_string(), proof.to_string());
+    }
+
+    /// keep the C emitted for the bodies of functions proven in this build
+    pub fn store(&self, bodies: &HashMap<String, String>) {
+        let mut proven = self.proven.lock().unwrap();
+        for (name, body) in bodies {
+            if let Some(proof) = proven.remove(name) {
+                let _ = std::fs::write(&proof, format!("{}\n{}", name, body));
+            }
+        }
+    }
+}
+
+enum Cached {
+    // proven, but never emitted. executed again for the transformation, trusting all asserts
+    Proven,
+    // proven and emitted. the body is replaced with the C emitted from it
+    Emitted(String),
+}
+
+// replace the body of a cached function with the C that was emitted for it
+fn emitted_body(module: &mut flatten::Module, at: usize, c: String) {
+    let loc = module.d[at].0.loc.clone();
+    if let ast::Def::Function{body, ..} = &mut module.d[at].0.def {
+        *body = ast::Block{
+            end:        body.end.clone(),
+            statements: vec![Box::new(ast::Statement::CBlock{loc, lit: c})],
+            expanded:   true,
+        };
+    }
+}
+
+// what callers can see of a definition.
+// for functions that's the signature and contracts, for everything else the whole definition
+fn declaration_source(d: &ast::Local) -> String {
+    match &d.def {
+        ast::Def::Function{args, ret, vararg, callassert, calleffect, callattests, decreases, modifies, ..} => {
+            let mut s = format!("\nfn {}(", d.name);
+            for arg in args {
+                s.push_str(&format!("{} {} {},", arg.typed, arg.name, arg.loc.span.as_str()));
+            }
+            if *vararg {
+                s.push_str("...");
+            }
+            s.push(')');
+            if let Some(ret) = ret {
+                s.push_str(&format!(" -> {}", ret.typed));
+            }
+            for (what, exprs) in &[("where", callassert), ("model", calleffect), ("attest", callattests), ("modifies", modifies)] {
+                for expr in exprs.iter() {
+                    s.push_str(&format!(" {} {}", what, expr.loc().span.as_str()));
+                }
+            }
+            if let Some(decreases) = decreases {
+                s.push_str(&format!(" decreases {}", decreases.loc().span.as_str()));
+            }
+            s
+        }
+        _ => format!("\n{} {}", d.name, d.loc.span.as_str()),
+    }
+}
+
+/// when counterexamples are requested, failed functions are executed again without proving,
+/// so that the module can still be emitted for the counterexample harness.
+pub fn execute(
+    module:             &mut flatten::Module,
+    cache:              &ProofCache,
+    counterexamples:    &mut Vec<Counterexample>,
+    reports:            &mut Vec<report::Function>,
+) -> bool {
     use rayon::prelude::*;
 
+    let floats = uses_floats(module);
+    let quantifiers = uses_quantifiers(module);
+    // the report must contain every obligation, so nothing is taken from the cache
+    let reporting = report::ENABLED.load(Ordering::Relaxed);
+
     let mut defs        = Vec::new();
     let mut function_at = Vec::new();
     for (i, (d,_,defined_here)) in module.d.clone().into_iter().enumerate() {
@@ -3323,35 +6244,162 @@ pub fn execute(module: &mut flatten::Module) -> bool {
 
     // execute one in serial on the borrowed module to get modifications to globals
     if let Some((at, name, _, hints)) = function_at.pop() {
-        let mut sym = Symbolic::new(&Name::from(&name), &hints);
-        if let Err(e) = sym.execute_module(module, at) {
+        let proof = cache.path(module, at);
+        let cached = if reporting { None } else { cache.lookup(&proof) };
+        let original = if COUNTEREXAMPLES.load(Ordering::Relaxed) { Some(module.clone()) } else { None };
+        let mut sym = Symbolic::new(&Name::from(&name), &hints, floats, quantifiers);
+        let start = Instant::now();
+        let r = match cached {
+            Some(Cached::Emitted(c)) => {
+                // the globals still need to be transformed
+                let r = sym.execute_declarations(module);
+                emitted_body(module, at, c);
+                r
+            }
+            Some(Cached::Proven) => {
+                sym.ssa.trust();
+                sym.execute_module(module, at)
+            }
+            None => sym.execute_module(module, at),
+        };
+        reports.push(function_report(module, at, &name, start.elapsed(), &sym));
+        if let Err(e) = r {
             parser::emit_error(e.message.clone(), &e.details);
-            return false;
+            *module = match original {
+                Some(original) => original,
+                None => return false,
+            };
+            match counterexample(module, at, &name, &hints, floats, sym, e) {
+                Some(cx) => counterexamples.push(cx),
+                None => return false,
+            }
+        } else {
+            cache.record(&proof, &name);
         }
     }
 
     let repl = function_at.into_par_iter().map(|(at, name, mut module, hints)|{
-        let mut sym = Symbolic::new(&Name::from(&name), &hints);
-        match sym.execute_module(&mut module, at) {
+        let proof = cache.path(&module, at);
+        let cached = if reporting { None } else { cache.lookup(&proof) };
+        let mut sym = Symbolic::new(&Name::from(&name), &hints, floats, quantifiers);
+        match cached {
+            Some(Cached::Emitted(c)) => {
+                let report = cached_report(&module, at, &name);
+                emitted_body(&mut module, at, c);
+                return (Some((at, module.d.remove(at).0)), None, report);
+            }
+            Some(Cached::Proven) => sym.ssa.trust(),
+            None => (),
+        }
+        let original = if COUNTEREXAMPLES.load(Ordering::Relaxed) { Some(module.clone()) } else { None };
+        let start = Instant::now();
+        let r = sym.execute_module(&mut module, at);
+        let report = function_report(&module, at, &name, start.elapsed(), &sym);
+        match r {
             Err(e) => {
                 parser::emit_error(e.message.clone(), &e.details);
-                None
+                let mut module = match original {
+                    Some(original) => original,
+                    None => return (None, None, report),
+                };
+                match counterexample(&mut module, at, &name, &hints, floats, sym, e) {
+                    Some(cx) => (Some((at, module.d.remove(at).0)), Some(cx), report),
+                    None => (None, None, report),
+                }
             }
             Ok(_)  => {
-                Some((at, module.d.remove(at).0))
+                cache.record(&proof, &name);
+                (Some((at, module.d.remove(at).0)), None, report)
             }
         }
-    }).collect::<Vec<Option<(usize, ast::Local)>>>();
-
-    for r in repl {
+    }).collect::<Vec<(Option<(usize, ast::Local)>, Option<Counterexample>, report::Function)>>();
+
+    let mut ok = counterexamples.is_empty();
+    for (r, cx, report) in repl {
+        reports.push(report);
+        if let Some(cx) = cx {
+            counterexamples.push(cx);
+            ok = false;
+        }
         if let Some((at,l)) = r {
             module.d[at].0 = l;
         } else {
-            return false;
+            ok = false;
         }
     }
 
-    true
+    ok
+}
+
+fn function_report(module: &flatten::Module, at: usize, name: &str, time: Duration, sym: &Symbolic) -> report::Function {
+    let loc = &module.d[at].0.loc;
+    report::Function {
+        module:         module.name.human_name(),
+        function:       name.to_string(),
+        file:           loc.file.clone(),
+        line:           loc.line(),
+        time_ms:        time.as_secs_f64() * 1000.0,
+        obligations:    std::mem::replace(&mut *sym.obligations.borrow_mut(), Vec::new()),
+    }
+}
+
+// a function taken from the proof cache has nothing to report
+fn cached_report(module: &flatten::Module, at: usize, name: &str) -> report::Function {
+    let loc = &module.d[at].0.loc;
+    report::Function {
+        module:         module.name.human_name(),
+        function:       name.to_string(),
+        file:           loc.file.clone(),
+        line:           loc.line(),
+        time_ms:        0.0,
+        obligations:    Vec::new(),
+    }
 }
 
+// take the inputs recorded by a failed execution, and execute the function again trusting all proofs,
+// which transforms it as if it passed
+fn counterexample(
+    module: &mut flatten::Module,
+    at:     usize,
+    name:   &str,
+    hints:  &HashMap<String, String>,
+    floats: bool,
+    failed: Symbolic,
+    e:      Error,
+) -> Option<Counterexample> {
+    let args = failed.counterexample.into_inner()?;
+
+    let mut sym = Symbolic::new(&Name::from(name), hints, floats, uses_quantifiers(module));
+    sym.ssa.trust();
+    if let Err(e) = sym.execute_module(module, at) {
+        parser::emit_error(e.message.clone(), &e.details);
+        return None;
+    }
+
+    Some(Counterexample{
+        function:   Name::from(name),
+        message:    e.message,
+        loc:        e.details.first().map(|(loc,_)|loc.clone()).unwrap_or_else(||module.d[at].0.loc.clone()),
+        args,
+    })
+}
 
+fn for_in_loc(stm: &ast::Statement) -> Option<&ast::Location> {
+    match stm {
+        ast::Statement::ForIn{loc, ..} => Some(loc),
+        ast::Statement::Block(block) | ast::Statement::Unsafe(block) => {
+            block.statements.iter().find_map(|stm|for_in_loc(stm))
+        }
+        ast::Statement::If{branches} => {
+            branches.iter().flat_map(|(_,_,block)|block.statements.iter()).find_map(|stm|for_in_loc(stm))
+        }
+        ast::Statement::Switch{cases, default, ..} => {
+            cases.iter().map(|(_, block)|block).chain(default.iter())
+                .flat_map(|block|block.statements.iter()).find_map(|stm|for_in_loc(stm))
+        }
+        ast::Statement::For{body, ..} | ast::Statement::While{body, ..} => {
+            body.statements.iter().find_map(|stm|for_in_loc(stm))
+        }
+        _ => None,
+    }
+}
@@ -1,6 +1,9 @@
+use std::ffi::{CStr, CString};
 use z3_sys::*;
+use crate::z3::ast::{self, Ast};
 use crate::z3::Config;
 use crate::z3::Context;
+use crate::z3::FuncDecl;
 use crate::z3::Z3_MUTEX;
 
 impl Context {
@@ -15,6 +18,58 @@ impl Context {
         }
     }
 
+    /// Parse the assertions in a sequence of SMT-LIB2 commands.
+    ///
+    /// Symbols that are not declared in the text itself resolve to `decls`.
+    /// Declarations made by the text are gone once it is parsed.
+    pub fn parse_smtlib2<'ctx>(&'ctx self, commands: &str, decls: &[&FuncDecl<'ctx>]) -> Result<Vec<ast::Bool<'ctx>>, String> {
+        let commands = CString::new(commands).map_err(|e| e.to_string())?;
+        let names: Vec<Z3_symbol> = decls.iter()
+            .map(|d| unsafe { Z3_get_decl_name(self.z3_ctx, d.z3_func_decl) })
+            .collect();
+        let decls: Vec<Z3_func_decl> = decls.iter().map(|d| d.z3_func_decl).collect();
+        unsafe {
+            // report errors through the error code instead of the default handler, which exits
+            Z3_set_error_handler(self.z3_ctx, None);
+            // z3-sys declares the signature from before z3 4.8, which returned the conjunction as a single ast
+            let v = Z3_parse_smtlib2_string(
+                self.z3_ctx,
+                commands.as_ptr(),
+                0,
+                std::ptr::null(),
+                std::ptr::null(),
+                decls.len() as ::std::os::raw::c_uint,
+                names.as_ptr(),
+                decls.as_ptr(),
+            ) as Z3_ast_vector;
+            let code = Z3_get_error_code(self.z3_ctx);
+            if code != ErrorCode::OK || v.is_null() {
+                let msg = CStr::from_ptr(Z3_get_error_msg(self.z3_ctx, code)).to_string_lossy().into_owned();
+                return Err(msg);
+            }
+            Z3_ast_vector_inc_ref(self.z3_ctx, v);
+            let r = (0..Z3_ast_vector_size(self.z3_ctx, v))
+                .map(|i| ast::Bool::new(self, Z3_ast_vector_get(self.z3_ctx, v, i)))
+                .collect();
+            Z3_ast_vector_dec_ref(self.z3_ctx, v);
+            Ok(r)
+        }
+    }
+
+    /// Parse a single SMT-LIB2 term, see [`parse_smtlib2`](#method.parse_smtlib2).
+    pub fn parse_smtlib2_term<'ctx>(&'ctx self, term: &str, decls: &[&FuncDecl<'ctx>]) -> Result<ast::Dynamic<'ctx>, String> {
+        // the parser only returns assertions, so the term goes in as one side of an equality
+        let mut v = self.parse_smtlib2(&format!("(assert (= {} {}))", term, term), decls)?;
+        let eq = match v.pop() {
+            Some(eq) => eq,
+            None => return Err(format!("cannot parse {}", term)),
+        };
+        unsafe {
+            let app = Z3_to_app(self.z3_ctx, eq.get_z3_ast());
+            Ok(ast::Dynamic::new(self, Z3_get_app_arg(self.z3_ctx, app, 0)))
+        }
+    }
+
     /// Interrupt a solver performing a satisfiability test, a tactic processing a goal, or simplify functions.
     ///
     /// This method can be invoked from a thread different from the one executing the
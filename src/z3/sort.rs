@@ -49,6 +49,14 @@ impl<'ctx> Sort<'ctx> {
         Sort::new(ctx, unsafe { Z3_mk_set_sort(ctx.z3_ctx, elt.z3_sort) })
     }
 
+    /// IEEE-754 floating point with `ebits` exponent and `sbits` significand bits,
+    /// as in `(_ FloatingPoint ebits sbits)`
+    pub fn float(ctx: &Context, ebits: u32, sbits: u32) -> Sort<'_> {
+        Sort::new(ctx, unsafe {
+            Z3_mk_fpa_sort(ctx.z3_ctx, ebits as ::std::os::raw::c_uint, sbits as ::std::os::raw::c_uint)
+        })
+    }
+
     /// Create an enumeration sort.
     ///
     /// Creates a Z3 enumeration sort with the given `name`.
@@ -32,6 +32,18 @@ do
 done
 
 
+# functions with @solver = native only use the z3 linked into zz
+cargo build --release --features native-z3
+cd $THIS/mustpass/solver_native
+../../../target/release/zz clean
+if ../../../target/release/zz run; then
+    echo "$PWD" passed with native-z3
+else
+    echo "$PWD" failed with native-z3
+    exit 1
+fi
+
+
 
 echo
 echo all passed
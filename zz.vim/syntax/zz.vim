@@ -18,7 +18,7 @@ syn keyword zzKeywords          mut as
 syn keyword zzSelf              self
 syn keyword zzDanger            unsafe
 syn keyword zzOperator          len safe static_attest static_assert nullterm
-syn keyword zzSSA1              where
+syn keyword zzSSA1              where invariant decreases
 syn keyword zzSSA2              model
 
 syn region zzCommentLineDoc     start="//\%(//\@!\|!\)"         end="$"  contains=@Spell